@@ -13,13 +13,36 @@ use url::Url;
 type GpuiHttpClient = Arc<dyn gpui::http_client::HttpClient>;
 type GpuiHttpResponse = gpui::http_client::Response<AsyncBody>;
 
+/// Hooks into the requests made through a [`GpuiHttpWrapper`].
+pub trait RequestObserver: Send + Sync {
+    /// Called before a request is sent, allowing it to be modified.
+    fn prepare(&self, _request: &mut Request<Vec<u8>>) {}
+
+    /// Called with the status of the response, before any of its body is received.
+    fn on_response(&self, _status: StatusCode) {}
+
+    /// Called with every chunk of a response body as it is received.
+    fn on_chunk(&self, _chunk: &[u8]) {}
+}
+
+#[derive(Clone)]
 pub struct GpuiHttpWrapper {
     inner: GpuiHttpClient,
+    /// Called in the order they were added.
+    observers: Vec<Arc<dyn RequestObserver>>,
 }
 
 impl GpuiHttpWrapper {
     pub fn new(client: GpuiHttpClient) -> GpuiHttpWrapper {
-        Self { inner: client }
+        Self {
+            inner: client,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn observer(mut self, observer: Arc<dyn RequestObserver>) -> Self {
+        self.observers.push(observer);
+        self
     }
 }
 
@@ -27,12 +50,22 @@ impl GpuiHttpWrapper {
 impl HttpClient for GpuiHttpWrapper {
     async fn execute(
         &self,
-        request: Request<Vec<u8>>,
+        mut request: Request<Vec<u8>>,
     ) -> std::result::Result<Response, anyhow::Error> {
+        for observer in &self.observers {
+            observer.prepare(&mut request);
+        }
+
         let request = request.map(|this| AsyncBody::from_bytes(this.into()));
         let response = self.inner.send(request).await?;
 
-        Ok(Response::new(GpuiHttpResponseWrapper::new(response)))
+        for observer in &self.observers {
+            observer.on_response(response.status());
+        }
+
+        Ok(Response::new(
+            GpuiHttpResponseWrapper::new(response).observers(self.observers.clone()),
+        ))
     }
 }
 
@@ -58,11 +91,20 @@ impl DerefMut for GpuiHttpWrapper {
 
 pub struct GpuiHttpResponseWrapper {
     inner: gpui::http_client::Response<AsyncBody>,
+    observers: Vec<Arc<dyn RequestObserver>>,
 }
 
 impl GpuiHttpResponseWrapper {
     pub fn new(response: GpuiHttpResponse) -> GpuiHttpResponseWrapper {
-        Self { inner: response }
+        Self {
+            inner: response,
+            observers: Vec::new(),
+        }
+    }
+
+    fn observers(mut self, observers: Vec<Arc<dyn RequestObserver>>) -> Self {
+        self.observers = observers;
+        self
     }
 }
 
@@ -71,6 +113,11 @@ impl HttpResponse for GpuiHttpResponseWrapper {
     async fn bytes(self: Box<Self>) -> anyhow::Result<Bytes> {
         let mut buf = Vec::new();
         self.inner.into_body().read_to_end(&mut buf).await?;
+
+        for observer in &self.observers {
+            observer.on_chunk(&buf);
+        }
+
         Ok(Bytes::from(buf))
     }
 
@@ -88,6 +135,7 @@ impl HttpResponse for GpuiHttpResponseWrapper {
 
         struct BodyStream {
             body: AsyncBody,
+            observers: Vec<Arc<dyn RequestObserver>>,
         }
 
         impl Stream for BodyStream {
@@ -102,6 +150,9 @@ impl HttpResponse for GpuiHttpResponseWrapper {
                 match pinned.as_mut().poll_frame(cx) {
                     Poll::Ready(Some(Ok(frame))) => {
                         if let Ok(data) = frame.into_data() {
+                            for observer in &self.observers {
+                                observer.on_chunk(&data);
+                            }
                             Poll::Ready(Some(Ok(data)))
                         } else {
                             // A frame with no data is rare but valid
@@ -117,6 +168,7 @@ impl HttpResponse for GpuiHttpResponseWrapper {
 
         Box::pin(BodyStream {
             body: self.inner.into_body(),
+            observers: self.observers,
         })
     }
 
@@ -68,6 +68,42 @@ pub enum AstrumIconKind {
 
     #[assoc(path = "icons/download.svg")]
     Download,
+
+    #[assoc(path = "icons/upload.svg")]
+    Upload,
+
+    #[assoc(path = "icons/archive.svg")]
+    Archive,
+
+    #[assoc(path = "icons/restore.svg")]
+    Restore,
+
+    #[assoc(path = "icons/edit.svg")]
+    Edit,
+
+    #[assoc(path = "icons/regenerate.svg")]
+    Regenerate,
+
+    #[assoc(path = "icons/chevron_left.svg")]
+    ChevronLeft,
+
+    #[assoc(path = "icons/chevron_right.svg")]
+    ChevronRight,
+
+    #[assoc(path = "icons/copy.svg")]
+    Copy,
+
+    #[assoc(path = "icons/check.svg")]
+    Check,
+
+    #[assoc(path = "icons/attach.svg")]
+    Attach,
+
+    #[assoc(path = "icons/close.svg")]
+    Close,
+
+    #[assoc(path = "icons/file.svg")]
+    File,
 }
 
 impl Into<SharedString> for AstrumIconKind {
@@ -97,6 +133,9 @@ pub enum AstrumLogoKind {
     #[assoc(path = "logos/providers/openai.svg")]
     OpenAi,
 
+    #[assoc(path = "logos/providers/openai_compatible.svg")]
+    OpenAiCompatible,
+
     #[assoc(path = "logos/providers/xai.svg")]
     Xai,
 }
@@ -0,0 +1,829 @@
+use std::{collections::HashMap, ops::Range, time::Duration};
+
+use gpui::{
+    AnyElement, App, ClipboardItem, Context, Div, ElementId, Entity, FocusHandle, FontStyle,
+    FontWeight, HighlightStyle, KeyDownEvent, MouseButton, MouseDownEvent, MouseMoveEvent,
+    MouseUpEvent, Pixels, Point, Rgba, SharedString, StrikethroughStyle, StyledText, TextLayout,
+    UnderlineStyle, Window, combine_highlights, div, prelude::*, px,
+};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant},
+    theme::{ThemeExt, ThemeLayerKind},
+};
+
+use crate::{
+    RgbaExt,
+    assets::AstrumIconKind,
+    blocks::render_math,
+    utils::{
+        markdown::{
+            ColumnAlignment, InlineStyle, InlineText, ListMarker, MarkdownBlock, MarkdownBlockKind,
+            MarkdownDocument, is_openable_link,
+        },
+        math::MathNode,
+        syntax_highlighting::CodeHighlighter,
+    },
+};
+
+#[cfg(target_os = "macos")]
+const CODE_FONT_FAMILY: &str = "Menlo";
+#[cfg(target_os = "windows")]
+const CODE_FONT_FAMILY: &str = "Consolas";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const CODE_FONT_FAMILY: &str = "DejaVu Sans Mono";
+
+/// How far each level of a list is indented.
+const LIST_INDENT: Pixels = px(24.);
+
+/// How long the copy button of a code block shows that its code was copied.
+const COPIED_FEEDBACK_DURATION: Duration = Duration::from_secs(2);
+
+/// Renders markdown, keeping its text selectable across all of its blocks.
+///
+/// The source can be swapped on every render, e.g. while a reply streams in.
+/// Only the blocks at its end are parsed again when text was appended.
+#[derive(IntoElement)]
+pub struct Markdown {
+    id: ElementId,
+    source: SharedString,
+    text_size: Pixels,
+}
+
+impl Markdown {
+    pub fn new(id: impl Into<ElementId>, source: impl Into<SharedString>) -> Self {
+        Self {
+            id: id.into(),
+            source: source.into(),
+            text_size: px(14.),
+        }
+    }
+
+    pub fn text_size(mut self, text_size: Pixels) -> Self {
+        self.text_size = text_size;
+        self
+    }
+}
+
+/// A selection of the plain text of the rendered blocks.
+#[derive(Clone, Copy)]
+struct TextSelection {
+    anchor: usize,
+    head: usize,
+}
+
+impl TextSelection {
+    fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+}
+
+/// A block of text as it was laid out in the last render.
+struct RenderedText {
+    /// Where the text is in the plain text of all blocks.
+    range: Range<usize>,
+    layout: TextLayout,
+    /// Ranges of the text that are links, and where they go.
+    links: Vec<(Range<usize>, String)>,
+}
+
+struct CodeHighlights {
+    code: String,
+    dark: bool,
+    highlighter: CodeHighlighter,
+    highlights: Vec<(Range<usize>, HighlightStyle)>,
+}
+
+struct MarkdownState {
+    document: MarkdownDocument,
+    focus_handle: FocusHandle,
+    selection: Option<TextSelection>,
+    is_selecting: bool,
+    /// The text of every rendered block, one block per line and table cells split by tabs.
+    /// This is what gets selected and copied.
+    text: String,
+    rendered_texts: Vec<RenderedText>,
+    /// Highlights of each code block by block index, kept until its code changes.
+    code_highlights: HashMap<usize, CodeHighlights>,
+    /// The code block whose code was just copied.
+    copied_code_block: Option<usize>,
+}
+
+impl MarkdownState {
+    fn new(cx: &mut App) -> Self {
+        Self {
+            document: MarkdownDocument::default(),
+            focus_handle: cx.focus_handle(),
+            selection: None,
+            is_selecting: false,
+            text: String::new(),
+            rendered_texts: Vec::new(),
+            code_highlights: HashMap::new(),
+            copied_code_block: None,
+        }
+    }
+
+    /// The position in the plain text under a point of the window. Points between
+    /// blocks resolve to the closest text above them.
+    fn offset_for_position(&self, position: Point<Pixels>) -> Option<(usize, &RenderedText)> {
+        let rendered = self
+            .rendered_texts
+            .iter()
+            .find(|rendered| rendered.layout.bounds().contains(&position))
+            .or_else(|| {
+                self.rendered_texts
+                    .iter()
+                    .rfind(|rendered| rendered.layout.bounds().top() <= position.y)
+            })
+            .or_else(|| self.rendered_texts.first())?;
+
+        let local_offset = match rendered.layout.index_for_position(position) {
+            Ok(offset) | Err(offset) => offset.min(rendered.range.len()),
+        };
+
+        Some((rendered.range.start + local_offset, rendered))
+    }
+
+    fn selected_text(&self) -> Option<&str> {
+        let range = self.selection?.range();
+        (!range.is_empty()).then(|| self.text.get(range)).flatten()
+    }
+
+    fn word_range(&self, offset: usize) -> Range<usize> {
+        let is_word_char = |char: char| char.is_alphanumeric() || char == '_';
+
+        let start = self.text[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|(_, char)| is_word_char(*char))
+            .last()
+            .map_or(offset, |(ix, _)| ix);
+        let end = self.text[offset..]
+            .char_indices()
+            .find(|(_, char)| !is_word_char(*char))
+            .map_or(self.text.len(), |(ix, _)| offset + ix);
+
+        start..end
+    }
+
+    fn mouse_down(&mut self, event: &MouseDownEvent, window: &mut Window, cx: &mut Context<Self>) {
+        window.focus(&self.focus_handle, cx);
+
+        let Some((offset, rendered)) = self.offset_for_position(event.position) else {
+            return;
+        };
+        let block_range = rendered.range.clone();
+
+        let range = match event.click_count {
+            2 => self.word_range(offset),
+            3.. => block_range,
+            _ => match (event.modifiers.shift, self.selection) {
+                (true, Some(selection)) => selection.anchor..offset,
+                _ => offset..offset,
+            },
+        };
+
+        self.selection = Some(TextSelection {
+            anchor: range.start,
+            head: range.end,
+        });
+        self.is_selecting = true;
+        cx.notify();
+    }
+
+    fn mouse_move(&mut self, event: &MouseMoveEvent, cx: &mut Context<Self>) {
+        if !self.is_selecting {
+            return;
+        }
+
+        if event.pressed_button != Some(MouseButton::Left) {
+            self.is_selecting = false;
+            return;
+        }
+
+        let Some((offset, _)) = self.offset_for_position(event.position) else {
+            return;
+        };
+
+        if let Some(selection) = &mut self.selection
+            && selection.head != offset
+        {
+            selection.head = offset;
+            cx.notify();
+        }
+    }
+
+    fn mouse_up(&mut self, event: &MouseUpEvent, cx: &mut Context<Self>) {
+        if !self.is_selecting {
+            return;
+        }
+        self.is_selecting = false;
+
+        if self.selected_text().is_some() {
+            return;
+        }
+
+        // A click rather than a drag follows the link under it.
+        self.selection = None;
+        cx.notify();
+
+        let Some((offset, rendered)) = self.offset_for_position(event.position) else {
+            return;
+        };
+        let local_offset = offset - rendered.range.start;
+
+        if let Some((_, url)) = rendered
+            .links
+            .iter()
+            .find(|(range, _)| range.contains(&local_offset))
+            && is_openable_link(url)
+        {
+            cx.open_url(url);
+        }
+    }
+
+    fn key_down(&mut self, event: &KeyDownEvent, cx: &mut Context<Self>) {
+        let keystroke = &event.keystroke;
+        if !keystroke.modifiers.secondary() {
+            return;
+        }
+
+        match keystroke.key.as_str() {
+            "c" => {
+                let Some(selected_text) = self.selected_text() else {
+                    return;
+                };
+                cx.write_to_clipboard(ClipboardItem::new_string(selected_text.to_string()));
+            }
+            "a" => {
+                self.selection = Some(TextSelection {
+                    anchor: 0,
+                    head: self.text.len(),
+                });
+                cx.notify();
+            }
+            _ => return,
+        }
+
+        cx.stop_propagation();
+    }
+
+    fn copy_code(&mut self, block_ix: usize, code: String, cx: &mut Context<Self>) {
+        cx.write_to_clipboard(ClipboardItem::new_string(code));
+
+        self.copied_code_block = Some(block_ix);
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            cx.background_executor()
+                .timer(COPIED_FEEDBACK_DURATION)
+                .await;
+
+            let _ = this.update(cx, |this, cx| {
+                if this.copied_code_block == Some(block_ix) {
+                    this.copied_code_block = None;
+                    cx.notify();
+                }
+            });
+        })
+        .detach();
+    }
+
+    /// Highlights code blocks that are new or changed since the last render. A block
+    /// that is still streaming is only highlighted from its last complete line on.
+    fn update_code_highlights(&mut self, dark: bool) {
+        let blocks = self.document.blocks();
+        self.code_highlights.retain(|block_ix, _| {
+            matches!(
+                blocks.get(*block_ix).map(|block| &block.kind),
+                Some(MarkdownBlockKind::CodeBlock { .. })
+            )
+        });
+
+        for (block_ix, block) in blocks.iter().enumerate() {
+            let MarkdownBlockKind::CodeBlock { language, code } = &block.kind else {
+                continue;
+            };
+
+            let cached = self
+                .code_highlights
+                .entry(block_ix)
+                .or_insert_with(|| CodeHighlights {
+                    code: String::new(),
+                    dark,
+                    highlighter: CodeHighlighter::default(),
+                    highlights: Vec::new(),
+                });
+            if cached.dark == dark && &cached.code == code {
+                continue;
+            }
+
+            cached.highlights = cached
+                .highlighter
+                .highlight(code, language.as_deref(), dark);
+            cached.code = code.clone();
+            cached.dark = dark;
+        }
+    }
+}
+
+/// The colors and sizes the blocks are rendered with.
+struct MarkdownStyle {
+    text_size: Pixels,
+    caption_size: Pixels,
+    text_color: Rgba,
+    secondary_text_color: Rgba,
+    link_color: Rgba,
+    selection_color: Rgba,
+    code_background_color: Rgba,
+    border_color: Rgba,
+    dark: bool,
+}
+
+impl MarkdownStyle {
+    fn new(text_size: Pixels, cx: &App) -> Self {
+        let colors = &cx.get_theme().variants.active(cx).colors;
+        let background_color = colors.background.primary;
+
+        Self {
+            text_size,
+            caption_size: cx.get_theme().layout.text.default_font.sizes.caption,
+            text_color: colors.text.primary,
+            secondary_text_color: colors.text.secondary,
+            link_color: colors.accent.primary,
+            selection_color: colors.accent.primary.alpha(0.3),
+            code_background_color: ThemeLayerKind::Tertiary.resolve(cx),
+            border_color: ThemeLayerKind::Tertiary.next().resolve(cx),
+            dark: background_color.r + background_color.g + background_color.b < 1.5,
+        }
+    }
+
+    fn inline_highlight(&self, style: &InlineStyle) -> HighlightStyle {
+        match style {
+            InlineStyle::Strong => HighlightStyle {
+                font_weight: Some(FontWeight::BOLD),
+                ..Default::default()
+            },
+            InlineStyle::Emphasis => HighlightStyle {
+                font_style: Some(FontStyle::Italic),
+                ..Default::default()
+            },
+            InlineStyle::Strikethrough => HighlightStyle {
+                strikethrough: Some(StrikethroughStyle {
+                    thickness: px(1.),
+                    color: None,
+                }),
+                ..Default::default()
+            },
+            InlineStyle::Code => HighlightStyle {
+                background_color: Some(self.code_background_color.into()),
+                ..Default::default()
+            },
+            InlineStyle::Link(_) => HighlightStyle {
+                color: Some(self.link_color.into()),
+                underline: Some(UnderlineStyle {
+                    thickness: px(1.),
+                    color: None,
+                    wavy: false,
+                }),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Lays out the blocks of a document, collecting their plain text as it goes.
+struct BlocksRenderer<'a> {
+    id: &'a ElementId,
+    state: &'a Entity<MarkdownState>,
+    style: &'a MarkdownStyle,
+    selection: Option<Range<usize>>,
+    code_highlights: &'a HashMap<usize, CodeHighlights>,
+    copied_code_block: Option<usize>,
+    text: String,
+    rendered_texts: Vec<RenderedText>,
+}
+
+impl BlocksRenderer<'_> {
+    fn render_block(&mut self, block_ix: usize, block: &MarkdownBlock) -> Div {
+        let content = match &block.kind {
+            MarkdownBlockKind::Paragraph(text) => self.render_text(text, '\n'),
+            MarkdownBlockKind::Heading { level, text } => {
+                let scale = match level {
+                    1 => 1.5,
+                    2 => 1.3,
+                    3 => 1.15,
+                    _ => 1.,
+                };
+
+                self.render_text(text, '\n')
+                    .text_size(self.style.text_size * scale)
+                    .font_weight(FontWeight::SEMIBOLD)
+            }
+            MarkdownBlockKind::CodeBlock { language, code } => {
+                self.render_code_block(block_ix, language.as_deref(), code)
+            }
+            MarkdownBlockKind::Table {
+                alignments,
+                header,
+                rows,
+            } => self.render_table(alignments, header, rows),
+            MarkdownBlockKind::Math { source, node } => self.render_math(source, node.as_ref()),
+            MarkdownBlockKind::Rule => div()
+                .w_full()
+                .h(px(1.))
+                .my(px(4.))
+                .bg(self.style.border_color),
+        };
+
+        let indent = LIST_INDENT * block.list_depth as f32;
+        let content = div()
+            .relative()
+            .w_full()
+            .pl(indent)
+            .child(content)
+            .when_some(block.marker, |this, marker| {
+                let marker = match marker {
+                    ListMarker::Bullet => SharedString::from("•"),
+                    ListMarker::Ordered(number) => format!("{number}.").into(),
+                    ListMarker::Task { checked: false } => SharedString::from("☐"),
+                    ListMarker::Task { checked: true } => SharedString::from("☑"),
+                };
+
+                this.child(
+                    div()
+                        .absolute()
+                        .top_0()
+                        .left(indent - LIST_INDENT)
+                        .w(LIST_INDENT)
+                        .text_color(self.style.secondary_text_color)
+                        .child(marker),
+                )
+            });
+
+        (0..block.quote_depth).fold(content, |content, _| {
+            div()
+                .w_full()
+                .pl(px(12.))
+                .border_l(px(3.))
+                .border_color(self.style.border_color)
+                .text_color(self.style.secondary_text_color)
+                .child(content)
+        })
+    }
+
+    /// Renders selectable text, followed in the plain text by `separator`.
+    fn render_text(&mut self, text: &InlineText, separator: char) -> Div {
+        let highlights = text
+            .spans
+            .iter()
+            .map(|(range, style)| (range.clone(), self.style.inline_highlight(style)))
+            .collect::<Vec<_>>();
+
+        let links = text
+            .spans
+            .iter()
+            .filter_map(|(range, style)| match style {
+                InlineStyle::Link(url) => Some((range.clone(), url.clone())),
+                _ => None,
+            })
+            .collect();
+
+        self.render_highlighted_text(&text.text, highlights, links, separator)
+    }
+
+    fn render_highlighted_text(
+        &mut self,
+        text: &str,
+        highlights: Vec<(Range<usize>, HighlightStyle)>,
+        links: Vec<(Range<usize>, String)>,
+        separator: char,
+    ) -> Div {
+        let start = self.text.len();
+        self.text.push_str(text);
+        let range = start..self.text.len();
+        self.text.push(separator);
+
+        let selection_highlight = self
+            .selection
+            .as_ref()
+            .map(|selection| selection.start.max(range.start)..selection.end.min(range.end))
+            .filter(|selected| !selected.is_empty())
+            .map(|selected| {
+                (
+                    selected.start - range.start..selected.end - range.start,
+                    HighlightStyle {
+                        background_color: Some(self.style.selection_color.into()),
+                        ..Default::default()
+                    },
+                )
+            });
+
+        let styled_text = StyledText::new(SharedString::from(text.to_string()))
+            .with_highlights(combine_highlights(highlights, selection_highlight));
+
+        self.rendered_texts.push(RenderedText {
+            range,
+            layout: styled_text.layout().clone(),
+            links,
+        });
+
+        div().w_full().cursor_text().child(styled_text)
+    }
+
+    fn render_code_block(&mut self, block_ix: usize, language: Option<&str>, code: &str) -> Div {
+        let highlights = self
+            .code_highlights
+            .get(&block_ix)
+            .map(|cached| cached.highlights.clone())
+            .unwrap_or_default();
+
+        let is_copied = self.copied_code_block == Some(block_ix);
+        let copy_button = {
+            let state = self.state.clone();
+            let code = code.to_string();
+
+            Button::new(self.id.with_suffix(format!("copy_code_btn_{block_ix}")))
+                .variant(ButtonVariant::SecondaryGhost)
+                .icon(match is_copied {
+                    true => AstrumIconKind::Check,
+                    false => AstrumIconKind::Copy,
+                })
+                .icon_size(px(12.))
+                .p(px(6.))
+                .rounded(px(6.))
+                .on_click(move |_event, _window, cx| {
+                    let code = code.clone();
+                    state.update(cx, |state, cx| state.copy_code(block_ix, code, cx));
+                })
+        };
+
+        div()
+            .w_full()
+            .flex()
+            .flex_col()
+            .rounded(px(8.))
+            .bg(self.style.code_background_color)
+            .border(px(1.))
+            .border_color(self.style.border_color)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .pl(px(12.))
+                    .pr(px(4.))
+                    .pt(px(4.))
+                    .text_size(self.style.caption_size)
+                    .text_color(self.style.secondary_text_color)
+                    .child(language.unwrap_or("text").to_string())
+                    .child(copy_button),
+            )
+            .child(
+                div()
+                    .w_full()
+                    .px(px(12.))
+                    .pb(px(10.))
+                    .font_family(CODE_FONT_FAMILY)
+                    .text_size(self.style.text_size * 0.9)
+                    .child(self.render_highlighted_text(code, highlights, Vec::new(), '\n')),
+            )
+    }
+
+    /// Renders display math, or its source if it couldn't be parsed. Either way, the
+    /// source is what gets copied.
+    fn render_math(&mut self, source: &str, node: Option<&MathNode>) -> Div {
+        let source = source.trim();
+
+        // Labelled, so the source isn't mistaken for what the model meant to show.
+        let Some(node) = node else {
+            return div()
+                .w_full()
+                .flex()
+                .flex_col()
+                .rounded(px(8.))
+                .bg(self.style.code_background_color)
+                .border(px(1.))
+                .border_color(self.style.border_color)
+                .child(
+                    div()
+                        .pl(px(12.))
+                        .pt(px(6.))
+                        .text_size(self.style.caption_size)
+                        .text_color(self.style.secondary_text_color)
+                        .child("Math that couldn't be rendered"),
+                )
+                .child(
+                    div()
+                        .w_full()
+                        .px(px(12.))
+                        .pb(px(10.))
+                        .font_family(CODE_FONT_FAMILY)
+                        .text_size(self.style.text_size * 0.9)
+                        .child(self.render_highlighted_text(source, Vec::new(), Vec::new(), '\n')),
+                );
+        };
+
+        let start = self.text.len();
+        self.text.push_str(source);
+        let range = start..self.text.len();
+        self.text.push('\n');
+
+        let is_selected = self
+            .selection
+            .as_ref()
+            .is_some_and(|selection| selection.start < range.end && range.start < selection.end);
+
+        div()
+            .w_full()
+            .flex()
+            .justify_center()
+            .py(px(4.))
+            .overflow_x_hidden()
+            .when(is_selected, |this| this.bg(self.style.selection_color))
+            .child(render_math(
+                node,
+                self.style.text_size * 1.1,
+                self.style.text_color,
+            ))
+    }
+
+    fn render_table(
+        &mut self,
+        alignments: &[ColumnAlignment],
+        header: &[InlineText],
+        rows: &[Vec<InlineText>],
+    ) -> Div {
+        let style = self.style;
+        let rows = std::iter::once(header).chain(rows.iter().map(Vec::as_slice));
+
+        let rendered_rows = rows
+            .enumerate()
+            .map(|(row_ix, cells)| {
+                let cell_count = cells.len();
+                let cells = cells
+                    .iter()
+                    .enumerate()
+                    .map(|(column_ix, cell)| {
+                        let separator = if column_ix + 1 < cell_count {
+                            '\t'
+                        } else {
+                            '\n'
+                        };
+                        let text = self.render_text(cell, separator);
+
+                        let text = match alignments.get(column_ix).copied().unwrap_or_default() {
+                            ColumnAlignment::Left => text,
+                            ColumnAlignment::Center => text.text_center(),
+                            ColumnAlignment::Right => text.text_right(),
+                        };
+
+                        div()
+                            .flex_1()
+                            .min_w_0()
+                            .px(px(10.))
+                            .py(px(6.))
+                            .when(column_ix > 0, |this| {
+                                this.border_l(px(1.)).border_color(style.border_color)
+                            })
+                            .child(text)
+                    })
+                    .collect::<Vec<_>>();
+
+                div()
+                    .w_full()
+                    .flex()
+                    .when(row_ix == 0, |this| {
+                        this.font_weight(FontWeight::SEMIBOLD)
+                            .bg(style.code_background_color)
+                    })
+                    .when(row_ix > 0, |this| {
+                        this.border_t(px(1.)).border_color(style.border_color)
+                    })
+                    .children(cells)
+            })
+            .collect::<Vec<_>>();
+
+        div()
+            .w_full()
+            .flex()
+            .flex_col()
+            .rounded(px(6.))
+            .overflow_hidden()
+            .border(px(1.))
+            .border_color(style.border_color)
+            .children(rendered_rows)
+    }
+}
+
+impl RenderOnce for Markdown {
+    fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
+        let state = window.use_keyed_state(self.id.with_suffix("state"), cx, |_window, cx| {
+            MarkdownState::new(cx)
+        });
+
+        let style = MarkdownStyle::new(self.text_size, cx);
+
+        let (blocks, focus_handle) = state.update(cx, |state, cx| {
+            state.document.set_source(&self.source);
+            state.update_code_highlights(style.dark);
+
+            let entity = cx.entity();
+            let mut renderer = BlocksRenderer {
+                id: &self.id,
+                state: &entity,
+                style: &style,
+                selection: state.selection.map(|selection| selection.range()),
+                code_highlights: &state.code_highlights,
+                copied_code_block: state.copied_code_block,
+                text: String::new(),
+                rendered_texts: Vec::new(),
+            };
+
+            let mut previous_block: Option<&MarkdownBlock> = None;
+            let blocks = state
+                .document
+                .blocks()
+                .iter()
+                .enumerate()
+                .map(|(block_ix, block)| {
+                    // Items of the same list sit closer together than other blocks.
+                    let spacing = match previous_block {
+                        None => px(0.),
+                        Some(previous) if previous.list_depth > 0 && block.list_depth > 0 => px(4.),
+                        Some(_) => px(12.),
+                    };
+                    previous_block = Some(block);
+
+                    renderer
+                        .render_block(block_ix, block)
+                        .pt(spacing)
+                        .into_any_element()
+                })
+                .collect::<Vec<AnyElement>>();
+
+            let BlocksRenderer {
+                text,
+                rendered_texts,
+                ..
+            } = renderer;
+
+            // Keeps the selection within the text if it got shorter.
+            if let Some(selection) = &mut state.selection {
+                selection.anchor = selection.anchor.min(text.len());
+                selection.head = selection.head.min(text.len());
+            }
+
+            state.text = text;
+            state.rendered_texts = rendered_texts;
+
+            (blocks, state.focus_handle.clone())
+        });
+
+        div()
+            .id(self.id.clone())
+            .w_full()
+            .flex()
+            .flex_col()
+            .text_size(self.text_size)
+            .track_focus(&focus_handle)
+            .on_key_down({
+                let state = state.clone();
+                move |event, _window, cx| {
+                    state.update(cx, |state, cx| state.key_down(event, cx));
+                }
+            })
+            .on_mouse_down(MouseButton::Left, {
+                let state = state.clone();
+                move |event, window, cx| {
+                    state.update(cx, |state, cx| state.mouse_down(event, window, cx));
+                }
+            })
+            .on_mouse_move({
+                let state = state.clone();
+                move |event, _window, cx| {
+                    state.update(cx, |state, cx| state.mouse_move(event, cx));
+                }
+            })
+            .on_mouse_up(MouseButton::Left, {
+                let state = state.clone();
+                move |event, _window, cx| {
+                    state.update(cx, |state, cx| state.mouse_up(event, cx));
+                }
+            })
+            .on_mouse_up_out(MouseButton::Left, {
+                let state = state.clone();
+                move |_event, _window, cx| {
+                    state.update(cx, |state, _cx| state.is_selecting = false);
+                }
+            })
+            .on_mouse_down_out(move |_event, _window, cx| {
+                state.update(cx, |state, cx| {
+                    if state.selection.take().is_some() {
+                        cx.notify();
+                    }
+                });
+            })
+            .children(blocks)
+    }
+}
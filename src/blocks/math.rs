@@ -0,0 +1,246 @@
+use gpui::{AnyElement, Div, Pixels, Rgba, div, prelude::*, px};
+
+use crate::utils::math::MathNode;
+
+/// How much smaller scripts, and the parts of fractions, are than what they're attached to.
+const SCRIPT_SCALE: f32 = 0.75;
+
+/// The smallest scripts get, relative to the text size.
+const MIN_SCRIPT_SCALE: f32 = 0.5;
+
+/// Symbols drawn bigger than the text around them.
+const LARGE_OPERATORS: &[&str] = &["∑", "∏", "∐", "⋃", "⋂", "⨁", "⨂", "∫", "∬", "∭", "∮"];
+
+/// Lays out a parsed formula, centering each part vertically on the ones beside it.
+pub fn render_math(node: &MathNode, text_size: Pixels, color: Rgba) -> AnyElement {
+    let renderer = MathRenderer { text_size, color };
+
+    div()
+        .flex()
+        .items_center()
+        .text_size(text_size)
+        .child(renderer.render(node, text_size))
+        .into_any_element()
+}
+
+struct MathRenderer {
+    text_size: Pixels,
+    color: Rgba,
+}
+
+impl MathRenderer {
+    fn script_size(&self, size: Pixels) -> Pixels {
+        (size * SCRIPT_SCALE).max(self.text_size * MIN_SCRIPT_SCALE)
+    }
+
+    fn render(&self, node: &MathNode, size: Pixels) -> Div {
+        match node {
+            MathNode::Identifier(identifier) => {
+                div().text_size(size).italic().child(identifier.clone())
+            }
+            MathNode::Text(text) if LARGE_OPERATORS.contains(&text.as_str()) => {
+                div().text_size(size * 1.6).child(text.clone())
+            }
+            MathNode::Text(text) => div().text_size(size).child(text.clone()),
+            MathNode::Operator(operator) => div()
+                .text_size(size)
+                .px(size * 0.25)
+                .child(operator.clone()),
+            MathNode::Space(ems) => div().w(size * *ems),
+            MathNode::Row(nodes) => div()
+                .flex()
+                .items_center()
+                .children(nodes.iter().map(|node| self.render(node, size))),
+            MathNode::Fraction {
+                numerator,
+                denominator,
+                line,
+            } => {
+                let part_size = self.script_size(size);
+
+                div()
+                    .flex()
+                    .flex_col()
+                    .items_center()
+                    .px(size * 0.15)
+                    .child(self.render(numerator, part_size))
+                    .child(
+                        div()
+                            .w_full()
+                            .h(px(1.))
+                            .my(px(2.))
+                            .when(*line, |this| this.bg(self.color)),
+                    )
+                    .child(self.render(denominator, part_size))
+            }
+            MathNode::Root { index, radicand } => div()
+                .flex()
+                .items_end()
+                .when_some(index.as_ref(), |this, index| {
+                    this.child(
+                        div()
+                            .self_start()
+                            .mr(size * -0.3)
+                            .child(self.render(index, self.script_size(self.script_size(size)))),
+                    )
+                })
+                .child(div().text_size(size * height(radicand).max(1.)).child("√"))
+                .child(
+                    div()
+                        .pt(px(2.))
+                        .border_t(px(1.))
+                        .border_color(self.color)
+                        .child(self.render(radicand, size)),
+                ),
+            MathNode::Scripts {
+                base,
+                superscript,
+                subscript,
+                limits: true,
+            } => div()
+                .flex()
+                .flex_col()
+                .items_center()
+                .when_some(superscript.as_ref(), |this, superscript| {
+                    this.child(self.render(superscript, self.script_size(size)))
+                })
+                .child(self.render(base, size))
+                .when_some(subscript.as_ref(), |this, subscript| {
+                    this.child(self.render(subscript, self.script_size(size)))
+                }),
+            MathNode::Scripts {
+                base,
+                superscript,
+                subscript,
+                limits: false,
+            } => {
+                let script_size = self.script_size(size);
+                // Leaves room for the missing script, so the one that's there sits above
+                // or below the middle of the base.
+                let spacer = || div().h(script_size);
+
+                div()
+                    .flex()
+                    .items_center()
+                    .child(self.render(base, size))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .child(match superscript {
+                                Some(superscript) => self.render(superscript, script_size),
+                                None => spacer(),
+                            })
+                            .child(match subscript {
+                                Some(subscript) => self.render(subscript, script_size),
+                                None => spacer(),
+                            }),
+                    )
+            }
+            MathNode::Delimited { open, close, body } => {
+                let delimiter_size = size * height(body).max(1.);
+
+                div()
+                    .flex()
+                    .items_center()
+                    .when(!open.is_empty(), |this| {
+                        this.child(div().text_size(delimiter_size).child(open.clone()))
+                    })
+                    .child(self.render(body, size))
+                    .when(!close.is_empty(), |this| {
+                        this.child(div().text_size(delimiter_size).child(close.clone()))
+                    })
+            }
+            MathNode::Matrix(rows) => {
+                let columns = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+
+                div()
+                    .grid()
+                    .grid_cols(columns as u16)
+                    .gap(size * 0.5)
+                    .px(size * 0.2)
+                    .children(rows.iter().flat_map(|row| {
+                        (0..columns).map(move |column_ix| {
+                            div()
+                                .flex()
+                                .items_center()
+                                .justify_center()
+                                .when_some(row.get(column_ix), |this, cell| {
+                                    this.child(self.render(cell, size))
+                                })
+                        })
+                    }))
+            }
+            MathNode::Accent { accent, body } => {
+                let accent = match accent {
+                    '\u{302}' => "^",
+                    '\u{303}' => "~",
+                    '\u{307}' => "˙",
+                    '\u{308}' => "¨",
+                    '\u{20d7}' => "→",
+                    // Bars are drawn as a line over the whole body.
+                    _ => "",
+                };
+
+                div()
+                    .flex()
+                    .flex_col()
+                    .items_center()
+                    .when(accent.is_empty(), |this| {
+                        this.child(div().w_full().h(px(1.)).mb(px(1.)).bg(self.color))
+                    })
+                    .when(!accent.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .h(size * 0.5)
+                                .flex()
+                                .items_end()
+                                .text_size(size * 0.8)
+                                .child(accent),
+                        )
+                    })
+                    .child(self.render(body, size))
+            }
+        }
+    }
+}
+
+/// Roughly how many lines of text a node is as tall as, to size the delimiters and
+/// radical signs around it.
+fn height(node: &MathNode) -> f32 {
+    match node {
+        MathNode::Identifier(_)
+        | MathNode::Text(_)
+        | MathNode::Operator(_)
+        | MathNode::Space(_) => 1.,
+        MathNode::Row(nodes) => nodes.iter().map(height).fold(1., f32::max),
+        MathNode::Fraction {
+            numerator,
+            denominator,
+            ..
+        } => (height(numerator) + height(denominator)) * SCRIPT_SCALE,
+        MathNode::Root { radicand, .. } => height(radicand),
+        MathNode::Scripts {
+            base,
+            superscript,
+            subscript,
+            limits,
+        } => {
+            let scripts = [superscript, subscript]
+                .into_iter()
+                .flatten()
+                .map(|script| height(script) * SCRIPT_SCALE);
+
+            match limits {
+                true => height(base) + scripts.sum::<f32>(),
+                false => height(base).max(scripts.sum::<f32>()),
+            }
+        }
+        MathNode::Delimited { body, .. } => height(body),
+        MathNode::Matrix(rows) => rows
+            .iter()
+            .map(|row| row.iter().map(height).fold(1., f32::max))
+            .sum(),
+        MathNode::Accent { body, .. } => height(body) + 0.3,
+    }
+}
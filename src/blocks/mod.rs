@@ -5,3 +5,12 @@ pub mod models_menu;
 
 mod model_picker;
 pub use model_picker::ModelPicker;
+
+mod parameters_form;
+pub use parameters_form::ParametersForm;
+
+mod markdown;
+pub use markdown::Markdown;
+
+mod math;
+pub use math::render_math;
@@ -16,7 +16,11 @@ use gpui_tesserae::{
 };
 use smol::lock::RwLock;
 
-use crate::{Managers, managers::Provider, managers::UniqueId, utils::FrontInsertMap};
+use crate::{
+    Managers,
+    managers::{DbError, Provider, ProviderOptions, ProviderParams, UniqueId},
+    utils::FrontInsertMap,
+};
 
 /// Minimum interval between model fetches per provider (in seconds)
 const MODEL_FETCH_COOLDOWN_SECS: u64 = 120;
@@ -329,6 +333,20 @@ pub fn create_models_select_state(
                     selection.provider_name,
                 );
                 managers.models.set_current_model(cx, selection.model_id);
+
+                // The open chat remembers its own model. Without one, the default changes.
+                let model_choice = managers.models.current_model_choice(cx);
+                match managers.chats.get_current_chat(cx) {
+                    Ok(Some(current_chat)) => {
+                        let result = current_chat.update(cx, |current_chat, cx| {
+                            current_chat.set_model(cx, model_choice)
+                        });
+                        if let Err(err) = result {
+                            error!("failed to save the model of the chat: {err}");
+                        }
+                    }
+                    _ => managers.models.save_current_as_default(cx),
+                }
             }
 
             state.hide_menu(cx);
@@ -384,6 +402,10 @@ pub enum ProviderConfigChange {
     Url(String),
     /// API key may have changed (None = cleared)
     ApiKey(Option<String>),
+    /// Extra request headers or query parameters may have changed
+    Params(ProviderParams),
+    /// Kind-specific options may have changed
+    Options(ProviderOptions),
 }
 
 /// Refetches models for a provider. For `Url` and `ApiKey` changes, checks if
@@ -393,7 +415,7 @@ pub fn refetch_provider_models(
     provider_id: UniqueId,
     config_change: ProviderConfigChange,
     cx: &mut App,
-) {
+) -> Result<(), DbError> {
     let models_cache = managers.read_arc_blocking().models.models_cache.clone();
 
     if !matches!(config_change, ProviderConfigChange::Create) {
@@ -406,13 +428,14 @@ pub fn refetch_provider_models(
         );
 
         if !should_proceed {
-            return;
+            return Ok(());
         }
 
-        apply_config_change(&managers, &provider_id, &config_change, cx);
+        apply_config_change(&managers, &provider_id, &config_change, cx)?;
     }
 
     spawn_fetch_models(managers, provider_id, models_cache, cx);
+    Ok(())
 }
 
 fn check_and_update_config_cache(
@@ -437,6 +460,17 @@ fn check_and_update_config_cache(
         .get_provider_api_key(cx, provider_id)
         .unwrap_or_default();
 
+    let (current_params, current_options) = cx
+        .read_entity(&managers_guard.models.providers, |providers, cx| {
+            providers.get(provider_id).map(|provider| {
+                (
+                    provider.params.read(cx).clone(),
+                    provider.options.read(cx).clone(),
+                )
+            })
+        })
+        .unwrap_or_default();
+
     models_cache.update(cx, |cache, _| {
         let config_cache =
             cache.get_or_create_config_cache(provider_id, &current_url, &current_api_key);
@@ -458,6 +492,13 @@ fn check_and_update_config_cache(
                 }
                 changed
             }
+            ProviderConfigChange::Params(params) => {
+                // Params read from the inputs have new ids until they're matched up.
+                let mut params = params.clone();
+                params.keep_ids(&current_params);
+                params != current_params
+            }
+            ProviderConfigChange::Options(options) => *options != current_options,
         }
     })
 }
@@ -467,24 +508,37 @@ fn apply_config_change(
     provider_id: &UniqueId,
     config_change: &ProviderConfigChange,
     cx: &mut App,
-) {
+) -> Result<(), DbError> {
     let mut managers_guard = managers.write_arc_blocking();
     match config_change {
         ProviderConfigChange::Create => {}
         ProviderConfigChange::Url(url) => {
-            let _ = managers_guard
+            managers_guard
                 .models
-                .edit_provider_url(cx, provider_id.clone(), url.clone());
+                .edit_provider_url(cx, provider_id.clone(), url.clone())?;
         }
         ProviderConfigChange::ApiKey(api_key) => {
-            let _ = managers_guard.models.edit_provider_api_key(
+            managers_guard.models.edit_provider_api_key(
                 cx,
                 provider_id.clone(),
                 api_key.clone(),
-            );
+            )?;
+        }
+        ProviderConfigChange::Params(params) => {
+            managers_guard
+                .models
+                .edit_provider_params(cx, provider_id.clone(), params.clone())?;
+        }
+        ProviderConfigChange::Options(options) => {
+            managers_guard.models.edit_provider_options(
+                cx,
+                provider_id.clone(),
+                options.clone(),
+            )?;
         }
     }
     let _ = managers_guard.models.reinit_provider(cx, provider_id);
+    Ok(())
 }
 
 fn spawn_fetch_models(
@@ -515,10 +569,8 @@ fn spawn_fetch_models(
             "Fetching models for provider"
         );
 
-        match provider.inner.list_models().await {
-            Ok(models) => {
-                let model_ids: Vec<String> = models.iter().map(|m| m.id.clone()).collect();
-
+        match provider.list_model_ids().await {
+            Ok(model_ids) => {
                 let _ = models_cache.update(cx, |cache, _| {
                     cache.refresh_models_for_provider(provider_id, provider_name, model_ids);
                 });
@@ -570,9 +622,8 @@ pub fn prefetch_all_models(managers: Arc<RwLock<Managers>>, cx: &mut App) {
                 cx.read_entity(&provider.name, |name: &SharedString, _| name.to_string());
 
             // Fetch from API
-            match provider.inner.list_models().await {
-                Ok(models) => {
-                    let model_ids: Vec<String> = models.iter().map(|m| m.id.clone()).collect();
+            match provider.list_model_ids().await {
+                Ok(model_ids) => {
                     let provider_name_clone = provider_name.clone();
                     let provider_id_clone = provider_id.clone();
 
@@ -710,10 +761,9 @@ pub fn fetch_all_models_with_source(
             }
 
             // Fetch from API if not cached or stale
-            match provider.inner.list_models().await {
-                Ok(models) => {
+            match provider.list_model_ids().await {
+                Ok(model_ids) => {
                     // Cache the model IDs
-                    let model_ids: Vec<String> = models.iter().map(|m| m.id.clone()).collect();
                     let provider_name_clone = provider_name.clone();
                     let provider_id_clone = provider_id.clone();
 
@@ -721,15 +771,15 @@ pub fn fetch_all_models_with_source(
                         cache.refresh_models_for_provider(
                             provider_id_clone,
                             provider_name_clone,
-                            model_ids,
+                            model_ids.clone(),
                         );
                     });
 
                     let _ = cx.update(|cx| {
-                        for model in models {
+                        for model_id in model_ids {
                             let item = ModelSelectItem::new(
                                 &provider_name,
-                                model.id.clone(),
+                                model_id.clone(),
                                 provider_id.clone(),
                             );
 
@@ -739,7 +789,7 @@ pub fn fetch_all_models_with_source(
 
                             let provider_matches =
                                 current_provider_id.as_ref() == Some(&provider_id);
-                            let model_matches = current_model.as_ref() == Some(&model.id);
+                            let model_matches = current_model.as_ref() == Some(&model_id);
 
                             if provider_matches && model_matches {
                                 let _ = state.select_item(cx, item_name);
@@ -792,6 +842,20 @@ pub fn observe_providers_for_refresh(
             }
         }
 
+        let default_provider_id = managers
+            .models
+            .default_model
+            .read(cx)
+            .as_ref()
+            .map(|default_model| default_model.provider_id.clone());
+
+        if let Some(provider_id) = default_provider_id {
+            let provider_exists = providers.read(cx).get(&provider_id).is_some();
+            if !provider_exists {
+                managers.models.clear_default_selection(cx);
+            }
+        }
+
         // Check if chat_titles provider still exists, if not clear the manager selection
         let chat_titles_provider_id = managers
             .models
@@ -0,0 +1,137 @@
+use std::str::FromStr;
+
+use gpui::{App, AppContext, Div, ElementId, Entity, SharedString, div, prelude::*, px};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::Input,
+    primitives::{input::InputState, min_w0_wrapper},
+    theme::ThemeExt,
+};
+
+use crate::managers::GenerationParameters;
+
+/// Inputs for editing [`GenerationParameters`]. An empty input leaves its parameter unset.
+#[derive(Clone)]
+pub struct ParametersForm {
+    temperature: Entity<InputState>,
+    top_p: Entity<InputState>,
+    max_tokens: Entity<InputState>,
+    seed: Entity<InputState>,
+    /// One stop sequence per line.
+    stop: Entity<InputState>,
+}
+
+impl ParametersForm {
+    pub fn new(cx: &mut App, parameters: &GenerationParameters) -> Self {
+        let input = |value: Option<String>, cx: &mut App| {
+            cx.new(|cx| InputState::new(cx).initial_value(value.unwrap_or_default()))
+        };
+
+        Self {
+            temperature: input(parameters.temperature.map(|value| value.to_string()), cx),
+            top_p: input(parameters.top_p.map(|value| value.to_string()), cx),
+            max_tokens: input(parameters.max_tokens.map(|value| value.to_string()), cx),
+            seed: input(parameters.seed.map(|value| value.to_string()), cx),
+            stop: input(Some(parameters.stop.join("\n")), cx),
+        }
+    }
+
+    /// Reads the parameters out of the form, or explains which one is invalid.
+    pub fn parameters(&self, cx: &App) -> Result<GenerationParameters, SharedString> {
+        let stop = self
+            .stop
+            .read(cx)
+            .value()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(ToString::to_string)
+            .collect();
+
+        Ok(GenerationParameters {
+            temperature: parse_parameter(&self.temperature, "Temperature", cx)?,
+            top_p: parse_parameter(&self.top_p, "Top P", cx)?,
+            max_tokens: parse_parameter(&self.max_tokens, "Max tokens", cx)?,
+            stop,
+            seed: parse_parameter(&self.seed, "Seed", cx)?,
+        })
+    }
+
+    pub fn render(&self, id: &ElementId, cx: &App) -> Div {
+        let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+        let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+        let field = |label: &'static str, input: Input| {
+            div()
+                .flex_1()
+                .min_w(px(90.))
+                .flex()
+                .flex_col()
+                .gap(px(4.))
+                .child(
+                    min_w0_wrapper()
+                        .text_size(caption_size)
+                        .text_color(secondary_text_color)
+                        .child(label),
+                )
+                .child(input.w_full())
+        };
+
+        let number_input = |suffix: &str, input_state: &Entity<InputState>, placeholder| {
+            Input::new(id.with_suffix(suffix), input_state.clone()).placeholder(placeholder)
+        };
+
+        div()
+            .w_full()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(
+                div()
+                    .w_full()
+                    .flex()
+                    .flex_row()
+                    .flex_wrap()
+                    .gap(px(10.))
+                    .child(field(
+                        "Temperature",
+                        number_input("temperature_input", &self.temperature, "Default"),
+                    ))
+                    .child(field(
+                        "Top P",
+                        number_input("top_p_input", &self.top_p, "Default"),
+                    ))
+                    .child(field(
+                        "Max tokens",
+                        number_input("max_tokens_input", &self.max_tokens, "Default"),
+                    ))
+                    .child(field(
+                        "Seed",
+                        number_input("seed_input", &self.seed, "Random"),
+                    )),
+            )
+            .child(field(
+                "Stop sequences, one per line",
+                Input::new(id.with_suffix("stop_input"), self.stop.clone())
+                    .line_clamp(4)
+                    .placeholder("None"),
+            ))
+    }
+}
+
+fn parse_parameter<T: FromStr>(
+    input_state: &Entity<InputState>,
+    label: &str,
+    cx: &App,
+) -> Result<Option<T>, SharedString> {
+    let value = input_state.read(cx).value();
+    let value = value.trim();
+
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("{label} must be a number.").into())
+}
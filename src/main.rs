@@ -1,6 +1,7 @@
 use gpui::{
-    App, Application, Bounds, KeyBinding, Menu, MenuItem, SharedString, TitlebarOptions,
-    WindowBounds, WindowOptions, actions, point, prelude::*, px, size,
+    AnyWindowHandle, App, Application, AsyncApp, Bounds, KeyBinding, Menu, MenuItem, PromptLevel,
+    SharedString, TitlebarOptions, WindowBounds, WindowOptions, actions, point, prelude::*, px,
+    size,
 };
 use gpui_tesserae::{
     TesseraeAssets, assets,
@@ -71,13 +72,30 @@ fn main() {
                             ..Default::default()
                         },
                         |window, cx| {
+                            let window_handle = window.window_handle();
+
                             let chat_view = cx.new(move |cx| {
                                 let chat_view = ChatView::new("chat_view", managers);
 
-                                cx.spawn(async |chat_view, cx| {
-                                    let _ = chat_view.update(cx, |chat_view: &mut ChatView, cx| {
-                                        let _ = chat_view.managers.write_arc_blocking().init(cx);
+                                cx.spawn(async move |chat_view, cx| {
+                                    let init_result =
+                                        chat_view.update(cx, |chat_view: &mut ChatView, cx| {
+                                            chat_view.managers.write_arc_blocking().init(cx)
+                                        });
+
+                                    if let Ok(Err(err)) = init_result {
+                                        tracing::error!("failed to initialize database: {err:?}");
+                                        show_fatal_error(
+                                            window_handle,
+                                            "Astrum couldn't open its database",
+                                            err.to_string(),
+                                            cx,
+                                        )
+                                        .await;
+                                        return;
+                                    }
 
+                                    let _ = chat_view.update(cx, |chat_view: &mut ChatView, cx| {
                                         prefetch_all_models(chat_view.managers.clone(), cx);
 
                                         let http_client = cx.http_client();
@@ -126,6 +144,24 @@ fn main() {
         });
 }
 
+/// Shows an error that Astrum can't recover from, then quits once it is dismissed.
+async fn show_fatal_error(
+    window_handle: AnyWindowHandle,
+    message: &str,
+    detail: String,
+    cx: &mut AsyncApp,
+) {
+    let answer = cx.update_window(window_handle, |_, window, cx| {
+        window.prompt(PromptLevel::Critical, message, Some(&detail), &["Quit"], cx)
+    });
+
+    if let Ok(answer) = answer {
+        let _ = answer.await;
+    }
+
+    let _ = cx.update(|cx| cx.quit());
+}
+
 fn init_tab_indexing_actions(cx: &mut App) {
     cx.on_action(move |_: &TabNext, cx| {
         cx.defer(move |cx| {
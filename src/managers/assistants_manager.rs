@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use gpui::{App, AppContext, Entity, SharedString};
+use indexmap::IndexMap;
+use rusqlite::Connection;
+
+use crate::{
+    assets::AstrumIconKind,
+    managers::{DbError, GenerationParameters, Migration, UniqueId},
+};
+
+/// The icons an assistant can be given.
+pub const ASSISTANT_ICONS: [AstrumIconKind; 6] = [
+    AstrumIconKind::Chat,
+    AstrumIconKind::Think,
+    AstrumIconKind::Web,
+    AstrumIconKind::Search,
+    AstrumIconKind::Edit,
+    AstrumIconKind::Title,
+];
+
+/// A reusable preset that new chats can start from.
+#[derive(Clone)]
+pub struct Assistant {
+    pub name: String,
+    pub icon: SharedString,
+    pub system_prompt: Option<String>,
+    pub provider_id: Option<UniqueId>,
+    pub provider_name: Option<String>,
+    pub model: Option<String>,
+    pub parameters: GenerationParameters,
+}
+
+impl Default for Assistant {
+    fn default() -> Self {
+        Self {
+            name: String::from("New Assistant"),
+            icon: AstrumIconKind::Chat.into(),
+            system_prompt: None,
+            provider_id: None,
+            provider_name: None,
+            model: None,
+            parameters: GenerationParameters::default(),
+        }
+    }
+}
+
+pub struct AssistantsManager {
+    db_connection: Option<Arc<Connection>>,
+    pub assistants: Entity<IndexMap<UniqueId, Assistant>>,
+    /// The assistant the next new chat starts from.
+    pub new_chat_assistant_id: Entity<Option<UniqueId>>,
+}
+
+impl AssistantsManager {
+    pub const MIGRATIONS: &'static [Migration] = &[Migration::sql(
+        1,
+        "create assistants",
+        "
+        CREATE TABLE IF NOT EXISTS assistants (
+            id            TEXT PRIMARY KEY,
+            name          TEXT NOT NULL,
+            icon          TEXT NOT NULL,
+            system_prompt TEXT,
+            provider_id   TEXT,
+            provider_name TEXT,
+            model         TEXT,
+            parameters    TEXT NOT NULL DEFAULT '{}',
+            created_at    DATETIME NOT NULL,
+            edited_at     DATETIME NOT NULL
+        );
+        ",
+    )];
+
+    pub fn new(cx: &mut App) -> Self {
+        Self {
+            db_connection: None,
+            assistants: cx.new(|_cx| IndexMap::new()),
+            new_chat_assistant_id: cx.new(|_cx| None),
+        }
+    }
+
+    pub fn init(&mut self, cx: &mut App, db_connection: Arc<Connection>) -> Result<(), DbError> {
+        let assistants = load_assistants_from_db(&db_connection).map_err(DbError::SqliteError)?;
+
+        self.assistants.update(cx, |this, _cx| {
+            *this = assistants;
+        });
+
+        self.db_connection = Some(db_connection);
+
+        Ok(())
+    }
+
+    pub fn get<'a>(&'a self, cx: &'a App, assistant_id: &UniqueId) -> Option<&'a Assistant> {
+        self.assistants.read(cx).get(assistant_id)
+    }
+
+    /// The assistant the next new chat starts from, if it still exists.
+    pub fn get_new_chat_assistant<'a>(
+        &'a self,
+        cx: &'a App,
+    ) -> Option<(&'a UniqueId, &'a Assistant)> {
+        let assistant_id = self.new_chat_assistant_id.read(cx).as_ref()?;
+        self.assistants.read(cx).get_key_value(assistant_id)
+    }
+
+    pub fn set_new_chat_assistant(&self, cx: &mut App, assistant_id: Option<UniqueId>) {
+        self.new_chat_assistant_id.update(cx, |this, cx| {
+            *this = assistant_id;
+            cx.notify();
+        });
+    }
+
+    pub fn create_assistant(
+        &self,
+        cx: &mut App,
+        assistant: Assistant,
+    ) -> Result<UniqueId, DbError> {
+        let db_connection = self.db_connection()?;
+
+        let assistant_id = UniqueId::new();
+        let created_at = Utc::now().naive_utc();
+        let parameters =
+            serde_json::to_string(&assistant.parameters).map_err(DbError::SerializationError)?;
+
+        db_connection
+            .execute(
+                "INSERT INTO assistants (id, name, icon, system_prompt, provider_id, provider_name, model, parameters, created_at, edited_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+                (
+                    &assistant_id,
+                    &assistant.name,
+                    assistant.icon.as_ref(),
+                    &assistant.system_prompt,
+                    &assistant.provider_id,
+                    &assistant.provider_name,
+                    &assistant.model,
+                    &parameters,
+                    &created_at,
+                ),
+            )
+            .map_err(DbError::SqliteError)?;
+
+        self.assistants.update(cx, |assistants, cx| {
+            assistants.insert(assistant_id.clone(), assistant);
+            cx.notify();
+        });
+
+        Ok(assistant_id)
+    }
+
+    /// Saves changes to an assistant. Chats that were started from it keep their
+    /// own system prompt.
+    pub fn update_assistant(
+        &self,
+        cx: &mut App,
+        assistant_id: &UniqueId,
+        assistant: Assistant,
+    ) -> Result<(), DbError> {
+        let db_connection = self.db_connection()?;
+
+        let edited_at = Utc::now().naive_utc();
+        let parameters =
+            serde_json::to_string(&assistant.parameters).map_err(DbError::SerializationError)?;
+
+        db_connection
+            .execute(
+                "UPDATE assistants
+                SET name = ?1, icon = ?2, system_prompt = ?3, provider_id = ?4, provider_name = ?5, model = ?6, parameters = ?7, edited_at = ?8
+                WHERE id = ?9",
+                (
+                    &assistant.name,
+                    assistant.icon.as_ref(),
+                    &assistant.system_prompt,
+                    &assistant.provider_id,
+                    &assistant.provider_name,
+                    &assistant.model,
+                    &parameters,
+                    &edited_at,
+                    assistant_id,
+                ),
+            )
+            .map_err(DbError::SqliteError)?;
+
+        self.assistants.update(cx, |assistants, cx| {
+            if let Some(current_assistant) = assistants.get_mut(assistant_id) {
+                *current_assistant = assistant;
+            }
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    /// Deletes an assistant. Chats that were started from it are left as they are.
+    pub fn delete_assistant(&self, cx: &mut App, assistant_id: &UniqueId) -> Result<(), DbError> {
+        let db_connection = self.db_connection()?;
+
+        db_connection
+            .execute("DELETE FROM assistants WHERE id = ?1", [assistant_id])
+            .map_err(DbError::SqliteError)?;
+
+        self.assistants.update(cx, |assistants, cx| {
+            assistants.shift_remove(assistant_id);
+            cx.notify();
+        });
+
+        if self.new_chat_assistant_id.read(cx).as_ref() == Some(assistant_id) {
+            self.set_new_chat_assistant(cx, None);
+        }
+
+        Ok(())
+    }
+
+    fn db_connection(&self) -> Result<&Arc<Connection>, DbError> {
+        self.db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))
+    }
+}
+
+fn load_assistants_from_db(
+    db_connection: &Connection,
+) -> rusqlite::Result<IndexMap<UniqueId, Assistant>> {
+    let mut stmt = db_connection.prepare(
+        "SELECT id, name, icon, system_prompt, provider_id, provider_name, model, parameters
+        FROM assistants
+        ORDER BY created_at",
+    )?;
+
+    stmt.query_map([], |row| {
+        let parameters = row.get::<_, String>(7)?;
+
+        Ok((
+            UniqueId::from_string(row.get::<_, String>(0)?),
+            Assistant {
+                name: row.get(1)?,
+                icon: row.get::<_, String>(2)?.into(),
+                system_prompt: row.get(3)?,
+                provider_id: row.get::<_, Option<String>>(4)?.map(UniqueId::from_string),
+                provider_name: row.get(5)?,
+                model: row.get(6)?,
+                // Unknown or malformed parameters are dropped rather than failing to load.
+                parameters: serde_json::from_str(&parameters).unwrap_or_default(),
+            },
+        ))
+    })?
+    .collect()
+}
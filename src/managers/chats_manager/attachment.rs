@@ -0,0 +1,354 @@
+use std::{borrow::Cow, path::Path, sync::Arc};
+
+use anyml::models::Message;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use gpui::{Image, ImageFormat};
+use serde_json::{Value, json};
+
+use crate::managers::ProviderKind;
+
+/// The largest file that can be attached to a message, in bytes.
+pub const MAX_ATTACHMENT_SIZE: usize = 20 * 1024 * 1024;
+
+/// The most text a document may add to a message, in bytes, which is roughly 64k tokens.
+pub const MAX_DOCUMENT_TEXT_SIZE: usize = 256 * 1024;
+
+/// A file sent to the model along with a message.
+#[derive(Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub content: AttachmentContent,
+}
+
+#[derive(Clone)]
+pub enum AttachmentContent {
+    Image(Arc<Image>),
+    /// A text file or PDF. Only the text extracted from it is kept.
+    Document {
+        mime_type: String,
+        text: String,
+    },
+}
+
+impl Attachment {
+    /// Reads an attached file. Returns `None` for files that can't be sent to a model.
+    pub fn from_file(name: impl Into<String>, bytes: Vec<u8>) -> Option<Self> {
+        if bytes.len() > MAX_ATTACHMENT_SIZE {
+            return None;
+        }
+
+        let name = name.into();
+
+        let content = match image_format(&bytes) {
+            Some(format) => AttachmentContent::Image(Arc::new(Image::from_bytes(format, bytes))),
+            None if bytes.starts_with(b"%PDF") => AttachmentContent::Document {
+                mime_type: String::from("application/pdf"),
+                text: pdf_text(&bytes)?,
+            },
+            None => AttachmentContent::Document {
+                mime_type: text_mime_type(&name)?.to_string(),
+                text: decode_text(bytes)?,
+            },
+        };
+
+        if let AttachmentContent::Document { text, .. } = &content
+            && text.len() > MAX_DOCUMENT_TEXT_SIZE
+        {
+            return None;
+        }
+
+        Some(Self { name, content })
+    }
+
+    /// Restores an attachment written with [`Self::mime_type`], [`Self::data`] and
+    /// [`Self::text`].
+    pub fn from_stored(
+        name: String,
+        mime_type: String,
+        data: Option<Vec<u8>>,
+        text: Option<String>,
+    ) -> Option<Self> {
+        let content = match (ImageFormat::from_mime_type(&mime_type), data, text) {
+            (Some(format), Some(data), _) => {
+                AttachmentContent::Image(Arc::new(Image::from_bytes(format, data)))
+            }
+            (None, _, Some(text)) => AttachmentContent::Document { mime_type, text },
+            _ => return None,
+        };
+
+        Some(Self { name, content })
+    }
+
+    pub fn mime_type(&self) -> &str {
+        match &self.content {
+            AttachmentContent::Image(image) => image.format.mime_type(),
+            AttachmentContent::Document { mime_type, .. } => mime_type,
+        }
+    }
+
+    /// The file itself, which is only kept for images.
+    pub fn data(&self) -> Option<&[u8]> {
+        match &self.content {
+            AttachmentContent::Image(image) => Some(&image.bytes),
+            AttachmentContent::Document { .. } => None,
+        }
+    }
+
+    /// The text of a document.
+    pub fn text(&self) -> Option<&str> {
+        match &self.content {
+            AttachmentContent::Image(_) => None,
+            AttachmentContent::Document { text, .. } => Some(text),
+        }
+    }
+
+    /// Roughly how many tokens the text of a document takes up, at about four
+    /// characters a token.
+    pub fn estimated_tokens(&self) -> Option<usize> {
+        self.text().map(|text| text.chars().count().div_ceil(4))
+    }
+}
+
+/// Extracts the text of a PDF. Scanned PDFs without a text layer have none.
+fn pdf_text(bytes: &[u8]) -> Option<String> {
+    // The extractor panics on some malformed files rather than returning an error.
+    // Catching that relies on panics unwinding: with `panic = "abort"` in a profile,
+    // a malformed PDF would close the app instead.
+    let text = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(bytes))
+        .ok()?
+        .ok()?;
+
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// Reads a text file, rejecting binary files.
+fn decode_text(bytes: Vec<u8>) -> Option<String> {
+    let text = String::from_utf8(bytes).ok()?;
+
+    match text.contains('\0') {
+        true => None,
+        false => Some(text.trim_start_matches('\u{feff}').to_string()),
+    }
+}
+
+/// The type of a text document, from the extension of its name. Returns `None` for
+/// files that aren't meant to be read, e.g. lockfiles, or whose type isn't known.
+fn text_mime_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())?;
+
+    let mime_type = match extension.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "txt" | "text" | "log" | "tsv" | "xml" | "yaml" | "yml" | "toml" | "ini" | "tex"
+        | "rst" | "rs" | "py" | "js" | "jsx" | "ts" | "tsx" | "c" | "h" | "cpp" | "hpp" | "cc"
+        | "cs" | "go" | "java" | "kt" | "swift" | "rb" | "php" | "sh" | "sql" | "css" | "scss"
+        | "lua" | "r" | "dart" | "scala" | "zig" => "text/plain",
+        _ => return None,
+    };
+
+    Some(mime_type)
+}
+
+/// The format of an image that every provider accepts, detected from its first bytes.
+pub fn image_format(bytes: &[u8]) -> Option<ImageFormat> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some(ImageFormat::Png),
+        [0xFF, 0xD8, 0xFF, ..] => Some(ImageFormat::Jpeg),
+        [b'G', b'I', b'F', b'8', ..] => Some(ImageFormat::Gif),
+        _ if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) => {
+            Some(ImageFormat::Webp)
+        }
+        _ => None,
+    }
+}
+
+/// Writes a message for a chat request, in the format of the provider. Attached documents
+/// go before the text of the message, while messages without images are plain text for
+/// every provider.
+pub fn message_to_json(kind: ProviderKind, message: &Message, attachments: &[Attachment]) -> Value {
+    let role = message.role.as_str();
+    let content = content_with_documents(&message.content, attachments);
+
+    let mut images = attachments
+        .iter()
+        .filter_map(|attachment| match &attachment.content {
+            AttachmentContent::Image(image) => {
+                Some((image.format.mime_type(), BASE64.encode(&image.bytes)))
+            }
+            AttachmentContent::Document { .. } => None,
+        })
+        .peekable();
+
+    if images.peek().is_none() {
+        return json!({ "role": role, "content": content });
+    }
+
+    match kind {
+        // Ollama takes images next to the content, without their type.
+        ProviderKind::Ollama => json!({
+            "role": role,
+            "content": content,
+            "images": images.map(|(_, data)| data).collect::<Vec<_>>(),
+        }),
+        ProviderKind::OpenAi
+        | ProviderKind::OpenAiCompatible
+        | ProviderKind::Gemini
+        | ProviderKind::AzureOpenAi => {
+            let parts = text_part(&content)
+                .into_iter()
+                .chain(images.map(|(mime_type, data)| {
+                    json!({
+                        "type": "image_url",
+                        "image_url": { "url": format!("data:{mime_type};base64,{data}") },
+                    })
+                }))
+                .collect::<Vec<_>>();
+
+            json!({ "role": role, "content": parts })
+        }
+        // Anthropic recommends putting images before the text that refers to them.
+        ProviderKind::Anthropic => {
+            let parts = images
+                .map(|(mime_type, data)| {
+                    json!({
+                        "type": "image",
+                        "source": { "type": "base64", "media_type": mime_type, "data": data },
+                    })
+                })
+                .chain(text_part(&content))
+                .collect::<Vec<_>>();
+
+            json!({ "role": role, "content": parts })
+        }
+    }
+}
+
+/// Puts the text of each attached document before the content of a message, in a block
+/// that says which file it came from.
+fn content_with_documents<'a>(content: &'a str, attachments: &[Attachment]) -> Cow<'a, str> {
+    let documents = attachments
+        .iter()
+        .filter_map(|attachment| Some((attachment, attachment.text()?)))
+        .map(|(attachment, text)| {
+            format!(
+                "<attachment name=\"{}\" type=\"{}\">\n{}\n</attachment>",
+                attachment.name.replace('"', "'"),
+                attachment.mime_type(),
+                text.trim_end(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if documents.is_empty() {
+        return Cow::Borrowed(content);
+    }
+
+    let mut with_documents = documents.join("\n\n");
+    if !content.is_empty() {
+        with_documents.push_str("\n\n");
+        with_documents.push_str(content);
+    }
+
+    Cow::Owned(with_documents)
+}
+
+/// A text content part, left out when empty since some providers reject empty text.
+fn text_part(text: &str) -> Option<Value> {
+    (!text.is_empty()).then(|| json!({ "type": "text", "text": text }))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyml::MessageRole;
+
+    use super::*;
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    #[test]
+    fn test_image_format() {
+        assert_eq!(image_format(PNG), Some(ImageFormat::Png));
+        assert_eq!(
+            image_format(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageFormat::Webp)
+        );
+        assert_eq!(image_format(b"BM\0\0"), None);
+        assert_eq!(image_format(b""), None);
+    }
+
+    #[test]
+    fn test_message_parts_per_provider() {
+        let message = Message {
+            content: String::from("What's this?"),
+            role: MessageRole::User,
+        };
+        let attachments = [Attachment::from_file("screenshot.png", PNG.to_vec()).unwrap()];
+
+        assert_eq!(
+            message_to_json(ProviderKind::Ollama, &message, &attachments),
+            json!({ "role": "user", "content": "What's this?", "images": ["iVBORw0KGgo="] })
+        );
+        assert_eq!(
+            message_to_json(ProviderKind::OpenAi, &message, &attachments),
+            json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "What's this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } },
+                ],
+            })
+        );
+        assert_eq!(
+            message_to_json(ProviderKind::Anthropic, &message, &attachments),
+            json!({
+                "role": "user",
+                "content": [
+                    {
+                        "type": "image",
+                        "source": { "type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo=" },
+                    },
+                    { "type": "text", "text": "What's this?" },
+                ],
+            })
+        );
+        assert_eq!(
+            message_to_json(ProviderKind::Anthropic, &message, &[]),
+            json!({ "role": "user", "content": "What's this?" })
+        );
+    }
+
+    #[test]
+    fn test_documents_come_before_content() {
+        let message = Message {
+            content: String::from("Summarize this."),
+            role: MessageRole::User,
+        };
+        let document =
+            Attachment::from_file("notes.md", b"\xEF\xBB\xBF# Notes\n".to_vec()).unwrap();
+
+        assert_eq!(document.mime_type(), "text/markdown");
+        assert_eq!(document.estimated_tokens(), Some(2));
+        assert_eq!(
+            message_to_json(ProviderKind::OpenAi, &message, &[document]),
+            json!({
+                "role": "user",
+                "content": "<attachment name=\"notes.md\" type=\"text/markdown\">\n# Notes\n</attachment>\n\nSummarize this.",
+            })
+        );
+        assert!(Attachment::from_file("binary.dat", vec![0, 159, 146, 150]).is_none());
+    }
+
+    #[test]
+    fn test_documents_are_limited() {
+        assert!(Attachment::from_file("main.rs", b"fn main() {}".to_vec()).is_some());
+        assert!(Attachment::from_file("Cargo.lock", b"version = 4".to_vec()).is_none());
+        assert!(Attachment::from_file("README", b"# Readme".to_vec()).is_none());
+
+        let large = "a".repeat(MAX_DOCUMENT_TEXT_SIZE + 1).into_bytes();
+        assert!(Attachment::from_file("large.txt", large).is_none());
+    }
+}
@@ -1,29 +1,162 @@
-use std::{cmp::Reverse, sync::Arc};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyml::models::{Message, MessageRole};
 use chrono::{NaiveDateTime, Utc};
-use gpui::{App, AppContext, Entity};
+use futures::future::AbortHandle;
+use gpui::{App, AppContext, Context, Entity, Task};
 use indexmap::IndexMap;
 use rusqlite::Connection;
-use serde::{Serialize, Serializer, ser::SerializeSeq};
+use serde::Serialize;
 
-use crate::managers::{UniqueId, chats_manager::ChatsMap};
+use crate::managers::{
+    Assistant, Attachment, DbError, GenerationMetadata, GenerationParameters, MessageStatus,
+    ModelChoice, UniqueId, chats_manager::ChatsMap,
+};
 
 pub struct Chat {
     db_connection: Arc<Connection>,
     pub chat_id: UniqueId,
     pub title: Entity<String>,
     pub edited_at: NaiveDateTime,
+    /// Sent before the messages of every request. `None` falls back to the default system prompt.
+    pub system_prompt: Entity<Option<String>>,
+    /// The assistant the chat was started from, which may since have been deleted.
+    pub assistant_id: Option<UniqueId>,
+    /// The model replies are generated with, restored when the chat is opened.
+    pub model: Entity<Option<ModelChoice>>,
+    /// Overrides the parameters of the model and assistant for this chat.
+    pub parameters: Entity<GenerationParameters>,
+    /// The loaded messages of the chat, including the ones on inactive branches, in the order they were created.
     messages: Entity<IndexMap<UniqueId, MessageWithMetadata>>,
+    messages_load_state: Entity<MessagesLoadState>,
+    /// The last message of the branch that is currently shown.
+    active_leaf_id: Entity<Option<UniqueId>>,
+    /// The reply currently being streamed into this chat, if any.
+    streaming: Entity<Option<StreamingReply>>,
+    /// Streamed content that is shown but not yet written to the database.
+    pending_content: HashMap<UniqueId, PendingContent>,
+    /// The timer waiting to write the pending content, if one is scheduled.
+    flush_task: Option<Task<()>>,
     chats: Entity<Option<ChatsMap>>,
 }
 
+struct PendingContent {
+    content: String,
+    buffered_since: Instant,
+}
+
+/// A reply that is being streamed, and the handle to abort it with.
+pub struct StreamingReply {
+    pub message_id: UniqueId,
+    abort_handle: AbortHandle,
+}
+
 #[derive(Serialize)]
 pub struct MessageWithMetadata {
     #[serde(flatten)]
     pub message: Message,
     #[serde(skip)]
     message_id: UniqueId,
+    /// The message this one replies to, or `None` for the first message of the chat.
+    #[serde(skip)]
+    pub parent_id: Option<UniqueId>,
+    #[serde(skip)]
+    pub created_at: NaiveDateTime,
+    /// Only set for assistant messages.
+    #[serde(skip)]
+    pub generation: Option<GenerationMetadata>,
+    #[serde(skip)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl MessageWithMetadata {
+    pub fn message_id(&self) -> &UniqueId {
+        &self.message_id
+    }
+}
+
+/// How many messages are loaded from the database at a time.
+const MESSAGES_PAGE_SIZE: usize = 50;
+
+/// How long streamed content may stay in memory before it's written to the database.
+const CONTENT_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+/// How much streamed content, in bytes, is written to the database at once.
+const CONTENT_FLUSH_THRESHOLD: usize = 4096;
+
+/// Selects the columns of a [`ChatHeader`] from `chats`. The active leaf falls back
+/// to the latest message if it's missing.
+pub(super) const CHAT_HEADER_COLUMNS: &str = "
+    chats.id,
+    chats.title,
+    chats.edited_at,
+    COALESCE(
+        (SELECT id FROM messages WHERE id = chats.active_leaf_id),
+        (SELECT id FROM messages WHERE chat_id = chats.id ORDER BY created_at DESC, rowid DESC LIMIT 1)
+    ),
+    chats.system_prompt,
+    chats.assistant_id,
+    chats.provider_id,
+    chats.provider_name,
+    chats.model,
+    chats.parameters
+";
+
+/// The part of a chat that is loaded up front, without any of its messages.
+pub(super) struct ChatHeader {
+    pub chat_id: UniqueId,
+    pub title: String,
+    pub edited_at: NaiveDateTime,
+    pub active_leaf_id: Option<UniqueId>,
+    pub system_prompt: Option<String>,
+    pub assistant_id: Option<UniqueId>,
+    pub model: Option<ModelChoice>,
+    pub parameters: GenerationParameters,
+}
+
+impl ChatHeader {
+    /// Reads a header from a row selected with [`CHAT_HEADER_COLUMNS`].
+    pub fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            chat_id: UniqueId::from_string(row.get::<_, String>(0)?),
+            title: row.get(1)?,
+            edited_at: row.get(2)?,
+            active_leaf_id: row.get::<_, Option<String>>(3)?.map(UniqueId::from_string),
+            system_prompt: row.get(4)?,
+            assistant_id: row.get::<_, Option<String>>(5)?.map(UniqueId::from_string),
+            model: match (
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, Option<String>>(8)?,
+            ) {
+                // The provider may have been deleted before its name was recorded.
+                (Some(provider_id), provider_name, Some(model)) => Some(ModelChoice {
+                    provider_id: UniqueId::from_string(provider_id),
+                    provider_name: provider_name.unwrap_or_default(),
+                    model,
+                }),
+                _ => None,
+            },
+            // Unknown or malformed parameters are dropped rather than failing to load.
+            parameters: row
+                .get::<_, Option<String>>(9)?
+                .and_then(|parameters| serde_json::from_str(&parameters).ok())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// How much of a chat's history has been loaded from the database.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MessagesLoadState {
+    NotLoaded,
+    /// The newest messages are loaded, but there are older ones left.
+    Partial,
+    Complete,
 }
 
 impl<'a> Chat {
@@ -33,60 +166,356 @@ impl<'a> Chat {
         chat_id: UniqueId,
         chats: Entity<Option<ChatsMap>>,
     ) -> rusqlite::Result<Self> {
-        let mut stmt = db_connection.prepare(
-            r#"
-                SELECT
-                    title,
-                    edited_at
-                FROM chats
-                WHERE id = ?
-                "#,
+        let header = db_connection.query_row(
+            &format!("SELECT {CHAT_HEADER_COLUMNS} FROM chats WHERE id = ?"),
+            [chat_id.to_string()],
+            ChatHeader::from_row,
         )?;
 
-        let (title, edited_at) = stmt.query_row([chat_id.to_string()], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, NaiveDateTime>(1)?))
-        })?;
-
-        Ok(Chat {
-            db_connection: db_connection.clone(),
-            title: cx.new(|_cx| title),
-            edited_at,
-            messages: {
-                let messages = Self::load_messages_from_db(&chat_id, &db_connection)?;
-                cx.new(|_cx| messages)
-            },
-            chat_id,
+        Ok(Self::from_header(cx, db_connection, header, chats))
+    }
+
+    /// Creates a chat from its header. Its messages are loaded once it's opened.
+    pub(super) fn from_header(
+        cx: &mut App,
+        db_connection: Arc<Connection>,
+        header: ChatHeader,
+        chats: Entity<Option<ChatsMap>>,
+    ) -> Self {
+        Chat {
+            db_connection,
+            chat_id: header.chat_id,
+            title: cx.new(|_cx| header.title),
+            edited_at: header.edited_at,
+            system_prompt: cx.new(|_cx| header.system_prompt),
+            assistant_id: header.assistant_id,
+            model: cx.new(|_cx| header.model),
+            parameters: cx.new(|_cx| header.parameters),
+            messages: cx.new(|_cx| IndexMap::new()),
+            messages_load_state: cx.new(|_cx| MessagesLoadState::NotLoaded),
+            active_leaf_id: cx.new(|_cx| header.active_leaf_id),
+            streaming: cx.new(|_cx| None),
+            pending_content: HashMap::new(),
+            flush_task: None,
             chats,
-        })
+        }
     }
 
     pub fn new(
         cx: &mut App,
         db_connection: Arc<Connection>,
         chats: Entity<Option<ChatsMap>>,
-    ) -> rusqlite::Result<Self> {
+        assistant: Option<(&UniqueId, &Assistant)>,
+        parameters: GenerationParameters,
+    ) -> Result<Self, DbError> {
         let chat_id = UniqueId::new();
         let created_at = Utc::now().naive_utc();
-
-        db_connection.execute(
-            "INSERT INTO chats (id, title, created_at, edited_at) VALUES (?1, ?2, ?3, ?3)",
-            (&chat_id, "Untitled Chat", &created_at),
-        )?;
+        let assistant_id = assistant.map(|(assistant_id, _)| assistant_id.clone());
+        let system_prompt = assistant.and_then(|(_, assistant)| assistant.system_prompt.clone());
+        let serialized_parameters = serialize_parameters(&parameters)?;
+
+        db_connection
+            .execute(
+                "INSERT INTO chats (id, title, created_at, edited_at, system_prompt, assistant_id, parameters) VALUES (?1, ?2, ?3, ?3, ?4, ?5, ?6)",
+                (
+                    &chat_id,
+                    "Untitled Chat",
+                    &created_at,
+                    &system_prompt,
+                    &assistant_id,
+                    &serialized_parameters,
+                ),
+            )
+            .map_err(DbError::SqliteError)?;
 
         Ok(Self {
             db_connection,
             chat_id,
             edited_at: created_at,
             title: cx.new(|_cx| String::from("Untitled Chat")),
+            system_prompt: cx.new(|_cx| system_prompt),
+            assistant_id,
+            model: cx.new(|_cx| None),
+            parameters: cx.new(|_cx| parameters),
             messages: cx.new(|_cx| IndexMap::new()),
+            messages_load_state: cx.new(|_cx| MessagesLoadState::Complete),
+            active_leaf_id: cx.new(|_cx| None),
+            streaming: cx.new(|_cx| None),
+            pending_content: HashMap::new(),
+            flush_task: None,
             chats,
         })
     }
 
+    pub fn is_streaming(&self, cx: &App) -> bool {
+        self.streaming.read(cx).is_some()
+    }
+
+    /// Marks a reply as being streamed into this chat, aborting any reply that
+    /// was still being streamed.
+    pub fn start_streaming(&self, cx: &mut App, message_id: UniqueId, abort_handle: AbortHandle) {
+        self.cancel_streaming(cx);
+
+        self.streaming.update(cx, |streaming, cx| {
+            *streaming = Some(StreamingReply {
+                message_id,
+                abort_handle,
+            });
+            cx.notify();
+        });
+    }
+
+    /// Clears the streaming state once a reply is done, unless another reply
+    /// has started streaming since.
+    pub fn finish_streaming(&self, cx: &mut App, message_id: &UniqueId) {
+        self.streaming.update(cx, |streaming, cx| {
+            if streaming
+                .as_ref()
+                .is_some_and(|streaming| &streaming.message_id == message_id)
+            {
+                *streaming = None;
+                cx.notify();
+            }
+        });
+    }
+
+    /// Aborts the reply being streamed into this chat, if any.
+    pub fn cancel_streaming(&self, cx: &mut App) {
+        self.streaming.update(cx, |streaming, cx| {
+            if let Some(streaming) = streaming.take() {
+                streaming.abort_handle.abort();
+                cx.notify();
+            }
+        });
+    }
+
+    /// Aborts the reply being streamed into a chat that's being deleted, and drops the
+    /// streamed content that isn't written yet so nothing is written into it afterwards.
+    pub fn discard_streaming(&mut self, cx: &mut App) {
+        self.cancel_streaming(cx);
+        self.pending_content.clear();
+        self.flush_task = None;
+    }
+
+    /// Writes what's left of a reply once it stops streaming, whether it completed
+    /// or was aborted, and clears the streaming state.
+    pub fn finish_reply(
+        &mut self,
+        cx: &mut App,
+        message_id: &UniqueId,
+        generation: GenerationMetadata,
+    ) -> Result<(), rusqlite::Error> {
+        let flushed = self.flush_pending_content(cx);
+        let generation_set = self.set_generation_metadata(cx, message_id, generation);
+        self.finish_streaming(cx, message_id);
+
+        flushed.and(generation_set)
+    }
+
     pub fn read_messages(&'a self, cx: &'a App) -> &'a IndexMap<UniqueId, MessageWithMetadata> {
         self.messages.read(cx)
     }
 
+    pub fn messages_load_state(&self, cx: &App) -> MessagesLoadState {
+        *self.messages_load_state.read(cx)
+    }
+
+    pub fn has_older_messages(&self, cx: &App) -> bool {
+        self.messages_load_state(cx) != MessagesLoadState::Complete
+    }
+
+    /// Loads the newest page of messages, and enough older ones to reach the
+    /// active leaf, if nothing has been loaded yet.
+    pub fn load_messages(&self, cx: &mut App) -> rusqlite::Result<()> {
+        if self.messages_load_state(cx) != MessagesLoadState::NotLoaded {
+            return Ok(());
+        }
+
+        self.load_older_page(cx, None, Some(MESSAGES_PAGE_SIZE))?;
+
+        if let Some(active_leaf_id) = self.active_leaf_id(cx).cloned() {
+            self.load_messages_through(cx, &active_leaf_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the page of messages before the oldest loaded one.
+    pub fn load_older_messages(&self, cx: &mut App) -> rusqlite::Result<()> {
+        if !self.has_older_messages(cx) {
+            return Ok(());
+        }
+
+        self.load_older_page(cx, None, Some(MESSAGES_PAGE_SIZE))
+    }
+
+    /// Loads every message that hasn't been loaded yet.
+    pub fn load_all_messages(&self, cx: &mut App) -> rusqlite::Result<()> {
+        if !self.has_older_messages(cx) {
+            return Ok(());
+        }
+
+        self.load_older_page(cx, None, None)
+    }
+
+    /// Loads every message from `message_id` up to the oldest loaded one.
+    pub fn load_messages_through(
+        &self,
+        cx: &mut App,
+        message_id: &UniqueId,
+    ) -> rusqlite::Result<()> {
+        if !self.has_older_messages(cx) || self.messages.read(cx).contains_key(message_id) {
+            return Ok(());
+        }
+
+        self.load_older_page(cx, Some(message_id), None)
+    }
+
+    /// Loads messages older than the oldest loaded one, newest first, stopping
+    /// at `through_id` or after `limit` messages.
+    fn load_older_page(
+        &self,
+        cx: &mut App,
+        through_id: Option<&UniqueId>,
+        limit: Option<usize>,
+    ) -> rusqlite::Result<()> {
+        let before_id = self.messages.read(cx).keys().next().cloned();
+
+        let page = Self::load_messages_from_db(
+            &self.db_connection,
+            &self.chat_id,
+            before_id.as_ref(),
+            through_id,
+            limit,
+        )?;
+
+        let oldest_id = page.keys().next().or(before_id.as_ref()).cloned();
+        let has_older = match oldest_id {
+            Some(oldest_id) => self.db_connection.query_row(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1
+                    FROM messages
+                    WHERE chat_id = ?1
+                        AND (created_at, rowid) < (SELECT created_at, rowid FROM messages WHERE id = ?2)
+                )
+                "#,
+                (&self.chat_id, &oldest_id),
+                |row| row.get::<_, bool>(0),
+            )?,
+            None => false,
+        };
+
+        self.messages.update(cx, |messages, cx| {
+            let mut page = page;
+            page.extend(messages.drain(..));
+            *messages = page;
+            cx.notify();
+        });
+
+        self.messages_load_state.update(cx, |load_state, cx| {
+            *load_state = match has_older {
+                true => MessagesLoadState::Partial,
+                false => MessagesLoadState::Complete,
+            };
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    pub fn active_leaf_id(&'a self, cx: &'a App) -> Option<&'a UniqueId> {
+        self.active_leaf_id.read(cx).as_ref()
+    }
+
+    /// The messages of the active branch, from the first message to the active leaf.
+    pub fn active_path(&'a self, cx: &'a App) -> Vec<&'a MessageWithMetadata> {
+        let messages = self.messages.read(cx);
+
+        let mut path = Vec::new();
+        let mut next_id = self.active_leaf_id(cx);
+
+        while let Some(message) = next_id.and_then(|message_id| messages.get(message_id)) {
+            path.push(message);
+            next_id = message.parent_id.as_ref();
+        }
+
+        path.reverse();
+        path
+    }
+
+    /// The ids of a message and its siblings, in the order they were created.
+    pub fn branches(&'a self, cx: &'a App, message_id: &UniqueId) -> Vec<&'a UniqueId> {
+        let messages = self.messages.read(cx);
+
+        let Some(message) = messages.get(message_id) else {
+            return Vec::new();
+        };
+
+        messages
+            .iter()
+            .filter(|(_, sibling)| sibling.parent_id == message.parent_id)
+            .map(|(sibling_id, _)| sibling_id)
+            .collect()
+    }
+
+    /// Makes the branch containing `message_id` active, following its most
+    /// recent replies down to a leaf.
+    pub fn switch_branch(
+        &self,
+        cx: &mut App,
+        message_id: &UniqueId,
+    ) -> Result<(), rusqlite::Error> {
+        self.load_messages_through(cx, message_id)?;
+
+        let messages = self.messages.read(cx);
+
+        if !messages.contains_key(message_id) {
+            return Ok(());
+        }
+
+        let mut leaf_id = message_id;
+        while let Some((child_id, _)) = messages
+            .iter()
+            .rev()
+            .find(|(_, child)| child.parent_id.as_ref() == Some(leaf_id))
+        {
+            leaf_id = child_id;
+        }
+
+        let leaf_id = leaf_id.clone();
+        self.set_active_leaf(cx, Some(leaf_id))
+    }
+
+    /// Moves the active leaf back to `message_id`, so that the next pushed
+    /// message starts a new branch after it.
+    pub fn rewind_to(
+        &self,
+        cx: &mut App,
+        message_id: Option<&UniqueId>,
+    ) -> Result<(), rusqlite::Error> {
+        self.set_active_leaf(cx, message_id.cloned())
+    }
+
+    fn set_active_leaf(
+        &self,
+        cx: &mut App,
+        active_leaf_id: Option<UniqueId>,
+    ) -> Result<(), rusqlite::Error> {
+        self.db_connection.execute(
+            "UPDATE chats SET active_leaf_id = ?1 WHERE id = ?2",
+            (&active_leaf_id, &self.chat_id),
+        )?;
+
+        self.active_leaf_id
+            .update(cx, |current_active_leaf_id, cx| {
+                *current_active_leaf_id = active_leaf_id;
+                cx.notify();
+            });
+
+        Ok(())
+    }
+
     pub fn set_title(
         &self,
         cx: &mut App,
@@ -107,22 +536,141 @@ impl<'a> Chat {
         Ok(())
     }
 
+    /// Sets the system prompt of the chat. It only applies to replies generated
+    /// from now on, so earlier messages are left as they are.
+    pub fn set_system_prompt(
+        &self,
+        cx: &mut App,
+        system_prompt: Option<String>,
+    ) -> Result<(), rusqlite::Error> {
+        let system_prompt = system_prompt.filter(|system_prompt| !system_prompt.trim().is_empty());
+
+        self.db_connection.execute(
+            "UPDATE chats SET system_prompt = ?1 WHERE id = ?2",
+            (&system_prompt, &self.chat_id),
+        )?;
+
+        self.system_prompt.update(cx, |current_system_prompt, cx| {
+            *current_system_prompt = system_prompt;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    /// Sets the model replies in this chat are generated with.
+    pub fn set_model(
+        &self,
+        cx: &mut App,
+        model: Option<ModelChoice>,
+    ) -> Result<(), rusqlite::Error> {
+        if self.model.read(cx) == &model {
+            return Ok(());
+        }
+
+        self.db_connection.execute(
+            "UPDATE chats SET provider_id = ?1, provider_name = ?2, model = ?3 WHERE id = ?4",
+            (
+                model.as_ref().map(|model| &model.provider_id),
+                model.as_ref().map(|model| &model.provider_name),
+                model.as_ref().map(|model| &model.model),
+                &self.chat_id,
+            ),
+        )?;
+
+        self.model.update(cx, |current_model, cx| {
+            *current_model = model;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    /// Sets the parameters that override the model's and assistant's for this chat.
+    pub fn set_parameters(
+        &self,
+        cx: &mut App,
+        parameters: GenerationParameters,
+    ) -> Result<(), DbError> {
+        self.db_connection
+            .execute(
+                "UPDATE chats SET parameters = ?1 WHERE id = ?2",
+                (serialize_parameters(&parameters)?, &self.chat_id),
+            )
+            .map_err(DbError::SqliteError)?;
+
+        self.parameters.update(cx, |current_parameters, cx| {
+            *current_parameters = parameters;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    /// Marks the chat as archived in the database.
+    pub fn archive(&self) -> Result<(), rusqlite::Error> {
+        self.db_connection.execute(
+            "UPDATE chats SET archived_at = ?1 WHERE id = ?2",
+            (&Utc::now().naive_utc(), &self.chat_id),
+        )?;
+
+        Ok(())
+    }
+
+    /// Marks the chat as trashed in the database.
+    pub fn move_to_trash(&self) -> Result<(), rusqlite::Error> {
+        self.db_connection.execute(
+            "UPDATE chats SET deleted_at = ?1 WHERE id = ?2",
+            (&Utc::now().naive_utc(), &self.chat_id),
+        )?;
+
+        Ok(())
+    }
+
+    /// Clears the archived and trashed markers from the chat in the database.
+    pub fn restore(&self) -> Result<(), rusqlite::Error> {
+        self.db_connection.execute(
+            "UPDATE chats SET archived_at = NULL, deleted_at = NULL WHERE id = ?1",
+            [&self.chat_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Appends a message to the active branch and makes it the active leaf.
     pub fn push_message(
         &mut self,
         cx: &mut App,
         chat_id: &UniqueId,
         content: impl Into<String>,
         role: MessageRole,
+        attachments: Vec<Attachment>,
     ) -> Result<UniqueId, rusqlite::Error> {
         let content = content.into();
 
         let message_id = UniqueId::new();
+        let parent_id = self.active_leaf_id(cx).cloned();
         let created_at = Utc::now().naive_utc();
 
-        self.db_connection.execute(
-            "INSERT INTO messages (id, chat_id, role, content, created_at, edited_at) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
-            (&message_id, chat_id, role.as_str(), &content, &created_at),
+        let transaction = self.db_connection.unchecked_transaction()?;
+        transaction.execute(
+            "INSERT INTO messages (id, chat_id, parent_id, role, content, created_at, edited_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            (&message_id, chat_id, &parent_id, role.as_str(), &content, &created_at),
         )?;
+        for (position, attachment) in attachments.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO attachments (message_id, position, name, mime_type, data, text) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                (
+                    &message_id,
+                    position,
+                    &attachment.name,
+                    attachment.mime_type(),
+                    attachment.data(),
+                    attachment.text(),
+                ),
+            )?;
+        }
+        transaction.commit()?;
 
         // Pushes the message to our cache.
         self.messages.update(cx, |messages, cx| {
@@ -131,11 +679,17 @@ impl<'a> Chat {
                 MessageWithMetadata {
                     message: Message { content, role },
                     message_id: message_id.clone(),
+                    parent_id,
+                    created_at,
+                    generation: None,
+                    attachments,
                 },
             );
             cx.notify();
         });
 
+        self.set_active_leaf(cx, Some(message_id.clone()))?;
+
         // Updates our cached chats map with the created_at time stamp.
         self.chats.update(cx, |chats, cx| {
             let Some(chats) = chats else { return };
@@ -149,15 +703,18 @@ impl<'a> Chat {
         Ok(message_id)
     }
 
+    /// Appends streamed content to a message. It's shown right away but written to the
+    /// database in batches, see [`Self::flush_pending_content`].
     pub fn push_message_content(
-        &self,
-        cx: &mut App,
+        &mut self,
+        cx: &mut Context<Self>,
         message_id: &UniqueId,
         content: impl Into<String>,
     ) -> Result<(), rusqlite::Error> {
-        let edited_at = Utc::now().naive_utc();
-
         let content = content.into();
+        if content.is_empty() {
+            return Ok(());
+        }
 
         // Appends the content to the cached message.
         self.messages.update(cx, |chat, cx| {
@@ -168,103 +725,255 @@ impl<'a> Chat {
             cx.notify();
         });
 
-        let new_cached_content = self
-            .messages
-            .read(cx)
-            .get(message_id)
-            .map(|this| &this.message.content);
+        let pending = self
+            .pending_content
+            .entry(message_id.clone())
+            .or_insert_with(|| PendingContent {
+                content: String::new(),
+                buffered_since: Instant::now(),
+            });
+        pending.content += &content;
 
-        // Appends the content to the message in the database.
-        let new_content: String = self.db_connection.query_row(
-            r#"
-            UPDATE messages
-            SET
-                content = content || ?2,
-                edited_at = ?3
-            WHERE id = ?1
-            RETURNING content
-            "#,
-            (&message_id, &content, &edited_at),
-            |row| row.get(0),
+        if pending.content.len() >= CONTENT_FLUSH_THRESHOLD
+            || pending.buffered_since.elapsed() >= CONTENT_FLUSH_INTERVAL
+        {
+            return self.flush_pending_content(cx);
+        }
+
+        // Makes sure the content is written even if no more chunks arrive.
+        if self.flush_task.is_none() {
+            self.flush_task = Some(cx.spawn(async move |this, cx| {
+                cx.background_executor().timer(CONTENT_FLUSH_INTERVAL).await;
+
+                let _ = this.update(cx, |this, cx| {
+                    this.flush_task = None;
+                    if let Err(err) = this.flush_pending_content(cx) {
+                        tracing::error!("failed to write streamed content: {err}");
+                    }
+                });
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Empties a message so a failed reply can be generated again in its place.
+    pub fn clear_message_content(
+        &mut self,
+        cx: &mut App,
+        message_id: &UniqueId,
+    ) -> Result<(), rusqlite::Error> {
+        self.pending_content.remove(message_id);
+
+        self.db_connection.execute(
+            "UPDATE messages SET content = '', edited_at = ?2 WHERE id = ?1",
+            (message_id, &Utc::now().naive_utc()),
         )?;
 
-        // We need to resolve the desync using the database as our primary source.
-        if Some(&new_content) != new_cached_content {
-            self.messages.update(cx, |chat, cx| {
-                let Some(message) = chat.get_mut(message_id) else {
-                    return;
-                };
-                message.message.content = new_content;
+        self.messages.update(cx, |messages, cx| {
+            if let Some(message) = messages.get_mut(message_id) {
+                message.message.content.clear();
                 cx.notify();
-            });
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Writes streamed content that's still in memory to the database.
+    pub fn flush_pending_content(&mut self, cx: &mut App) -> Result<(), rusqlite::Error> {
+        if self.pending_content.is_empty() {
+            return Ok(());
+        }
+
+        let edited_at = Utc::now().naive_utc();
+
+        let transaction = self.db_connection.unchecked_transaction()?;
+        for (message_id, pending) in &self.pending_content {
+            transaction.execute(
+                "UPDATE messages SET content = content || ?2, edited_at = ?3 WHERE id = ?1",
+                (message_id, &pending.content, &edited_at),
+            )?;
         }
+        transaction.commit()?;
+        self.pending_content.clear();
 
-        // We need to update our internal chats map with the new edited_at time stamp.
+        // We need to update our internal chats map with the new edited_at time stamp. A chat
+        // that was archived or deleted while streaming isn't in it anymore.
         self.chats.update(cx, |chats, cx| {
             let Some(chats) = chats else { return };
-            chats
+            if chats
                 .update_order_for_key(&self.chat_id, Reverse(edited_at))
-                .unwrap();
+                .is_ok()
+            {
+                cx.notify();
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn set_generation_metadata(
+        &self,
+        cx: &mut App,
+        message_id: &UniqueId,
+        generation: GenerationMetadata,
+    ) -> Result<(), rusqlite::Error> {
+        self.db_connection.execute(
+            r#"
+            UPDATE messages
+            SET
+                provider_id = ?2,
+                model = ?3,
+                prompt_tokens = ?4,
+                completion_tokens = ?5,
+                time_to_first_token_ms = ?6,
+                duration_ms = ?7,
+                finish_reason = ?8,
+                status = ?9,
+                error = ?10
+            WHERE id = ?1
+            "#,
+            (
+                message_id,
+                &generation.provider_id,
+                &generation.model,
+                generation.prompt_tokens,
+                generation.completion_tokens,
+                generation.time_to_first_token.map(duration_to_millis),
+                generation.duration.map(duration_to_millis),
+                &generation.finish_reason,
+                generation.status,
+                // A malformed error is dropped rather than failing the whole update.
+                generation
+                    .error
+                    .as_ref()
+                    .and_then(|error| serde_json::to_string(error).ok()),
+            ),
+        )?;
 
+        self.messages.update(cx, |messages, cx| {
+            let Some(message) = messages.get_mut(message_id) else {
+                return;
+            };
+            message.generation = Some(generation);
             cx.notify();
         });
 
         Ok(())
     }
 
+    /// Loads the messages of a chat created before `before_id`, back to and including
+    /// `through_id`, returning at most `limit` of the newest ones in creation order.
     fn load_messages_from_db(
-        message_id: &UniqueId,
         db_connection: &Connection,
+        chat_id: &UniqueId,
+        before_id: Option<&UniqueId>,
+        through_id: Option<&UniqueId>,
+        limit: Option<usize>,
     ) -> rusqlite::Result<IndexMap<UniqueId, MessageWithMetadata>> {
         let mut stmt = db_connection.prepare(
             r#"
             SELECT
                 id,
+                parent_id,
                 content,
-                role
+                role,
+                provider_id,
+                model,
+                prompt_tokens,
+                completion_tokens,
+                time_to_first_token_ms,
+                duration_ms,
+                finish_reason,
+                created_at,
+                status,
+                error
             FROM messages
-            WHERE chat_id = ?
-            ORDER BY edited_at ASC
+            WHERE chat_id = ?1
+                AND (?2 IS NULL OR (created_at, rowid) < (SELECT created_at, rowid FROM messages WHERE id = ?2))
+                AND (?3 IS NULL OR (created_at, rowid) >= (SELECT created_at, rowid FROM messages WHERE id = ?3))
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT ?4
             "#,
         )?;
 
-        let messages = stmt
-            .query_map([message_id.to_string()], |row| {
+        // A negative limit means no limit to SQLite.
+        let limit = limit.map_or(-1, |limit| limit as i64);
+
+        let mut messages = stmt
+            .query_map((chat_id, before_id, through_id, limit), |row| {
                 let message_id = UniqueId::from_string(row.get::<_, String>(0)?);
-                let content: String = row.get(1)?;
-                let role: String = row.get(2)?;
+                let parent_id = row.get::<_, Option<String>>(1)?.map(UniqueId::from_string);
+                let content: String = row.get(2)?;
+                let role = MessageRole::from_str(&row.get::<_, String>(3)?);
+
+                let generation = match role {
+                    MessageRole::Assistant => Some(GenerationMetadata {
+                        provider_id: row.get::<_, Option<String>>(4)?.map(UniqueId::from_string),
+                        model: row.get(5)?,
+                        prompt_tokens: row.get(6)?,
+                        completion_tokens: row.get(7)?,
+                        time_to_first_token: row
+                            .get::<_, Option<u64>>(8)?
+                            .map(Duration::from_millis),
+                        duration: row.get::<_, Option<u64>>(9)?.map(Duration::from_millis),
+                        finish_reason: row.get(10)?,
+                        status: row.get::<_, Option<MessageStatus>>(12)?.unwrap_or_default(),
+                        error: row
+                            .get::<_, Option<String>>(13)?
+                            .and_then(|error| serde_json::from_str(&error).ok()),
+                    }),
+                    _ => None,
+                };
 
                 Ok((
                     message_id.clone(),
                     MessageWithMetadata {
-                        message: Message {
-                            content,
-                            role: MessageRole::from_str(&role),
-                        },
+                        message: Message { content, role },
                         message_id,
+                        parent_id,
+                        created_at: row.get(11)?,
+                        generation,
+                        attachments: Vec::new(),
                     },
                 ))
             })?
             .collect::<rusqlite::Result<IndexMap<_, _>>>()?;
 
+        let mut attachments_stmt = db_connection.prepare_cached(
+            "SELECT name, mime_type, data, text FROM attachments WHERE message_id = ?1 ORDER BY position",
+        )?;
+        for (message_id, message) in &mut messages {
+            message.attachments = attachments_stmt
+                .query_map([message_id], |row| {
+                    Ok(Attachment::from_stored(
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                    ))
+                })?
+                // Attachments of a kind this version can't read are left out.
+                .filter_map(Result::transpose)
+                .collect::<rusqlite::Result<_>>()?;
+        }
+
+        messages.reverse();
         Ok(messages)
     }
 }
 
-pub struct ValuesOnly<'a, K, V>(pub &'a IndexMap<K, V>);
-
-impl<'a, K, V> Serialize for ValuesOnly<'a, K, V>
-where
-    V: Serialize,
-{
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
-        for value in self.0.values() {
-            seq.serialize_element(value)?;
-        }
-        seq.end()
+fn duration_to_millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// Chats without overrides store `NULL` rather than an empty object.
+fn serialize_parameters(parameters: &GenerationParameters) -> Result<Option<String>, DbError> {
+    match parameters.is_empty() {
+        true => Ok(None),
+        false => serde_json::to_string(parameters)
+            .map(Some)
+            .map_err(DbError::SerializationError),
     }
 }
@@ -0,0 +1,623 @@
+use std::{collections::HashSet, io::ErrorKind, path::Path};
+
+use chrono::{NaiveDateTime, Utc};
+use gpui::{App, PathPromptOptions};
+use serde::Serialize;
+use smol::io::AsyncWriteExt;
+
+use crate::managers::{Attachment, Chat, GenerationError, GenerationMetadata, UniqueId};
+
+/// Bumped whenever the layout of exported JSON changes.
+const EXPORT_VERSION: u32 = 1;
+
+/// Inlined into exported HTML so that the file can be shared on its own.
+const HTML_STYLE: &str = "
+body { margin: 0; background: #f7f7f8; color: #1f1f23; font: 15px/1.6 -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; }
+main { max-width: 760px; margin: 0 auto; padding: 40px 20px; }
+h1 { font-size: 24px; margin: 0 0 32px; }
+h2 { font-size: 13px; margin: 0 0 8px; color: #6b6b76; font-weight: 600; }
+.message { margin-bottom: 32px; }
+.message.user .content { background: #e8e8ec; border-radius: 14px; padding: 10px 14px; }
+.content { white-space: pre-wrap; overflow-wrap: anywhere; }
+.attachment { margin-top: 8px; color: #6b6b76; }
+.attachment pre { white-space: pre-wrap; overflow-wrap: anywhere; color: #1f1f23; }
+@media (prefers-color-scheme: dark) {
+    body { background: #18181b; color: #ececf1; }
+    h2 { color: #a0a0ab; }
+    .message.user .content { background: #27272c; }
+    .attachment { color: #a0a0ab; }
+    .attachment pre { color: #ececf1; }
+}
+";
+
+/// The name of the file several chats are exported into as JSON.
+const BULK_JSON_FILE_STEM: &str = "Astrum chats";
+
+/// The longest a file name derived from a chat title can be, excluding its extension.
+const MAX_FILE_STEM_LEN: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Markdown,
+        ExportFormat::Json,
+        ExportFormat::Html,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Html => "HTML",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// An exported chat, ready to be written to disk.
+pub struct ExportFile {
+    pub file_name: String,
+    pub contents: String,
+}
+
+/// A snapshot of a chat, including every branch and the metadata of its messages.
+#[derive(Serialize)]
+pub struct ChatExport {
+    pub id: String,
+    pub title: String,
+    pub edited_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// The last message of the branch that was shown when the chat was exported.
+    pub active_leaf_id: Option<String>,
+    /// Every message of the chat, in the order they were created.
+    pub messages: Vec<MessageExport>,
+}
+
+#[derive(Serialize)]
+pub struct MessageExport {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub role: String,
+    pub content: String,
+    pub created_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation: Option<GenerationExport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentExport>,
+}
+
+/// An attached file. Only the text of documents is exported, images are listed by name.
+#[derive(Serialize)]
+pub struct AttachmentExport {
+    pub name: String,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl From<&Attachment> for AttachmentExport {
+    fn from(attachment: &Attachment) -> Self {
+        Self {
+            name: attachment.name.clone(),
+            mime_type: attachment.mime_type().to_string(),
+            text: attachment.text().map(str::to_string),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct GenerationExport {
+    pub provider_id: Option<String>,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub time_to_first_token_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub finish_reason: Option<String>,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<GenerationError>,
+}
+
+impl From<&GenerationMetadata> for GenerationExport {
+    fn from(generation: &GenerationMetadata) -> Self {
+        Self {
+            provider_id: generation.provider_id.as_ref().map(UniqueId::to_string),
+            model: generation.model.clone(),
+            prompt_tokens: generation.prompt_tokens,
+            completion_tokens: generation.completion_tokens,
+            time_to_first_token_ms: generation
+                .time_to_first_token
+                .map(|ttft| ttft.as_millis() as u64),
+            duration_ms: generation
+                .duration
+                .map(|duration| duration.as_millis() as u64),
+            finish_reason: generation.finish_reason.clone(),
+            status: generation.status.as_str(),
+            error: generation.error.clone(),
+        }
+    }
+}
+
+/// The top level of an exported JSON file.
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    version: u32,
+    exported_at: NaiveDateTime,
+    chats: &'a [&'a ChatExport],
+}
+
+impl ChatExport {
+    /// Snapshots the loaded messages of a chat. Call [`Chat::load_all_messages`]
+    /// first for a complete export.
+    pub fn from_chat(chat: &Chat, cx: &App) -> Self {
+        let messages = chat
+            .read_messages(cx)
+            .values()
+            .map(|message| MessageExport {
+                id: message.message_id().to_string(),
+                parent_id: message.parent_id.as_ref().map(UniqueId::to_string),
+                role: message.message.role.as_str().to_string(),
+                content: message.message.content.clone(),
+                created_at: message.created_at,
+                generation: message.generation.as_ref().map(GenerationExport::from),
+                attachments: message
+                    .attachments
+                    .iter()
+                    .map(AttachmentExport::from)
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            id: chat.chat_id.to_string(),
+            title: chat.title.read(cx).clone(),
+            edited_at: chat.edited_at,
+            system_prompt: chat.system_prompt.read(cx).clone(),
+            active_leaf_id: chat.active_leaf_id(cx).map(UniqueId::to_string),
+            messages,
+        }
+    }
+
+    /// The messages of the branch that was shown when the chat was exported.
+    fn active_path(&self) -> Vec<&MessageExport> {
+        let mut path = Vec::new();
+        let mut next_id = self.active_leaf_id.as_deref();
+
+        while let Some(message) =
+            next_id.and_then(|next_id| self.messages.iter().find(|message| message.id == next_id))
+        {
+            path.push(message);
+            next_id = message.parent_id.as_deref();
+        }
+
+        path.reverse();
+        path
+    }
+
+    /// Renders the shown branch as Markdown, with a heading for each message.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n", self.title.trim());
+
+        for message in self.active_path() {
+            let attachments = message
+                .attachments
+                .iter()
+                .map(|attachment| match &attachment.text {
+                    Some(text) => {
+                        let fence = code_fence(text);
+                        format!(
+                            "**{}**\n\n{fence}\n{}\n{fence}",
+                            attachment.name,
+                            text.trim_end()
+                        )
+                    }
+                    None => format!("**{}** (not included)", attachment.name),
+                });
+
+            let body = Some(message.content.trim_end().to_string())
+                .filter(|content| !content.is_empty())
+                .into_iter()
+                .chain(attachments)
+                .collect::<Vec<_>>()
+                .join("\n\n");
+
+            markdown.push_str(&format!("\n## {}\n\n{body}\n", role_heading(message)));
+        }
+
+        markdown
+    }
+
+    /// Renders the shown branch as a standalone HTML page with inline styles.
+    pub fn to_html(&self) -> String {
+        let messages = self
+            .active_path()
+            .into_iter()
+            .map(|message| {
+                let attachments = message
+                    .attachments
+                    .iter()
+                    .map(|attachment| match &attachment.text {
+                        Some(text) => format!(
+                            "\n<details class=\"attachment\"><summary>{}</summary><pre>{}</pre></details>",
+                            escape_html(&attachment.name),
+                            escape_html(text.trim_end()),
+                        ),
+                        None => format!(
+                            "\n<div class=\"attachment\">{} (not included)</div>",
+                            escape_html(&attachment.name),
+                        ),
+                    })
+                    .collect::<String>();
+
+                format!(
+                    "<section class=\"message {role}\">\n<h2>{heading}</h2>\n<div class=\"content\">{content}</div>{attachments}\n</section>",
+                    role = escape_html(&message.role),
+                    heading = escape_html(&role_heading(message)),
+                    content = escape_html(message.content.trim_end()),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<main>\n<h1>{title}</h1>\n{messages}\n</main>\n</body>\n</html>\n",
+            title = escape_html(self.title.trim()),
+        )
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        chats_to_json(&[self])
+    }
+
+    /// A file name for the chat derived from its title.
+    fn file_stem(&self) -> String {
+        let stem = self
+            .title
+            .chars()
+            .map(|char| match char {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => ' ',
+                char if char.is_control() => ' ',
+                char => char,
+            })
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let stem = stem
+            .chars()
+            .take(MAX_FILE_STEM_LEN)
+            .collect::<String>()
+            .trim_matches(|char: char| char == '.' || char.is_whitespace())
+            .to_string();
+
+        match stem.is_empty() {
+            true => String::from("Untitled Chat"),
+            false => stem,
+        }
+    }
+}
+
+/// Serializes chats into the lossless JSON export format.
+pub fn chats_to_json(chats: &[&ChatExport]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&JsonExport {
+        version: EXPORT_VERSION,
+        exported_at: Utc::now().naive_utc(),
+        chats,
+    })
+}
+
+/// Renders each chat into its own file, making sure no two files share a name. Chats
+/// exported as JSON all go into one file, since the format holds any number of them.
+pub fn export_files(
+    chats: &[ChatExport],
+    format: ExportFormat,
+) -> serde_json::Result<Vec<ExportFile>> {
+    if format == ExportFormat::Json && chats.len() > 1 {
+        return Ok(vec![ExportFile {
+            file_name: format!("{BULK_JSON_FILE_STEM}.{}", format.extension()),
+            contents: chats_to_json(&chats.iter().collect::<Vec<_>>())?,
+        }]);
+    }
+
+    let mut used_stems = HashSet::new();
+
+    chats
+        .iter()
+        .map(|chat| {
+            let base_stem = chat.file_stem();
+            let mut stem = base_stem.clone();
+            let mut counter = 2;
+
+            while !used_stems.insert(stem.to_lowercase()) {
+                stem = format!("{base_stem} ({counter})");
+                counter += 1;
+            }
+
+            let contents = match format {
+                ExportFormat::Markdown => chat.to_markdown(),
+                ExportFormat::Json => chat.to_json()?,
+                ExportFormat::Html => chat.to_html(),
+            };
+
+            Ok(ExportFile {
+                file_name: format!("{stem}.{}", format.extension()),
+                contents,
+            })
+        })
+        .collect()
+}
+
+/// Asks where to save the exported files and writes them there. A single file
+/// is saved with a save dialog, while several are written into a chosen folder.
+pub fn save_export_files(cx: &mut App, mut files: Vec<ExportFile>) {
+    let directory = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_default();
+
+    if files.len() == 1 {
+        let file = files.remove(0);
+        let path = cx.prompt_for_new_path(&directory, Some(&file.file_name));
+
+        cx.spawn(async move |_cx| {
+            let Ok(Ok(Some(path))) = path.await else {
+                return;
+            };
+
+            if let Err(err) = smol::fs::write(&path, file.contents).await {
+                tracing::error!("failed to export chat to {}: {err}", path.display());
+            }
+        })
+        .detach();
+
+        return;
+    }
+
+    let paths = cx.prompt_for_paths(PathPromptOptions {
+        files: false,
+        directories: true,
+        multiple: false,
+        prompt: Some("Export".into()),
+    });
+
+    cx.spawn(async move |_cx| {
+        let Ok(Ok(Some(paths))) = paths.await else {
+            return;
+        };
+        let Some(directory) = paths.first() else {
+            return;
+        };
+
+        for file in files {
+            write_into_directory(directory, file).await;
+        }
+    })
+    .detach();
+}
+
+/// Writes a file into a directory, numbering its name rather than overwriting a file
+/// that's already there.
+async fn write_into_directory(directory: &Path, file: ExportFile) {
+    let mut counter = 1;
+
+    loop {
+        let path = directory.join(numbered_file_name(&file.file_name, counter));
+
+        let opened = smol::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await;
+
+        let result = match opened {
+            // Writes to the file are buffered until it's flushed.
+            Ok(mut opened) => match opened.write_all(file.contents.as_bytes()).await {
+                Ok(()) => opened.flush().await,
+                Err(err) => Err(err),
+            },
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                counter += 1;
+                continue;
+            }
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            tracing::error!("failed to export chat to {}: {err}", path.display());
+        }
+        return;
+    }
+}
+
+/// A file name with ` (counter)` after its stem, or the name itself for the first one.
+fn numbered_file_name(file_name: &str, counter: usize) -> String {
+    if counter == 1 {
+        return file_name.to_string();
+    }
+
+    match file_name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem} ({counter}).{extension}"),
+        None => format!("{file_name} ({counter})"),
+    }
+}
+
+fn role_heading(message: &MessageExport) -> String {
+    let role = match message.role.as_str() {
+        "system" => "System",
+        "user" => "User",
+        "assistant" => "Assistant",
+        role => role,
+    };
+
+    match message
+        .generation
+        .as_ref()
+        .and_then(|generation| generation.model.as_ref())
+    {
+        Some(model) => format!("{role} ({model})"),
+        None => role.to_string(),
+    }
+}
+
+/// A code fence longer than any run of backticks in the text, so the text can't close it.
+fn code_fence(text: &str) -> String {
+    let longest_run = text
+        .split(|char| char != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            char => escaped.push(char),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, parent_id: Option<&str>, role: &str, content: &str) -> MessageExport {
+        MessageExport {
+            id: id.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: NaiveDateTime::default(),
+            generation: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    fn branching_chat() -> ChatExport {
+        ChatExport {
+            id: "chat".to_string(),
+            title: "Rust <questions>".to_string(),
+            edited_at: NaiveDateTime::default(),
+            system_prompt: None,
+            active_leaf_id: Some("a2".to_string()),
+            messages: vec![
+                message("u1", None, "user", "What is a borrow?"),
+                message("a1", Some("u1"), "assistant", "An old answer."),
+                message("a2", Some("u1"), "assistant", "A reference & more."),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_markdown_follows_active_branch() {
+        assert_eq!(
+            branching_chat().to_markdown(),
+            "# Rust <questions>\n\n## User\n\nWhat is a borrow?\n\n## Assistant\n\nA reference & more.\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_includes_documents() {
+        let mut chat = branching_chat();
+        chat.messages[0].attachments = vec![
+            AttachmentExport {
+                name: "notes.md".to_string(),
+                mime_type: "text/markdown".to_string(),
+                text: Some("```rust\nlet a = &b;\n```\n".to_string()),
+            },
+            AttachmentExport {
+                name: "diagram.png".to_string(),
+                mime_type: "image/png".to_string(),
+                text: None,
+            },
+        ];
+
+        assert!(chat.to_markdown().contains(
+            "What is a borrow?\n\n**notes.md**\n\n````\n```rust\nlet a = &b;\n```\n````\n\n**diagram.png** (not included)\n"
+        ));
+    }
+
+    #[test]
+    fn test_html_is_escaped() {
+        let html = branching_chat().to_html();
+
+        assert!(html.contains("<title>Rust &lt;questions&gt;</title>"));
+        assert!(html.contains("A reference &amp; more."));
+        assert!(!html.contains("An old answer."));
+    }
+
+    #[test]
+    fn test_json_keeps_every_branch() {
+        let json: serde_json::Value =
+            serde_json::from_str(&branching_chat().to_json().unwrap()).unwrap();
+
+        assert_eq!(json["version"], EXPORT_VERSION);
+        assert_eq!(json["chats"][0]["messages"].as_array().unwrap().len(), 3);
+        assert_eq!(json["chats"][0]["active_leaf_id"], "a2");
+    }
+
+    #[test]
+    fn test_export_file_names() {
+        let mut untitled = branching_chat();
+        untitled.title = " ../ ".to_string();
+
+        let files = export_files(
+            &[branching_chat(), branching_chat(), untitled],
+            ExportFormat::Markdown,
+        )
+        .unwrap();
+
+        assert_eq!(
+            files
+                .iter()
+                .map(|file| file.file_name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Rust questions.md",
+                "Rust questions (2).md",
+                "Untitled Chat.md"
+            ]
+        );
+        assert_eq!(
+            numbered_file_name("Rust questions.md", 3),
+            "Rust questions (3).md"
+        );
+    }
+
+    #[test]
+    fn test_bulk_json_export_is_one_file() {
+        let files =
+            export_files(&[branching_chat(), branching_chat()], ExportFormat::Json).unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_name, "Astrum chats.json");
+
+        let json: serde_json::Value = serde_json::from_str(&files[0].contents).unwrap();
+        assert_eq!(json["chats"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,661 @@
+use std::{sync::Mutex, time::Duration};
+
+use http::{Request, StatusCode};
+use rusqlite::{
+    ToSql,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    anyhttp_gpui::RequestObserver,
+    managers::{ProviderKind, UniqueId},
+};
+
+/// Information about how an assistant message was generated.
+#[derive(Clone, Default)]
+pub struct GenerationMetadata {
+    pub provider_id: Option<UniqueId>,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub time_to_first_token: Option<Duration>,
+    pub duration: Option<Duration>,
+    pub finish_reason: Option<String>,
+    pub status: MessageStatus,
+    /// Why the request failed, kept apart from the content so it's never sent back to the model.
+    pub error: Option<GenerationError>,
+}
+
+/// A request for a reply that failed, as reported by the provider.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenerationError {
+    pub message: String,
+    /// The HTTP status of the response, if one was received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+}
+
+impl GenerationMetadata {
+    /// Metadata for a reply that has just started streaming.
+    pub fn new(provider_id: UniqueId, model: impl Into<String>) -> Self {
+        Self {
+            provider_id: Some(provider_id),
+            model: Some(model.into()),
+            status: MessageStatus::Streaming,
+            ..Default::default()
+        }
+    }
+
+    /// Completion tokens generated per second, excluding the time to first token.
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let completion_tokens = self.completion_tokens?;
+        let generation_time = self
+            .duration?
+            .saturating_sub(self.time_to_first_token.unwrap_or_default());
+
+        (!generation_time.is_zero())
+            .then(|| completion_tokens as f64 / generation_time.as_secs_f64())
+    }
+}
+
+/// How far an assistant message got.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum MessageStatus {
+    /// Still being written. Replies left like this by a crash are marked cancelled on startup.
+    Streaming,
+
+    #[default]
+    Complete,
+
+    /// Stopped by the user, or by the app quitting mid-stream.
+    Cancelled,
+
+    Errored,
+
+    /// Cut off by the provider, usually because it hit the max tokens.
+    Truncated,
+}
+
+impl MessageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Streaming => "streaming",
+            Self::Complete => "complete",
+            Self::Cancelled => "cancelled",
+            Self::Errored => "errored",
+            Self::Truncated => "truncated",
+        }
+    }
+
+    /// The status of a reply that finished streaming for the given reason.
+    pub fn from_finish_reason(finish_reason: Option<&str>) -> Self {
+        match finish_reason {
+            // OpenAI and Ollama report "length", Anthropic reports "max_tokens".
+            Some("length" | "max_tokens") => Self::Truncated,
+            _ => Self::Complete,
+        }
+    }
+
+    /// Shown next to replies that didn't complete normally.
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            Self::Streaming => Some("Generating"),
+            Self::Complete => None,
+            Self::Cancelled => Some("Stopped"),
+            Self::Errored => Some("Failed"),
+            Self::Truncated => Some("Cut off"),
+        }
+    }
+
+    /// Whether the model can be asked to carry on from where the reply stopped.
+    pub fn can_continue(&self) -> bool {
+        matches!(self, Self::Cancelled | Self::Truncated)
+    }
+}
+
+impl ToSql for MessageStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for MessageStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "streaming" => Ok(Self::Streaming),
+            "complete" => Ok(Self::Complete),
+            "cancelled" => Ok(Self::Cancelled),
+            "errored" => Ok(Self::Errored),
+            "truncated" => Ok(Self::Truncated),
+            other => Err(FromSqlError::Other(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown MessageStatus: {other}"),
+            )))),
+        }
+    }
+}
+
+/// Sampling settings sent with a request. Unset fields are left to the provider.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationParameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl GenerationParameters {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Layers `overrides` on top of these parameters. Fields it leaves unset are kept.
+    pub fn merge(&self, overrides: &Self) -> Self {
+        Self {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            stop: match overrides.stop.is_empty() {
+                true => self.stop.clone(),
+                false => overrides.stop.clone(),
+            },
+            seed: overrides.seed.or(self.seed),
+        }
+    }
+
+    /// Writes the parameters into a chat request body, in the format of the provider.
+    pub fn apply(&self, kind: ProviderKind, body: &mut Map<String, Value>) {
+        let max_tokens_key = match kind {
+            ProviderKind::Ollama => "num_predict",
+            // `max_tokens` is rejected by reasoning models.
+            ProviderKind::OpenAi | ProviderKind::AzureOpenAi => "max_completion_tokens",
+            // Servers following the OpenAI API don't all know the newer key.
+            ProviderKind::Anthropic | ProviderKind::OpenAiCompatible | ProviderKind::Gemini => {
+                "max_tokens"
+            }
+        };
+        let stop_key = match kind {
+            ProviderKind::Anthropic => "stop_sequences",
+            ProviderKind::Ollama
+            | ProviderKind::OpenAi
+            | ProviderKind::OpenAiCompatible
+            | ProviderKind::Gemini
+            | ProviderKind::AzureOpenAi => "stop",
+        };
+
+        // Ollama takes its sampling settings in `options` rather than at the top level.
+        let target = match kind {
+            ProviderKind::Ollama => {
+                if self.is_empty() {
+                    return;
+                }
+
+                let options = body
+                    .entry("options")
+                    .or_insert_with(|| Value::Object(Map::new()));
+
+                match options.as_object_mut() {
+                    Some(options) => options,
+                    None => return,
+                }
+            }
+            ProviderKind::OpenAi
+            | ProviderKind::Anthropic
+            | ProviderKind::OpenAiCompatible
+            | ProviderKind::Gemini
+            | ProviderKind::AzureOpenAi => body,
+        };
+
+        if let Some(temperature) = self.temperature {
+            target.insert("temperature".to_string(), temperature.into());
+        }
+        if let Some(top_p) = self.top_p {
+            target.insert("top_p".to_string(), top_p.into());
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            target.insert(max_tokens_key.to_string(), max_tokens.into());
+        }
+        if !self.stop.is_empty() {
+            target.insert(stop_key.to_string(), self.stop.clone().into());
+        }
+        // Anthropic has no seed, so runs can't be made reproducible there.
+        if let Some(seed) = self.seed
+            && kind != ProviderKind::Anthropic
+        {
+            target.insert("seed".to_string(), seed.into());
+        }
+    }
+}
+
+/// What a provider reported about a response.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct ReportedUsage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub finish_reason: Option<String>,
+}
+
+impl ReportedUsage {
+    /// Reads a single line of a response body, which is either a server-sent event
+    /// (OpenAI, Anthropic), a line of newline-delimited JSON (Ollama) or a whole JSON body.
+    fn read_line(&mut self, line: &[u8]) {
+        let Ok(line) = std::str::from_utf8(line) else {
+            return;
+        };
+
+        let line = line.trim();
+        let line = line.strip_prefix("data:").unwrap_or(line).trim_start();
+
+        if let Ok(event) = serde_json::from_str::<Value>(line) {
+            self.read_event(&event);
+        }
+    }
+
+    fn read_event(&mut self, event: &Value) {
+        // Anthropic reports the prompt tokens in the `message_start` event.
+        for usage in [&event["usage"], &event["message"]["usage"]] {
+            if let Some(tokens) = first_u64(usage, &["prompt_tokens", "input_tokens"]) {
+                self.prompt_tokens = Some(tokens);
+            }
+            if let Some(tokens) = first_u64(usage, &["completion_tokens", "output_tokens"]) {
+                self.completion_tokens = Some(tokens);
+            }
+        }
+
+        // Ollama reports its token counts at the top level of the final line.
+        if let Some(tokens) = event["prompt_eval_count"].as_u64() {
+            self.prompt_tokens = Some(tokens);
+        }
+        if let Some(tokens) = event["eval_count"].as_u64() {
+            self.completion_tokens = Some(tokens);
+        }
+
+        let finish_reason = [
+            &event["choices"][0]["finish_reason"],
+            &event["delta"]["stop_reason"],
+            &event["stop_reason"],
+            &event["done_reason"],
+        ]
+        .into_iter()
+        .find_map(Value::as_str);
+
+        if let Some(finish_reason) = finish_reason {
+            self.finish_reason = Some(finish_reason.to_string());
+        }
+    }
+}
+
+fn first_u64(value: &Value, keys: &[&str]) -> Option<u64> {
+    keys.iter().find_map(|key| value[key].as_u64())
+}
+
+/// Writes the generation parameters into the body of a chat request.
+pub struct GenerationParametersObserver {
+    kind: ProviderKind,
+    parameters: GenerationParameters,
+}
+
+impl GenerationParametersObserver {
+    pub fn new(kind: ProviderKind, parameters: GenerationParameters) -> Self {
+        Self { kind, parameters }
+    }
+}
+
+impl RequestObserver for GenerationParametersObserver {
+    fn prepare(&self, request: &mut Request<Vec<u8>>) {
+        if self.parameters.is_empty() {
+            return;
+        }
+
+        patch_json_body(request, |body| self.parameters.apply(self.kind, body));
+    }
+}
+
+/// Rewrites the body of a request if it is a JSON object.
+fn patch_json_body(request: &mut Request<Vec<u8>>, patch: impl FnOnce(&mut Map<String, Value>)) {
+    let Ok(mut body) = serde_json::from_slice::<Value>(request.body()) else {
+        return;
+    };
+
+    let Some(body_object) = body.as_object_mut() else {
+        return;
+    };
+
+    patch(body_object);
+
+    if let Ok(patched_body) = serde_json::to_vec(&body) {
+        *request.body_mut() = patched_body;
+    }
+}
+
+/// Collects the usage a provider reports while streaming a response.
+pub struct UsageTracker {
+    kind: ProviderKind,
+    state: Mutex<UsageTrackerState>,
+}
+
+#[derive(Default)]
+struct UsageTrackerState {
+    pending: Vec<u8>,
+    usage: ReportedUsage,
+    status: Option<StatusCode>,
+    /// The start of the body of an unsuccessful response.
+    error_body: Vec<u8>,
+}
+
+/// How much of the body of an unsuccessful response is kept.
+const ERROR_BODY_LIMIT: usize = 16 * 1024;
+
+impl UsageTracker {
+    pub fn new(kind: ProviderKind) -> Self {
+        Self {
+            kind,
+            state: Mutex::new(UsageTrackerState::default()),
+        }
+    }
+
+    /// Returns everything reported so far, including a trailing line without a newline.
+    pub fn usage(&self) -> ReportedUsage {
+        let mut state = self.state.lock().unwrap();
+
+        let pending = std::mem::take(&mut state.pending);
+        state.usage.read_line(&pending);
+
+        state.usage.clone()
+    }
+
+    /// Describes a failed request, preferring the message the provider put in the
+    /// response body over `err`.
+    pub fn error(&self, err: impl std::fmt::Display) -> GenerationError {
+        let state = self.state.lock().unwrap();
+
+        GenerationError {
+            message: error_message_from_body(&state.error_body).unwrap_or_else(|| err.to_string()),
+            status_code: state.status.map(|status| status.as_u16()),
+        }
+    }
+}
+
+/// Reads the message out of an error body, e.g. `{"error":{"message":"..."}}` (OpenAI,
+/// Anthropic) or `{"error":"..."}` (Ollama).
+fn error_message_from_body(body: &[u8]) -> Option<String> {
+    let body = serde_json::from_slice::<Value>(body).ok()?;
+
+    [&body["error"]["message"], &body["error"], &body["message"]]
+        .into_iter()
+        .find_map(Value::as_str)
+        .map(ToString::to_string)
+}
+
+impl RequestObserver for UsageTracker {
+    fn prepare(&self, request: &mut Request<Vec<u8>>) {
+        // OpenAI only includes token usage in streamed responses when asked to. Other
+        // servers speaking its API may reject the option, so it's only asked of OpenAI.
+        if !matches!(self.kind, ProviderKind::OpenAi | ProviderKind::AzureOpenAi) {
+            return;
+        }
+
+        patch_json_body(request, |body| {
+            if body.get("stream") == Some(&Value::Bool(true))
+                && !body.contains_key("stream_options")
+            {
+                body.insert(
+                    "stream_options".to_string(),
+                    serde_json::json!({ "include_usage": true }),
+                );
+            }
+        });
+    }
+
+    fn on_response(&self, status: StatusCode) {
+        self.state.lock().unwrap().status = Some(status);
+    }
+
+    fn on_chunk(&self, chunk: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.status.is_some_and(|status| !status.is_success()) {
+            let remaining = ERROR_BODY_LIMIT.saturating_sub(state.error_body.len());
+            state
+                .error_body
+                .extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+            return;
+        }
+
+        state.pending.extend_from_slice(chunk);
+
+        while let Some(newline_idx) = state.pending.iter().position(|byte| *byte == b'\n') {
+            let line = state.pending.drain(..=newline_idx).collect::<Vec<_>>();
+            state.usage.read_line(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(kind: ProviderKind, chunks: &[&str]) -> ReportedUsage {
+        let tracker = UsageTracker::new(kind);
+        for chunk in chunks {
+            tracker.on_chunk(chunk.as_bytes());
+        }
+        tracker.usage()
+    }
+
+    #[test]
+    fn test_openai_stream() {
+        let usage = track(
+            ProviderKind::OpenAi,
+            &[
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\ndata: {\"choi",
+                "ces\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":3}}\n\n",
+                "data: [DONE]\n\n",
+            ],
+        );
+
+        assert_eq!(
+            usage,
+            ReportedUsage {
+                prompt_tokens: Some(12),
+                completion_tokens: Some(3),
+                finish_reason: Some("stop".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_anthropic_stream() {
+        let usage = track(
+            ProviderKind::Anthropic,
+            &[
+                "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+                "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":15}}\n\n",
+            ],
+        );
+
+        assert_eq!(
+            usage,
+            ReportedUsage {
+                prompt_tokens: Some(25),
+                completion_tokens: Some(15),
+                finish_reason: Some("end_turn".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_ollama_stream_without_trailing_newline() {
+        let usage = track(
+            ProviderKind::Ollama,
+            &[
+                "{\"message\":{\"content\":\"Hi\"},\"done\":false}\n",
+                "{\"done\":true,\"done_reason\":\"length\",\"prompt_eval_count\":8,\"eval_count\":64}",
+            ],
+        );
+
+        assert_eq!(
+            usage,
+            ReportedUsage {
+                prompt_tokens: Some(8),
+                completion_tokens: Some(64),
+                finish_reason: Some("length".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_openai_requests_include_usage() {
+        let tracker = UsageTracker::new(ProviderKind::OpenAi);
+        let mut request = Request::new(br#"{"model":"gpt","stream":true}"#.to_vec());
+
+        tracker.prepare(&mut request);
+
+        let body: Value = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(body["stream_options"]["include_usage"], Value::Bool(true));
+
+        let tracker = UsageTracker::new(ProviderKind::OpenAiCompatible);
+        let mut request = Request::new(br#"{"model":"gpt","stream":true}"#.to_vec());
+
+        tracker.prepare(&mut request);
+
+        let body: Value = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(body.get("stream_options"), None);
+    }
+
+    #[test]
+    fn test_parameters_observer() {
+        let parameters = GenerationParameters {
+            temperature: Some(0.5),
+            ..Default::default()
+        };
+        let observer = GenerationParametersObserver::new(ProviderKind::OpenAi, parameters);
+        let mut request = Request::new(br#"{"model":"gpt","stream":true}"#.to_vec());
+
+        observer.prepare(&mut request);
+
+        let body: Value = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "model": "gpt", "stream": true, "temperature": 0.5 })
+        );
+    }
+
+    #[test]
+    fn test_parameters_are_applied_per_provider() {
+        let parameters = GenerationParameters {
+            temperature: Some(0.2),
+            max_tokens: Some(256),
+            stop: vec!["END".to_string()],
+            seed: Some(7),
+            ..Default::default()
+        };
+
+        let mut body = Map::new();
+        parameters.apply(ProviderKind::Ollama, &mut body);
+        assert_eq!(
+            Value::Object(body),
+            serde_json::json!({
+                "options": { "temperature": 0.2, "num_predict": 256, "stop": ["END"], "seed": 7 }
+            })
+        );
+
+        let mut body = Map::new();
+        body.insert("max_tokens".to_string(), 4096.into());
+        parameters.apply(ProviderKind::Anthropic, &mut body);
+        assert_eq!(
+            Value::Object(body),
+            serde_json::json!({ "temperature": 0.2, "max_tokens": 256, "stop_sequences": ["END"] })
+        );
+    }
+
+    #[test]
+    fn test_error_from_response_body() {
+        let tracker = UsageTracker::new(ProviderKind::OpenAi);
+        tracker.on_response(StatusCode::UNAUTHORIZED);
+        tracker.on_chunk(br#"{"error":{"message":"Incorrect API key provided","#);
+        tracker.on_chunk(br#""type":"invalid_request_error"}}"#);
+
+        assert_eq!(
+            tracker.error("request failed"),
+            GenerationError {
+                message: "Incorrect API key provided".to_string(),
+                status_code: Some(401),
+            }
+        );
+        assert_eq!(tracker.usage(), ReportedUsage::default());
+
+        let tracker = UsageTracker::new(ProviderKind::Ollama);
+        tracker.on_response(StatusCode::NOT_FOUND);
+        tracker.on_chunk(br#"{"error":"model 'llama9' not found"}"#);
+        assert_eq!(
+            tracker.error("request failed").message,
+            "model 'llama9' not found"
+        );
+
+        let tracker = UsageTracker::new(ProviderKind::Ollama);
+        assert_eq!(
+            tracker.error("connection refused"),
+            GenerationError {
+                message: "connection refused".to_string(),
+                status_code: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_status_from_finish_reason() {
+        assert_eq!(
+            MessageStatus::from_finish_reason(Some("stop")),
+            MessageStatus::Complete
+        );
+        assert_eq!(
+            MessageStatus::from_finish_reason(Some("length")),
+            MessageStatus::Truncated
+        );
+        assert_eq!(
+            MessageStatus::from_finish_reason(Some("max_tokens")),
+            MessageStatus::Truncated
+        );
+        assert_eq!(
+            MessageStatus::from_finish_reason(None),
+            MessageStatus::Complete
+        );
+    }
+
+    #[test]
+    fn test_parameter_overrides() {
+        let model_defaults = GenerationParameters {
+            temperature: Some(0.8),
+            max_tokens: Some(1024),
+            stop: vec!["###".to_string()],
+            ..Default::default()
+        };
+        let chat_overrides = GenerationParameters {
+            temperature: Some(0.0),
+            seed: Some(42),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            model_defaults.merge(&chat_overrides),
+            GenerationParameters {
+                temperature: Some(0.0),
+                top_p: None,
+                max_tokens: Some(1024),
+                stop: vec!["###".to_string()],
+                seed: Some(42),
+            }
+        );
+    }
+}
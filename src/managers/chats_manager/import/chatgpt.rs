@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ImportedChat, RawMessage, build_chat, from_epoch_seconds, role_from_str};
+
+/// A conversation from the `conversations.json` file of a ChatGPT export. Every
+/// edit and regeneration is a node in `mapping`, and `current_node` is the last
+/// message of the branch that was shown.
+#[derive(Deserialize)]
+struct Conversation {
+    id: Option<String>,
+    conversation_id: Option<String>,
+    title: Option<String>,
+    create_time: Option<f64>,
+    update_time: Option<f64>,
+    #[serde(default)]
+    mapping: BTreeMap<String, Node>,
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    message: Option<NodeMessage>,
+    parent: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NodeMessage {
+    author: Author,
+    content: Option<Content>,
+    create_time: Option<f64>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Value>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Metadata {
+    model_slug: Option<String>,
+    #[serde(default)]
+    is_visually_hidden_from_conversation: bool,
+}
+
+pub(super) fn parse(conversations: Vec<Value>) -> Vec<ImportedChat> {
+    conversations
+        .into_iter()
+        .filter_map(|conversation| serde_json::from_value::<Conversation>(conversation).ok())
+        .filter_map(parse_conversation)
+        .collect()
+}
+
+fn parse_conversation(conversation: Conversation) -> Option<ImportedChat> {
+    let source_id = conversation.conversation_id.or(conversation.id)?;
+
+    let raw_messages = conversation
+        .mapping
+        .into_iter()
+        .map(|(id, node)| {
+            let message = node.message;
+
+            let role = message
+                .as_ref()
+                .filter(|message| !message.metadata.is_visually_hidden_from_conversation)
+                .and_then(|message| role_from_str(&message.author.role));
+
+            // Text is split into parts, alongside attachments which can't be imported.
+            let content = message
+                .as_ref()
+                .and_then(|message| message.content.as_ref())
+                .map(|content| {
+                    let parts = content
+                        .parts
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>();
+
+                    match parts.is_empty() {
+                        true => content.text.clone().unwrap_or_default(),
+                        false => parts.join("\n\n"),
+                    }
+                })
+                .unwrap_or_default();
+
+            RawMessage {
+                id,
+                parent_id: node.parent,
+                role,
+                content,
+                created_at: message
+                    .as_ref()
+                    .and_then(|message| message.create_time)
+                    .and_then(from_epoch_seconds),
+                model: message.and_then(|message| message.metadata.model_slug),
+            }
+        })
+        .collect();
+
+    build_chat(
+        source_id,
+        conversation.title,
+        conversation.create_time.and_then(from_epoch_seconds),
+        conversation.update_time.and_then(from_epoch_seconds),
+        raw_messages,
+        conversation.current_node.as_deref(),
+    )
+}
@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ImportedChat, RawMessage, build_chat, from_rfc3339, role_from_str};
+
+/// Claude's exports don't always link messages to their parents, in which case
+/// each one replies to the message before it.
+const ROOT_MESSAGE_ID: &str = "00000000-0000-4000-8000-000000000000";
+
+/// A conversation from the `conversations.json` file of a Claude data export.
+#[derive(Deserialize)]
+struct Conversation {
+    uuid: String,
+    name: Option<String>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    #[serde(default)]
+    chat_messages: Vec<ChatMessage>,
+    current_leaf_message_uuid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    uuid: String,
+    sender: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    created_at: Option<String>,
+    parent_message_uuid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    text: Option<String>,
+}
+
+pub(super) fn parse(conversations: Vec<Value>) -> Vec<ImportedChat> {
+    conversations
+        .into_iter()
+        .filter_map(|conversation| serde_json::from_value::<Conversation>(conversation).ok())
+        .filter_map(parse_conversation)
+        .collect()
+}
+
+fn parse_conversation(conversation: Conversation) -> Option<ImportedChat> {
+    let mut previous_id = None;
+
+    let raw_messages = conversation
+        .chat_messages
+        .into_iter()
+        .map(|message| {
+            // Only text is imported, leaving out tool use and thinking.
+            let text_blocks = message
+                .content
+                .iter()
+                .filter(|block| block.kind == "text")
+                .filter_map(|block| block.text.as_deref())
+                .collect::<Vec<_>>();
+
+            let content = match text_blocks.is_empty() {
+                true => message.text,
+                false => text_blocks.join("\n\n"),
+            };
+
+            let parent_id = match message.parent_message_uuid {
+                Some(parent_id) if parent_id != ROOT_MESSAGE_ID => Some(parent_id),
+                Some(_) => None,
+                None => previous_id.take(),
+            };
+            previous_id = Some(message.uuid.clone());
+
+            RawMessage {
+                id: message.uuid,
+                parent_id,
+                role: role_from_str(&message.sender),
+                content,
+                created_at: message.created_at.as_deref().and_then(from_rfc3339),
+                model: None,
+            }
+        })
+        .collect();
+
+    build_chat(
+        conversation.uuid,
+        conversation.name,
+        conversation.created_at.as_deref().and_then(from_rfc3339),
+        conversation.updated_at.as_deref().and_then(from_rfc3339),
+        raw_messages,
+        conversation.current_leaf_message_uuid.as_deref(),
+    )
+}
@@ -0,0 +1,630 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDateTime};
+use rusqlite::Connection;
+use serde_json::Value;
+
+use crate::managers::UniqueId;
+
+mod chatgpt;
+mod claude;
+mod open_webui;
+
+/// Where an imported chat came from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportSource {
+    ChatGpt,
+    Claude,
+    OpenWebUi,
+}
+
+impl ImportSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ImportSource::ChatGpt => "ChatGPT",
+            ImportSource::Claude => "Claude",
+            ImportSource::OpenWebUi => "Open WebUI",
+        }
+    }
+
+    /// The value stored in `chats.import_source`.
+    fn as_str(self) -> &'static str {
+        match self {
+            ImportSource::ChatGpt => "chatgpt",
+            ImportSource::Claude => "claude",
+            ImportSource::OpenWebUi => "open_webui",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("The file isn't valid JSON.")]
+    InvalidJson(#[source] serde_json::Error),
+
+    #[error("The file isn't a ChatGPT, Claude or Open WebUI export.")]
+    UnknownFormat,
+}
+
+/// The chats parsed from an export, ready to be previewed and written to the database.
+pub struct ParsedImport {
+    pub source: ImportSource,
+    pub chats: Vec<ImportedChat>,
+}
+
+pub struct ImportedChat {
+    /// The id of the chat in the app it was exported from, used to skip duplicates.
+    pub source_id: String,
+    pub title: String,
+    pub created_at: NaiveDateTime,
+    pub edited_at: NaiveDateTime,
+    /// Parents always come before their replies.
+    pub messages: Vec<ImportedMessage>,
+    /// The index of the last message of the branch that was shown in the original app.
+    pub active_leaf: Option<usize>,
+}
+
+pub struct ImportedMessage {
+    pub role: &'static str,
+    pub content: String,
+    pub created_at: NaiveDateTime,
+    /// The index of the message this one replies to.
+    pub parent: Option<usize>,
+    /// The model that generated the message, for assistant messages.
+    pub model: Option<String>,
+}
+
+/// What an import contains, or what it did once it has been written.
+#[derive(Clone, Copy)]
+pub struct ImportSummary {
+    pub source: ImportSource,
+    pub chats: usize,
+    pub messages: usize,
+    /// Chats that were imported before, which are skipped.
+    pub duplicates: usize,
+}
+
+/// Detects which app an export came from and parses its chats.
+pub fn parse_import(contents: &str) -> Result<ParsedImport, ImportError> {
+    let value: Value = serde_json::from_str(contents).map_err(ImportError::InvalidJson)?;
+
+    // Exports are lists of chats, but a single chat is accepted too.
+    let conversations = match value {
+        Value::Array(conversations) => conversations,
+        Value::Object(_) => vec![value],
+        _ => return Err(ImportError::UnknownFormat),
+    };
+
+    let source = conversations
+        .iter()
+        .find_map(detect_source)
+        .ok_or(ImportError::UnknownFormat)?;
+
+    let chats = match source {
+        ImportSource::ChatGpt => chatgpt::parse(conversations),
+        ImportSource::Claude => claude::parse(conversations),
+        ImportSource::OpenWebUi => open_webui::parse(conversations),
+    };
+
+    Ok(ParsedImport { source, chats })
+}
+
+fn detect_source(conversation: &Value) -> Option<ImportSource> {
+    let conversation = conversation.as_object()?;
+
+    if conversation.contains_key("mapping") {
+        Some(ImportSource::ChatGpt)
+    } else if conversation.contains_key("chat_messages") {
+        Some(ImportSource::Claude)
+    } else if conversation.contains_key("chat") || conversation.contains_key("history") {
+        Some(ImportSource::OpenWebUi)
+    } else {
+        None
+    }
+}
+
+/// A message as it appears in an export, before it's been linked to its parent.
+struct RawMessage {
+    id: String,
+    parent_id: Option<String>,
+    /// `None` for messages that aren't imported, like tool calls.
+    role: Option<&'static str>,
+    content: String,
+    created_at: Option<NaiveDateTime>,
+    model: Option<String>,
+}
+
+impl RawMessage {
+    fn is_kept(&self) -> bool {
+        self.role.is_some() && !self.content.trim().is_empty()
+    }
+}
+
+fn from_epoch_seconds(seconds: f64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp_millis((seconds * 1000.) as i64).map(|time| time.naive_utc())
+}
+
+fn from_rfc3339(time: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.naive_utc())
+}
+
+fn role_from_str(role: &str) -> Option<&'static str> {
+    match role {
+        "system" => Some("system"),
+        "user" | "human" => Some("user"),
+        "assistant" => Some("assistant"),
+        _ => None,
+    }
+}
+
+/// Links raw messages into a tree, dropping the ones that can't be imported and
+/// attaching their replies to the closest message that is.
+fn build_chat(
+    source_id: String,
+    title: Option<String>,
+    created_at: Option<NaiveDateTime>,
+    edited_at: Option<NaiveDateTime>,
+    raw_messages: Vec<RawMessage>,
+    current_id: Option<&str>,
+) -> Option<ImportedChat> {
+    let index_by_id = raw_messages
+        .iter()
+        .enumerate()
+        .map(|(index, raw)| (raw.id.as_str(), index))
+        .collect::<HashMap<_, _>>();
+
+    let parents = raw_messages
+        .iter()
+        .map(|raw| closest_kept(&raw_messages, &index_by_id, raw.parent_id.as_deref()))
+        .collect::<Vec<_>>();
+    let current_index = closest_kept(&raw_messages, &index_by_id, current_id);
+
+    let fallback_time = created_at
+        .or_else(|| raw_messages.iter().find_map(|raw| raw.created_at))
+        .unwrap_or_default();
+
+    // Replies are never dated before their parents, and come after them when
+    // their dates are equal.
+    let mut placements: Vec<Option<(NaiveDateTime, usize)>> = vec![None; raw_messages.len()];
+    let mut kept = Vec::new();
+
+    for index in 0..raw_messages.len() {
+        if !raw_messages[index].is_kept() {
+            continue;
+        }
+
+        let mut chain = vec![index];
+        while let Some(parent) = parents[*chain.last().unwrap()] {
+            if placements[parent].is_some() || chain.len() > raw_messages.len() {
+                break;
+            }
+            chain.push(parent);
+        }
+
+        for &index in chain.iter().rev() {
+            if placements[index].is_some() {
+                continue;
+            }
+
+            let own_time = raw_messages[index].created_at.unwrap_or(fallback_time);
+            placements[index] = Some(match parents[index].and_then(|parent| placements[parent]) {
+                Some((parent_time, parent_depth)) => (own_time.max(parent_time), parent_depth + 1),
+                None => (own_time, 0),
+            });
+        }
+
+        let (time, depth) = placements[index].unwrap();
+        kept.push((time, depth, index));
+    }
+
+    if kept.is_empty() {
+        return None;
+    }
+
+    kept.sort();
+
+    let new_indices = kept
+        .iter()
+        .enumerate()
+        .map(|(new_index, (_, _, index))| (*index, new_index))
+        .collect::<HashMap<_, _>>();
+
+    let mut raw_messages = raw_messages.into_iter().map(Some).collect::<Vec<_>>();
+
+    let messages = kept
+        .iter()
+        .map(|(time, _, index)| {
+            let raw = raw_messages[*index].take().unwrap();
+
+            ImportedMessage {
+                role: raw.role.unwrap(),
+                content: raw.content,
+                created_at: *time,
+                parent: parents[*index].and_then(|parent| new_indices.get(&parent).copied()),
+                model: raw.model,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let active_leaf = current_index
+        .and_then(|index| new_indices.get(&index).copied())
+        .unwrap_or(messages.len() - 1);
+
+    let created_at = created_at.unwrap_or(messages[0].created_at);
+    let edited_at = edited_at.unwrap_or(kept[kept.len() - 1].0);
+
+    Some(ImportedChat {
+        source_id,
+        title: title
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| String::from("Untitled Chat")),
+        created_at,
+        edited_at,
+        messages,
+        active_leaf: Some(active_leaf),
+    })
+}
+
+/// Walks up from a message to the closest one that is kept, guarding against cycles.
+fn closest_kept<'a>(
+    raw_messages: &'a [RawMessage],
+    index_by_id: &HashMap<&str, usize>,
+    mut id: Option<&'a str>,
+) -> Option<usize> {
+    for _ in 0..raw_messages.len() {
+        let index = *index_by_id.get(id?)?;
+        if raw_messages[index].is_kept() {
+            return Some(index);
+        }
+        id = raw_messages[index].parent_id.as_deref();
+    }
+    None
+}
+
+/// Counts what an import would add, without writing anything.
+pub(super) fn preview_import(
+    db_connection: &Connection,
+    import: &ParsedImport,
+) -> rusqlite::Result<ImportSummary> {
+    let mut summary = ImportSummary {
+        source: import.source,
+        chats: 0,
+        messages: 0,
+        duplicates: 0,
+    };
+
+    // A chat that appears more than once in the export is only imported once.
+    let mut seen_ids = HashSet::new();
+
+    for chat in &import.chats {
+        if !seen_ids.insert(chat.source_id.as_str())
+            || is_duplicate(db_connection, import.source, chat)?
+        {
+            summary.duplicates += 1;
+        } else {
+            summary.chats += 1;
+            summary.messages += chat.messages.len();
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Writes the chats that haven't been imported before, returning their new ids.
+pub(super) fn write_import(
+    db_connection: &Connection,
+    import: &ParsedImport,
+) -> rusqlite::Result<Vec<UniqueId>> {
+    let transaction = db_connection.unchecked_transaction()?;
+    let mut chat_ids = Vec::new();
+
+    for chat in &import.chats {
+        if is_duplicate(&transaction, import.source, chat)? {
+            continue;
+        }
+
+        let chat_id = UniqueId::new();
+        let message_ids = chat
+            .messages
+            .iter()
+            .map(|_| UniqueId::new())
+            .collect::<Vec<_>>();
+        let active_leaf_id = chat.active_leaf.map(|index| &message_ids[index]);
+
+        transaction.execute(
+            r#"
+            INSERT INTO chats (id, title, created_at, edited_at, active_leaf_id, import_source, import_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            (
+                &chat_id,
+                &chat.title,
+                &chat.created_at,
+                &chat.edited_at,
+                active_leaf_id,
+                import.source.as_str(),
+                &chat.source_id,
+            ),
+        )?;
+
+        let mut stmt = transaction.prepare_cached(
+            r#"
+            INSERT INTO messages (id, chat_id, parent_id, role, content, created_at, edited_at, model)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7)
+            "#,
+        )?;
+
+        for (message, message_id) in chat.messages.iter().zip(&message_ids) {
+            stmt.execute((
+                message_id,
+                &chat_id,
+                message.parent.map(|index| &message_ids[index]),
+                message.role,
+                &message.content,
+                &message.created_at,
+                &message.model,
+            ))?;
+        }
+
+        chat_ids.push(chat_id);
+    }
+
+    transaction.commit()?;
+
+    Ok(chat_ids)
+}
+
+fn is_duplicate(
+    db_connection: &Connection,
+    source: ImportSource,
+    chat: &ImportedChat,
+) -> rusqlite::Result<bool> {
+    db_connection.query_row(
+        "SELECT EXISTS (SELECT 1 FROM chats WHERE import_source = ?1 AND import_id = ?2)",
+        (source.as_str(), &chat.source_id),
+        |row| row.get(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(chat: &ImportedChat) -> Vec<&str> {
+        chat.messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_chatgpt_keeps_branches() {
+        let import = parse_import(
+            r#"[{
+                "conversation_id": "c1",
+                "title": "Borrowing",
+                "create_time": 1700000000.5,
+                "update_time": 1700000100.0,
+                "current_node": "a2",
+                "mapping": {
+                    "root": { "message": null, "parent": null },
+                    "sys": {
+                        "message": {
+                            "author": { "role": "system" },
+                            "content": { "content_type": "text", "parts": [""] },
+                            "metadata": { "is_visually_hidden_from_conversation": true }
+                        },
+                        "parent": "root"
+                    },
+                    "u1": {
+                        "message": {
+                            "author": { "role": "user" },
+                            "content": { "content_type": "text", "parts": ["What is a borrow?"] },
+                            "create_time": 1700000001.0
+                        },
+                        "parent": "sys"
+                    },
+                    "tool": {
+                        "message": {
+                            "author": { "role": "tool" },
+                            "content": { "content_type": "text", "parts": ["search results"] },
+                            "create_time": 1700000002.0
+                        },
+                        "parent": "u1"
+                    },
+                    "a1": {
+                        "message": {
+                            "author": { "role": "assistant" },
+                            "content": { "content_type": "text", "parts": ["First answer"] },
+                            "create_time": 1700000003.0,
+                            "metadata": { "model_slug": "gpt-4o" }
+                        },
+                        "parent": "tool"
+                    },
+                    "a2": {
+                        "message": {
+                            "author": { "role": "assistant" },
+                            "content": { "content_type": "text", "parts": ["Second answer"] },
+                            "create_time": 1700000004.0
+                        },
+                        "parent": "u1"
+                    }
+                }
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(import.source, ImportSource::ChatGpt);
+
+        let chat = &import.chats[0];
+        assert_eq!(chat.source_id, "c1");
+        assert_eq!(chat.title, "Borrowing");
+        assert_eq!(
+            contents(chat),
+            vec!["What is a borrow?", "First answer", "Second answer"]
+        );
+        assert_eq!(chat.messages[0].parent, None);
+        assert_eq!(chat.messages[1].parent, Some(0));
+        assert_eq!(chat.messages[2].parent, Some(0));
+        assert_eq!(chat.messages[1].model.as_deref(), Some("gpt-4o"));
+        assert_eq!(chat.active_leaf, Some(2));
+    }
+
+    #[test]
+    fn test_claude_links_messages_in_order() {
+        let import = parse_import(
+            r#"[{
+                "uuid": "c1",
+                "name": "",
+                "created_at": "2024-03-01T12:00:00.000000Z",
+                "updated_at": "2024-03-01T12:05:00.000000Z",
+                "chat_messages": [
+                    {
+                        "uuid": "m1",
+                        "sender": "human",
+                        "text": "Hello",
+                        "content": [{ "type": "text", "text": "Hello" }],
+                        "created_at": "2024-03-01T12:00:00.000000Z"
+                    },
+                    {
+                        "uuid": "m2",
+                        "sender": "assistant",
+                        "text": "",
+                        "content": [
+                            { "type": "thinking", "thinking": "..." },
+                            { "type": "text", "text": "Hi there" }
+                        ],
+                        "created_at": "2024-03-01T11:00:00.000000Z"
+                    }
+                ]
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(import.source, ImportSource::Claude);
+
+        let chat = &import.chats[0];
+        assert_eq!(chat.title, "Untitled Chat");
+        assert_eq!(contents(chat), vec!["Hello", "Hi there"]);
+        assert_eq!(chat.messages[1].role, "assistant");
+        assert_eq!(chat.messages[1].parent, Some(0));
+        // Replies are never dated before the message they reply to.
+        assert_eq!(chat.messages[1].created_at, chat.messages[0].created_at);
+    }
+
+    #[test]
+    fn test_open_webui_uses_history() {
+        let import = parse_import(
+            r#"[{
+                "id": "c1",
+                "title": "Local models",
+                "created_at": 1700000000,
+                "updated_at": 1700000100,
+                "chat": {
+                    "history": {
+                        "currentId": "a1",
+                        "messages": {
+                            "u1": { "id": "u1", "parentId": null, "role": "user", "content": "Hi", "timestamp": 1700000001 },
+                            "a1": { "id": "a1", "parentId": "u1", "role": "assistant", "content": "Hello", "timestamp": 1700000002, "model": "llama3.2" },
+                            "a2": { "id": "a2", "parentId": "u1", "role": "assistant", "content": "Hey", "timestamp": 1700000003 }
+                        }
+                    }
+                }
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(import.source, ImportSource::OpenWebUi);
+
+        let chat = &import.chats[0];
+        assert_eq!(contents(chat), vec!["Hi", "Hello", "Hey"]);
+        assert_eq!(chat.active_leaf, Some(1));
+        assert_eq!(chat.messages[1].model.as_deref(), Some("llama3.2"));
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(matches!(
+            parse_import(r#"[{ "foo": 1 }]"#),
+            Err(ImportError::UnknownFormat)
+        ));
+        assert!(matches!(
+            parse_import("not json"),
+            Err(ImportError::InvalidJson(_))
+        ));
+    }
+
+    fn import_db() -> Connection {
+        let db_connection = Connection::open_in_memory().unwrap();
+        db_connection
+            .execute_batch(
+                "
+                CREATE TABLE chats (
+                    id TEXT PRIMARY KEY, title TEXT, created_at DATETIME, edited_at DATETIME,
+                    active_leaf_id TEXT, import_source TEXT, import_id TEXT
+                );
+                CREATE TABLE messages (
+                    id TEXT PRIMARY KEY, chat_id TEXT, parent_id TEXT, role TEXT, content TEXT,
+                    created_at DATETIME, edited_at DATETIME, model TEXT
+                );
+                ",
+            )
+            .unwrap();
+        db_connection
+    }
+
+    const CLAUDE_CHAT: &str = r#"{
+        "uuid": "c1",
+        "name": "Hello",
+        "chat_messages": [
+            { "uuid": "m1", "sender": "human", "text": "Hello" },
+            { "uuid": "m2", "sender": "assistant", "text": "Hi" }
+        ]
+    }"#;
+
+    #[test]
+    fn test_reimport_skips_duplicates() {
+        let db_connection = import_db();
+
+        let import = parse_import(&format!("[{CLAUDE_CHAT}]")).unwrap();
+        let summary = preview_import(&db_connection, &import).unwrap();
+        assert_eq!(
+            (summary.chats, summary.messages, summary.duplicates),
+            (1, 2, 0)
+        );
+
+        assert_eq!(write_import(&db_connection, &import).unwrap().len(), 1);
+
+        let summary = preview_import(&db_connection, &import).unwrap();
+        assert_eq!(
+            (summary.chats, summary.messages, summary.duplicates),
+            (0, 0, 1)
+        );
+        assert!(write_import(&db_connection, &import).unwrap().is_empty());
+
+        let leaf_content: String = db_connection
+            .query_row(
+                "SELECT content FROM messages JOIN chats ON chats.active_leaf_id = messages.id",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leaf_content, "Hi");
+    }
+
+    #[test]
+    fn test_repeated_chats_are_counted_once() {
+        let db_connection = import_db();
+
+        let import = parse_import(&format!("[{CLAUDE_CHAT}, {CLAUDE_CHAT}]")).unwrap();
+        let summary = preview_import(&db_connection, &import).unwrap();
+        assert_eq!(
+            (summary.chats, summary.messages, summary.duplicates),
+            (1, 2, 1)
+        );
+        assert_eq!(write_import(&db_connection, &import).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{ImportedChat, RawMessage, build_chat, from_epoch_seconds, role_from_str};
+
+/// A chat from an Open WebUI export. Exports of every chat wrap each one with
+/// its metadata, while exports of a single chat may only contain the chat itself.
+#[derive(Deserialize)]
+struct ExportedChat {
+    id: Option<String>,
+    title: Option<String>,
+    created_at: Option<f64>,
+    updated_at: Option<f64>,
+    chat: Option<Chat>,
+    #[serde(flatten)]
+    inline_chat: Chat,
+}
+
+#[derive(Deserialize, Default)]
+struct Chat {
+    id: Option<String>,
+    title: Option<String>,
+    timestamp: Option<f64>,
+    history: Option<History>,
+    #[serde(default)]
+    messages: Vec<Message>,
+}
+
+/// Every message of the chat, including the ones on inactive branches.
+#[derive(Deserialize)]
+struct History {
+    #[serde(default)]
+    messages: BTreeMap<String, Message>,
+    #[serde(rename = "currentId")]
+    current_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+    #[serde(rename = "parentId")]
+    parent_id: Option<String>,
+    role: String,
+    #[serde(default)]
+    content: String,
+    timestamp: Option<f64>,
+    model: Option<String>,
+}
+
+pub(super) fn parse(conversations: Vec<Value>) -> Vec<ImportedChat> {
+    conversations
+        .into_iter()
+        .filter_map(|conversation| serde_json::from_value::<ExportedChat>(conversation).ok())
+        .filter_map(parse_chat)
+        .collect()
+}
+
+fn parse_chat(exported: ExportedChat) -> Option<ImportedChat> {
+    let chat = exported.chat.unwrap_or(exported.inline_chat);
+    let source_id = exported.id.or(chat.id)?;
+
+    let (messages, current_id) = match chat.history {
+        Some(history) if !history.messages.is_empty() => (
+            history.messages.into_values().collect::<Vec<_>>(),
+            history.current_id,
+        ),
+        _ => (chat.messages, None),
+    };
+
+    let raw_messages = messages
+        .into_iter()
+        .map(|message| RawMessage {
+            id: message.id,
+            parent_id: message.parent_id,
+            role: role_from_str(&message.role),
+            content: message.content,
+            // Older versions stored timestamps in milliseconds.
+            created_at: message
+                .timestamp
+                .map(|timestamp| match timestamp > 1e11 {
+                    true => timestamp / 1000.,
+                    false => timestamp,
+                })
+                .and_then(from_epoch_seconds),
+            model: message.model,
+        })
+        .collect();
+
+    build_chat(
+        source_id,
+        exported.title.or(chat.title),
+        exported
+            .created_at
+            .or(chat.timestamp.map(|timestamp| timestamp / 1000.))
+            .and_then(from_epoch_seconds),
+        exported.updated_at.and_then(from_epoch_seconds),
+        raw_messages,
+        current_id.as_deref(),
+    )
+}
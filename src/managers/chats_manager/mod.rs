@@ -1,59 +1,359 @@
 use std::{cmp::Reverse, sync::Arc};
 
-use chrono::NaiveDateTime;
-use futures::future::AbortHandle;
+use chrono::{Duration, NaiveDateTime, Utc};
 use gpui::{App, AppContext, Entity};
 use granular_btreemap::GranularBTreeMap;
 use rusqlite::Connection;
 
-use crate::managers::{DbError, UniqueId};
+use crate::managers::{
+    Assistant, DbError, GenerationParameters, MessageStatus, Migration, UniqueId,
+    add_column_if_missing,
+};
+
+mod attachment;
+pub use attachment::*;
 
 mod chat;
 pub use chat::*;
 
+mod export;
+pub use export::*;
+
+mod generation;
+pub use generation::*;
+
+mod import;
+pub use import::{ImportSource, ImportSummary, ParsedImport, parse_import};
+
+mod search;
+pub use search::{ChatSearchResult, MessageSearchMatch};
+
 type ChatsMap = GranularBTreeMap<UniqueId, Entity<Chat>, Reverse<NaiveDateTime>>;
 
+/// Full-text index over the content of messages, kept in sync by triggers.
+const MESSAGES_FTS_SQL: &str = "
+    CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+        content,
+        content = 'messages',
+        content_rowid = 'rowid',
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content)
+            VALUES ('delete', old.rowid, old.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content)
+            VALUES ('delete', old.rowid, old.content);
+        INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+    END;
+
+    INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+";
+
+/// Keeps streaming replies out of the full-text index until they finish, rather than
+/// indexing the whole reply again for every chunk written to it. A message is indexed
+/// exactly when its status isn't `streaming`, so the triggers only remove what they added.
+const MESSAGES_FTS_STATUS_SQL: &str = "
+    DROP TRIGGER IF EXISTS messages_fts_insert;
+    DROP TRIGGER IF EXISTS messages_fts_delete;
+    DROP TRIGGER IF EXISTS messages_fts_update;
+
+    CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages
+        WHEN new.status IS NOT 'streaming'
+    BEGIN
+        INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+    END;
+
+    CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages
+        WHEN old.status IS NOT 'streaming'
+    BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content)
+            VALUES ('delete', old.rowid, old.content);
+    END;
+
+    CREATE TRIGGER messages_fts_update AFTER UPDATE OF content, status ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content)
+            SELECT 'delete', old.rowid, old.content WHERE old.status IS NOT 'streaming';
+        INSERT INTO messages_fts (rowid, content)
+            SELECT new.rowid, new.content WHERE new.status IS NOT 'streaming';
+    END;
+
+    INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+    INSERT INTO messages_fts (messages_fts, rowid, content)
+        SELECT 'delete', rowid, content FROM messages WHERE status = 'streaming';
+";
+
+/// How long a chat stays in the trash before it is permanently deleted.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// A lightweight description of a chat that isn't part of the active chats list.
+#[derive(Clone)]
+pub struct ChatSummary {
+    pub chat_id: UniqueId,
+    pub title: String,
+    /// When the chat was archived or moved to the trash.
+    pub timestamp: NaiveDateTime,
+}
+
+/// The lists of chats that aren't active, each read with its own fixed query.
+#[derive(Clone, Copy)]
+enum InactiveChats {
+    Archived,
+    Trashed,
+}
+
+impl InactiveChats {
+    fn query(self) -> &'static str {
+        match self {
+            // Trashed chats are never shown in the archive, even if they were archived first.
+            Self::Archived => {
+                "SELECT id, title, archived_at FROM chats
+                WHERE archived_at IS NOT NULL AND deleted_at IS NULL
+                ORDER BY archived_at DESC"
+            }
+            Self::Trashed => {
+                "SELECT id, title, deleted_at FROM chats
+                WHERE deleted_at IS NOT NULL
+                ORDER BY deleted_at DESC"
+            }
+        }
+    }
+}
+
 pub struct ChatsManager {
     db_connection: Option<Arc<Connection>>,
     chats: Entity<Option<ChatsMap>>,
     current_chat_id: Entity<Option<UniqueId>>,
-    /// Tracks whether a response is currently being streamed
-    pub is_streaming: Entity<bool>,
-    /// Handle to abort the current streaming task
-    pub streaming_abort_handle: Entity<Option<AbortHandle>>,
+    pub archived_chats: Entity<Vec<ChatSummary>>,
+    pub trashed_chats: Entity<Vec<ChatSummary>>,
+    /// A message the chat view should scroll to once it is shown.
+    pub scroll_target: Entity<Option<UniqueId>>,
+    /// Parameters the next new chat starts with, set before it's created.
+    pub new_chat_parameters: Entity<GenerationParameters>,
 }
 
 impl<'a> ChatsManager {
+    pub const MIGRATIONS: &'static [Migration] = &[
+        Migration::sql(
+            1,
+            "create chats and messages",
+            "
+            CREATE TABLE IF NOT EXISTS chats (
+                id         TEXT PRIMARY KEY,
+                title      TEXT,
+                created_at DATETIME NOT NULL,
+                edited_at  DATETIME NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS messages (
+                id         TEXT PRIMARY KEY,
+                chat_id    TEXT NOT NULL,
+
+                role       TEXT NOT NULL
+                    CHECK (role IN ('system', 'user', 'assistant')),
+
+                content    TEXT NOT NULL,
+                created_at DATETIME NOT NULL,
+                edited_at  DATETIME NOT NULL,
+
+                FOREIGN KEY (chat_id)
+                    REFERENCES chats(id)
+                    ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_messages_chat
+                ON messages(chat_id, created_at);
+            ",
+        ),
+        Migration::rust(2, "archive and trash chats", add_archive_and_trash_columns),
+        Migration::sql(
+            3,
+            "record how assistant messages were generated",
+            "
+            ALTER TABLE messages ADD COLUMN provider_id TEXT;
+            ALTER TABLE messages ADD COLUMN model TEXT;
+            ALTER TABLE messages ADD COLUMN prompt_tokens INTEGER;
+            ALTER TABLE messages ADD COLUMN completion_tokens INTEGER;
+            ALTER TABLE messages ADD COLUMN time_to_first_token_ms INTEGER;
+            ALTER TABLE messages ADD COLUMN duration_ms INTEGER;
+            ALTER TABLE messages ADD COLUMN finish_reason TEXT;
+            ",
+        ),
+        Migration::sql(
+            4,
+            "branching messages",
+            "
+            ALTER TABLE messages ADD COLUMN parent_id TEXT;
+            ALTER TABLE chats ADD COLUMN active_leaf_id TEXT;
+
+            -- Existing chats are linear, so every message replies to the one before it.
+            UPDATE messages
+            SET parent_id = (
+                SELECT previous.id
+                FROM messages AS previous
+                WHERE previous.chat_id = messages.chat_id
+                    AND (previous.created_at, previous.rowid) < (messages.created_at, messages.rowid)
+                ORDER BY previous.created_at DESC, previous.rowid DESC
+                LIMIT 1
+            );
+
+            UPDATE chats
+            SET active_leaf_id = (
+                SELECT id
+                FROM messages
+                WHERE messages.chat_id = chats.id
+                ORDER BY created_at DESC, rowid DESC
+                LIMIT 1
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_messages_parent
+                ON messages(parent_id);
+            ",
+        ),
+        Migration::sql(5, "full-text search over messages", MESSAGES_FTS_SQL),
+        Migration::sql(
+            6,
+            "remember where imported chats came from",
+            "
+            ALTER TABLE chats ADD COLUMN import_source TEXT;
+            ALTER TABLE chats ADD COLUMN import_id TEXT;
+
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_chats_import
+                ON chats(import_source, import_id)
+                WHERE import_id IS NOT NULL;
+            ",
+        ),
+        Migration::sql(
+            7,
+            "per-chat system prompts",
+            "ALTER TABLE chats ADD COLUMN system_prompt TEXT;",
+        ),
+        Migration::sql(
+            8,
+            "remember the assistant a chat was started from",
+            "ALTER TABLE chats ADD COLUMN assistant_id TEXT;",
+        ),
+        Migration::sql(
+            9,
+            "remember the model of each chat",
+            "
+            ALTER TABLE chats ADD COLUMN provider_id TEXT;
+            ALTER TABLE chats ADD COLUMN provider_name TEXT;
+            ALTER TABLE chats ADD COLUMN model TEXT;
+
+            -- Existing chats keep the model of their latest reply.
+            UPDATE chats SET (provider_id, model) = (
+                SELECT provider_id, model FROM messages
+                WHERE messages.chat_id = chats.id
+                    AND messages.provider_id IS NOT NULL
+                    AND messages.model IS NOT NULL
+                ORDER BY messages.created_at DESC, messages.rowid DESC
+                LIMIT 1
+            );
+
+            UPDATE chats
+            SET provider_name = (SELECT name FROM providers WHERE providers.id = chats.provider_id)
+            WHERE provider_id IS NOT NULL;
+            ",
+        ),
+        Migration::sql(
+            10,
+            "per-chat generation parameters",
+            "ALTER TABLE chats ADD COLUMN parameters TEXT;",
+        ),
+        Migration::sql(
+            11,
+            "record the status of assistant messages",
+            "
+            ALTER TABLE messages ADD COLUMN status TEXT
+                CHECK (status IN ('streaming', 'complete', 'cancelled', 'errored', 'truncated'));
+
+            -- Replies that never recorded a duration were interrupted mid-stream.
+            UPDATE messages
+            SET status = CASE
+                WHEN finish_reason IN ('length', 'max_tokens') THEN 'truncated'
+                WHEN provider_id IS NOT NULL AND duration_ms IS NULL THEN 'cancelled'
+                ELSE 'complete'
+            END
+            WHERE role = 'assistant';
+            ",
+        ),
+        Migration::sql(
+            12,
+            "keep request errors apart from message content",
+            "ALTER TABLE messages ADD COLUMN error TEXT;",
+        ),
+        Migration::sql(
+            13,
+            "attachments",
+            "
+            CREATE TABLE IF NOT EXISTS attachments (
+                message_id TEXT NOT NULL,
+                position   INTEGER NOT NULL,
+                name       TEXT NOT NULL,
+                mime_type  TEXT NOT NULL,
+                data       BLOB NOT NULL,
+
+                PRIMARY KEY (message_id, position),
+
+                FOREIGN KEY (message_id)
+                    REFERENCES messages(id)
+                    ON DELETE CASCADE
+            );
+            ",
+        ),
+        // Documents keep only their text, so `data` becomes optional. SQLite can't drop
+        // a `NOT NULL` constraint, so the table is rebuilt.
+        Migration::sql(
+            14,
+            "document attachments",
+            "
+            CREATE TABLE attachments_new (
+                message_id TEXT NOT NULL,
+                position   INTEGER NOT NULL,
+                name       TEXT NOT NULL,
+                mime_type  TEXT NOT NULL,
+                data       BLOB,
+                text       TEXT,
+
+                PRIMARY KEY (message_id, position),
+
+                FOREIGN KEY (message_id)
+                    REFERENCES messages(id)
+                    ON DELETE CASCADE
+            );
+
+            INSERT INTO attachments_new (message_id, position, name, mime_type, data)
+                SELECT message_id, position, name, mime_type, data FROM attachments;
+
+            DROP TABLE attachments;
+            ALTER TABLE attachments_new RENAME TO attachments;
+            ",
+        ),
+        Migration::sql(
+            15,
+            "index replies once they finish streaming",
+            MESSAGES_FTS_STATUS_SQL,
+        ),
+    ];
+
     pub fn new(cx: &mut App) -> Self {
         Self {
             db_connection: None,
             chats: cx.new(|_cx| None),
             current_chat_id: cx.new(|_cx| None),
-            is_streaming: cx.new(|_cx| false),
-            streaming_abort_handle: cx.new(|_cx| None),
-        }
-    }
-
-    pub fn set_streaming(&self, cx: &mut App, streaming: bool) {
-        self.is_streaming.update(cx, |is_streaming, cx| {
-            *is_streaming = streaming;
-            cx.notify();
-        });
-    }
-
-    pub fn set_abort_handle(&self, cx: &mut App, handle: Option<AbortHandle>) {
-        self.streaming_abort_handle.update(cx, |abort_handle, cx| {
-            *abort_handle = handle;
-            cx.notify();
-        });
-    }
-
-    pub fn cancel_streaming(&self, cx: &mut App) {
-        if let Some(handle) = self.streaming_abort_handle.read(cx).as_ref() {
-            handle.abort();
+            archived_chats: cx.new(|_cx| Vec::new()),
+            trashed_chats: cx.new(|_cx| Vec::new()),
+            scroll_target: cx.new(|_cx| None),
+            new_chat_parameters: cx.new(|_cx| GenerationParameters::default()),
         }
-        self.set_abort_handle(cx, None);
-        self.set_streaming(cx, false);
     }
 
     pub fn init(
@@ -63,39 +363,8 @@ impl<'a> ChatsManager {
     ) -> Result<(), DbError> {
         self.db_connection = Some(db_connection.clone());
 
-        db_connection
-            .execute_batch(
-                "
-                PRAGMA foreign_keys = ON;
-
-                CREATE TABLE IF NOT EXISTS chats (
-                    id         TEXT PRIMARY KEY,
-                    title      TEXT,
-                    created_at DATETIME NOT NULL,
-                    edited_at  DATETIME NOT NULL
-                );
-
-                CREATE TABLE IF NOT EXISTS messages (
-                    id         TEXT PRIMARY KEY,
-                    chat_id    TEXT NOT NULL,
-
-                    role       TEXT NOT NULL
-                        CHECK (role IN ('system', 'user', 'assistant')),
-
-                    content    TEXT NOT NULL,
-                    created_at DATETIME NOT NULL,
-                    edited_at  DATETIME NOT NULL,
-
-                    FOREIGN KEY (chat_id)
-                        REFERENCES chats(id)
-                        ON DELETE CASCADE
-                );
-
-                CREATE INDEX IF NOT EXISTS idx_messages_chat
-                    ON messages(chat_id, created_at);
-                ",
-            )
-            .unwrap();
+        self.purge_expired_trash()?;
+        self.reconcile_interrupted_replies()?;
 
         let raw_chats = self.load_chats_from_db(cx)?;
 
@@ -114,6 +383,8 @@ impl<'a> ChatsManager {
             *chats = Some(new_chats);
         });
 
+        self.refresh_inactive_chats(cx)?;
+
         Ok(())
     }
 
@@ -121,11 +392,22 @@ impl<'a> ChatsManager {
         &self.current_chat_id
     }
 
+    /// Opens a chat, loading its newest messages if it hasn't been opened before.
     pub fn set_current_chat(&self, cx: &mut App, chat_id: UniqueId) {
         self.current_chat_id.update(cx, |current_chat_id, cx| {
             *current_chat_id = Some(chat_id);
             cx.notify();
         });
+
+        let loaded = self.get_current_chat(cx).and_then(|chat| match chat {
+            Some(chat) => chat
+                .update(cx, |chat, cx| chat.load_messages(cx))
+                .map_err(DbError::SqliteError),
+            None => Ok(()),
+        });
+        if let Err(err) = loaded {
+            tracing::error!("failed to load messages: {err}");
+        }
     }
 
     pub fn get_current_chat(&'a self, cx: &'a mut App) -> Result<Option<Entity<Chat>>, DbError> {
@@ -138,13 +420,13 @@ impl<'a> ChatsManager {
             return Ok(None);
         };
 
-        self.chats.update(cx, |chats, cx| {
+        let chat = self.chats.update(cx, |chats, cx| {
             let chats = chats
                 .as_mut()
                 .ok_or_else(|| DbError::MissingData("chats"))?;
 
             match chats.get(&current_chat_id) {
-                Some(chat) => Ok(Some(chat.clone())),
+                Some(chat) => Ok(chat.clone()),
                 None => {
                     let chat = Chat::load_from_db(
                         cx,
@@ -158,24 +440,43 @@ impl<'a> ChatsManager {
 
                     let chat = cx.new(|_cx| chat);
                     chats.insert(current_chat_id, chat.clone(), Reverse(edited_at));
-                    Ok(Some(chat))
+                    Ok(chat)
                 }
             }
-        })
+        })?;
+
+        Ok(Some(chat))
     }
 
-    pub fn create_chat(&self, cx: &mut App) -> Result<Entity<Chat>, DbError> {
+    /// Creates a chat, optionally starting from an assistant whose system prompt it copies.
+    /// The chat takes over [`Self::new_chat_parameters`].
+    pub fn create_chat(
+        &self,
+        cx: &mut App,
+        assistant: Option<(&UniqueId, &Assistant)>,
+    ) -> Result<Entity<Chat>, DbError> {
         let db_connection = self
             .db_connection
             .as_ref()
             .ok_or_else(|| DbError::MissingData("database connection"))?;
 
-        let chat = Chat::new(cx, db_connection.clone(), self.chats.clone())
-            .map_err(|err| DbError::SqliteError(err))?;
+        let parameters = self.new_chat_parameters.read(cx).clone();
+        let chat = Chat::new(
+            cx,
+            db_connection.clone(),
+            self.chats.clone(),
+            assistant,
+            parameters,
+        )?;
         let chat_id = chat.chat_id.clone();
         let edited_at = chat.edited_at.clone();
         let chat = cx.new(|_cx| chat);
 
+        self.new_chat_parameters.update(cx, |parameters, cx| {
+            *parameters = GenerationParameters::default();
+            cx.notify();
+        });
+
         self.chats.update(cx, |chats, cx| {
             let chats = chats.get_or_insert_default();
             chats.insert(chat_id, chat.clone(), Reverse(edited_at));
@@ -185,6 +486,335 @@ impl<'a> ChatsManager {
         Ok(chat)
     }
 
+    /// Searches the content of messages in chats that aren't archived or trashed.
+    pub fn search_messages(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<ChatSearchResult>, DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        search::search_messages(db_connection, query, limit).map_err(DbError::SqliteError)
+    }
+
+    /// Opens a chat on the branch containing a message, and asks the chat view
+    /// to scroll to it.
+    pub fn open_message(
+        &self,
+        cx: &mut App,
+        chat_id: &UniqueId,
+        message_id: &UniqueId,
+    ) -> Result<(), DbError> {
+        self.set_current_chat(cx, chat_id.clone());
+
+        let chat = self
+            .get_current_chat(cx)?
+            .ok_or_else(|| DbError::MissingData("chat"))?;
+
+        chat.update(cx, |chat, cx| chat.switch_branch(cx, message_id))
+            .map_err(DbError::SqliteError)?;
+
+        self.scroll_target.update(cx, |scroll_target, cx| {
+            *scroll_target = Some(message_id.clone());
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    /// Renders chats into files of the given format, loading any messages that
+    /// haven't been loaded yet.
+    pub fn export_chats(
+        &self,
+        cx: &mut App,
+        chat_ids: &[UniqueId],
+        format: ExportFormat,
+    ) -> Result<Vec<ExportFile>, DbError> {
+        let mut exports = Vec::with_capacity(chat_ids.len());
+
+        for chat_id in chat_ids {
+            let chat = self.get_or_load_chat(cx, chat_id)?;
+
+            chat.update(cx, |chat, cx| chat.load_all_messages(cx))
+                .map_err(DbError::SqliteError)?;
+
+            exports.push(ChatExport::from_chat(chat.read(cx), cx));
+        }
+
+        export_files(&exports, format).map_err(DbError::SerializationError)
+    }
+
+    /// Counts the chats and messages an import would add, and how many of its
+    /// chats were already imported.
+    pub fn preview_import(&self, import: &ParsedImport) -> Result<ImportSummary, DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        import::preview_import(db_connection, import).map_err(DbError::SqliteError)
+    }
+
+    /// Writes the chats of an import that weren't imported before and adds them
+    /// to the chats list.
+    pub fn import_chats(
+        &self,
+        cx: &mut App,
+        import: &ParsedImport,
+    ) -> Result<ImportSummary, DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        let summary =
+            import::preview_import(db_connection, import).map_err(DbError::SqliteError)?;
+        let chat_ids = import::write_import(db_connection, import).map_err(DbError::SqliteError)?;
+
+        for chat_id in chat_ids {
+            let chat = Chat::load_from_db(
+                cx,
+                db_connection.clone(),
+                chat_id.clone(),
+                self.chats.clone(),
+            )
+            .map_err(DbError::SqliteError)?;
+            let edited_at = chat.edited_at;
+            let chat = cx.new(|_cx| chat);
+
+            self.chats.update(cx, |chats, cx| {
+                let chats = chats.get_or_insert_default();
+                chats.insert(chat_id, chat, Reverse(edited_at));
+                cx.notify();
+            });
+        }
+
+        Ok(summary)
+    }
+
+    /// Moves a chat out of the chats list and into the archive.
+    pub fn archive_chat(&self, cx: &mut App, chat_id: &UniqueId) -> Result<(), DbError> {
+        let chat = self.get_or_load_chat(cx, chat_id)?;
+        chat.update(cx, |chat, cx| chat.cancel_streaming(cx));
+        chat.read(cx).archive().map_err(DbError::SqliteError)?;
+
+        self.remove_from_chats_list(cx, chat_id);
+        self.refresh_inactive_chats(cx)
+    }
+
+    /// Moves a chat to the trash. It can be restored until it is purged
+    /// after [`TRASH_RETENTION_DAYS`].
+    pub fn delete_chat(&self, cx: &mut App, chat_id: &UniqueId) -> Result<(), DbError> {
+        let chat = self.get_or_load_chat(cx, chat_id)?;
+        chat.update(cx, |chat, cx| chat.discard_streaming(cx));
+        chat.read(cx)
+            .move_to_trash()
+            .map_err(DbError::SqliteError)?;
+
+        self.remove_from_chats_list(cx, chat_id);
+        self.refresh_inactive_chats(cx)
+    }
+
+    /// Restores an archived or trashed chat back into the chats list.
+    pub fn restore_chat(&self, cx: &mut App, chat_id: &UniqueId) -> Result<(), DbError> {
+        let chat = self.get_or_load_chat(cx, chat_id)?;
+        chat.read(cx).restore().map_err(DbError::SqliteError)?;
+
+        let edited_at = chat.read(cx).edited_at;
+
+        self.chats.update(cx, |chats, cx| {
+            let chats = chats.get_or_insert_default();
+            chats.insert(chat_id.clone(), chat, Reverse(edited_at));
+            cx.notify();
+        });
+
+        self.refresh_inactive_chats(cx)
+    }
+
+    /// Permanently deletes a chat and all of its messages.
+    pub fn delete_chat_permanently(&self, cx: &mut App, chat_id: &UniqueId) -> Result<(), DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        self.discard_streaming(cx, chat_id);
+
+        db_connection
+            .execute("DELETE FROM chats WHERE id = ?1", [chat_id])
+            .map_err(DbError::SqliteError)?;
+
+        self.remove_from_chats_list(cx, chat_id);
+        self.refresh_inactive_chats(cx)
+    }
+
+    /// Permanently deletes every chat in the trash.
+    pub fn empty_trash(&self, cx: &mut App) -> Result<(), DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        let trashed_chat_ids = self
+            .trashed_chats
+            .read(cx)
+            .iter()
+            .map(|summary| summary.chat_id.clone())
+            .collect::<Vec<_>>();
+        for chat_id in &trashed_chat_ids {
+            self.discard_streaming(cx, chat_id);
+        }
+
+        db_connection
+            .execute("DELETE FROM chats WHERE deleted_at IS NOT NULL", [])
+            .map_err(DbError::SqliteError)?;
+
+        self.refresh_inactive_chats(cx)
+    }
+
+    /// Returns the chat from the chats list, or loads it from the database
+    /// if it's archived or trashed.
+    fn get_or_load_chat(&self, cx: &mut App, chat_id: &UniqueId) -> Result<Entity<Chat>, DbError> {
+        if let Some(chat) = self
+            .chats
+            .read(cx)
+            .as_ref()
+            .and_then(|chats| chats.get(chat_id))
+        {
+            return Ok(chat.clone());
+        }
+
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        let chat = Chat::load_from_db(
+            cx,
+            db_connection.clone(),
+            chat_id.clone(),
+            self.chats.clone(),
+        )
+        .map_err(DbError::SqliteError)?;
+
+        Ok(cx.new(|_cx| chat))
+    }
+
+    /// Aborts the reply being streamed into a chat, so nothing more is written to it.
+    /// Only loaded chats can be streaming.
+    fn discard_streaming(&self, cx: &mut App, chat_id: &UniqueId) {
+        let chat = self
+            .chats
+            .read(cx)
+            .as_ref()
+            .and_then(|chats| chats.get(chat_id))
+            .cloned();
+
+        if let Some(chat) = chat {
+            chat.update(cx, |chat, cx| chat.discard_streaming(cx));
+        }
+    }
+
+    fn remove_from_chats_list(&self, cx: &mut App, chat_id: &UniqueId) {
+        self.chats.update(cx, |chats, cx| {
+            let Some(chats) = chats else { return };
+            chats.remove(chat_id);
+            cx.notify();
+        });
+
+        if self.current_chat_id.read(cx).as_ref() == Some(chat_id) {
+            self.current_chat_id.update(cx, |current_chat_id, cx| {
+                *current_chat_id = None;
+                cx.notify();
+            });
+        }
+    }
+
+    /// Reloads the archived and trashed chat lists from the database.
+    fn refresh_inactive_chats(&self, cx: &mut App) -> Result<(), DbError> {
+        let archived = self.load_chat_summaries(InactiveChats::Archived)?;
+        let trashed = self.load_chat_summaries(InactiveChats::Trashed)?;
+
+        self.archived_chats.update(cx, |archived_chats, cx| {
+            *archived_chats = archived;
+            cx.notify();
+        });
+
+        self.trashed_chats.update(cx, |trashed_chats, cx| {
+            *trashed_chats = trashed;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    fn load_chat_summaries(&self, list: InactiveChats) -> Result<Vec<ChatSummary>, DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        let mut stmt = db_connection
+            .prepare(list.query())
+            .map_err(DbError::SqliteError)?;
+
+        stmt.query_map([], |row| {
+            Ok(ChatSummary {
+                chat_id: row.get(0)?,
+                title: row.get(1)?,
+                timestamp: row.get(2)?,
+            })
+        })
+        .map_err(DbError::SqliteError)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(DbError::SqliteError)
+    }
+
+    /// Permanently deletes chats that have been in the trash for longer than
+    /// [`TRASH_RETENTION_DAYS`].
+    fn purge_expired_trash(&self) -> Result<(), DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        let cutoff = Utc::now().naive_utc() - Duration::days(TRASH_RETENTION_DAYS);
+
+        db_connection
+            .execute(
+                "DELETE FROM chats WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+                [&cutoff],
+            )
+            .map_err(DbError::SqliteError)?;
+
+        Ok(())
+    }
+
+    /// Marks replies that were still streaming when the app last quit as cancelled,
+    /// so they can be continued.
+    fn reconcile_interrupted_replies(&self) -> Result<(), DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        let interrupted = db_connection
+            .execute(
+                "UPDATE messages SET status = ?1 WHERE status = ?2",
+                (MessageStatus::Cancelled, MessageStatus::Streaming),
+            )
+            .map_err(DbError::SqliteError)?;
+
+        if interrupted > 0 {
+            tracing::info!("marked {interrupted} interrupted replies as cancelled");
+        }
+
+        Ok(())
+    }
+
     pub fn chats_iter(&'a self, cx: &'a App) -> Option<impl Iterator<Item = &'a Chat>> {
         self.chats
             .read(cx)
@@ -192,6 +822,8 @@ impl<'a> ChatsManager {
             .map(|chats| chats.values().map(|chat| chat.read(cx)))
     }
 
+    /// Loads the headers of the active chats, leaving their messages to be loaded
+    /// once they're opened.
     fn load_chats_from_db(&'a self, cx: &mut App) -> Result<Box<[Chat]>, DbError> {
         let db_connection = self
             .db_connection
@@ -200,26 +832,146 @@ impl<'a> ChatsManager {
             .clone();
 
         let mut stmt = db_connection
-            .prepare(
+            .prepare(&format!(
                 r#"
-                SELECT
-                    id
+                SELECT {CHAT_HEADER_COLUMNS}
                 FROM chats
+                WHERE archived_at IS NULL AND deleted_at IS NULL
                 ORDER BY edited_at ASC
-                "#,
-            )
+                "#
+            ))
             .map_err(|err| DbError::SqliteError(err))?;
 
-        stmt.query_map([], |row| {
-            Chat::load_from_db(
-                cx,
-                db_connection.clone(),
-                UniqueId::from_string(row.get::<_, String>(0)?),
-                self.chats.clone(),
+        let headers = stmt
+            .query_map([], ChatHeader::from_row)
+            .map_err(|err| DbError::SqliteError(err))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|err| DbError::SqliteError(err))?;
+
+        Ok(headers
+            .into_iter()
+            .map(|header| Chat::from_header(cx, db_connection.clone(), header, self.chats.clone()))
+            .collect())
+    }
+}
+
+fn add_archive_and_trash_columns(db_connection: &Connection) -> rusqlite::Result<()> {
+    // Builds from before migrations existed may already have added these columns.
+    add_column_if_missing(db_connection, "chats", "archived_at", "DATETIME")?;
+    add_column_if_missing(db_connection, "chats", "deleted_at", "DATETIME")
+}
+
+#[cfg(test)]
+mod tests {
+    use anyml::MessageRole;
+    use futures::{
+        executor::block_on,
+        future::{AbortHandle, Abortable, Aborted, pending},
+    };
+    use gpui::TestAppContext;
+
+    use super::*;
+    use crate::managers::{Migrator, ModelsManager};
+
+    fn chats_manager(cx: &mut App) -> ChatsManager {
+        let db_connection = Arc::new(Connection::open_in_memory().unwrap());
+
+        let mut migrator = Migrator::new();
+        migrator.register("models", ModelsManager::MIGRATIONS);
+        migrator.register("chats", ChatsManager::MIGRATIONS);
+        migrator.run(&db_connection).unwrap();
+
+        let mut chats_manager = ChatsManager::new(cx);
+        chats_manager.init(cx, db_connection).unwrap();
+        chats_manager
+    }
+
+    #[gpui::test]
+    fn test_deleting_a_streaming_chat_aborts_the_reply(cx: &mut TestAppContext) {
+        let (chats_manager, chat, message_id, reply) = cx.update(|cx| {
+            let chats_manager = chats_manager(cx);
+            let chat = chats_manager.create_chat(cx, None).unwrap();
+            let chat_id = chat.read(cx).chat_id.clone();
+
+            let message_id = chat
+                .update(cx, |chat, cx| {
+                    chat.push_message(cx, &chat_id, "", MessageRole::Assistant, Vec::new())
+                })
+                .unwrap();
+
+            // Stands in for the task writing the reply, which stops once aborted.
+            let (abort_handle, abort_registration) = AbortHandle::new_pair();
+            let reply = Abortable::new(pending::<()>(), abort_registration);
+            chat.update(cx, |chat, cx| {
+                chat.start_streaming(cx, message_id.clone(), abort_handle);
+                chat.push_message_content(cx, &message_id, "Hello")
+            })
+            .unwrap();
+
+            chats_manager.delete_chat(cx, &chat_id).unwrap();
+
+            (chats_manager, chat, message_id, reply)
+        });
+
+        assert_eq!(block_on(reply), Err(Aborted));
+
+        // The aborted task still writes what's left of the reply once it stops.
+        cx.update(|cx| {
+            let generation = GenerationMetadata {
+                status: MessageStatus::Cancelled,
+                ..Default::default()
+            };
+            chat.update(cx, |chat, cx| {
+                chat.finish_reply(cx, &message_id, generation)
+            })
+            .unwrap();
+        });
+        cx.executor()
+            .advance_clock(std::time::Duration::from_secs(1));
+        cx.run_until_parked();
+
+        cx.update(|cx| {
+            assert!(!chat.read(cx).is_streaming(cx));
+            // The content buffered before the chat was deleted is dropped with it.
+            assert_eq!(message_content(&chats_manager, &message_id), "");
+        });
+    }
+
+    #[gpui::test]
+    fn test_flushing_into_an_archived_chat(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let chats_manager = chats_manager(cx);
+            let chat = chats_manager.create_chat(cx, None).unwrap();
+            let chat_id = chat.read(cx).chat_id.clone();
+
+            let message_id = chat
+                .update(cx, |chat, cx| {
+                    chat.push_message(cx, &chat_id, "", MessageRole::Assistant, Vec::new())
+                })
+                .unwrap();
+            chat.update(cx, |chat, cx| {
+                chat.push_message_content(cx, &message_id, "Hello")
+            })
+            .unwrap();
+
+            chats_manager.archive_chat(cx, &chat_id).unwrap();
+            chat.update(cx, |chat, cx| chat.flush_pending_content(cx))
+                .unwrap();
+
+            assert_eq!(message_content(&chats_manager, &message_id), "Hello");
+        });
+    }
+
+    fn message_content(chats_manager: &ChatsManager, message_id: &UniqueId) -> String {
+        chats_manager
+            .db_connection
+            .as_ref()
+            .unwrap()
+            .query_row(
+                "SELECT content FROM messages WHERE id = ?1",
+                [message_id],
+                |row| row.get(0),
             )
-        })
-        .map_err(|err| DbError::SqliteError(err))?
-        .collect::<rusqlite::Result<Box<[Chat]>>>()
-        .map_err(|err| DbError::SqliteError(err))
+            .unwrap()
     }
 }
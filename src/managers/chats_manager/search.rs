@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use rusqlite::Connection;
+
+use crate::managers::UniqueId;
+
+/// Marks the start of a highlighted match in the snippets returned by SQLite.
+const HIGHLIGHT_START: char = '\u{2}';
+/// Marks the end of a highlighted match in the snippets returned by SQLite.
+const HIGHLIGHT_END: char = '\u{3}';
+
+/// A chat containing messages that match a search query.
+#[derive(Clone)]
+pub struct ChatSearchResult {
+    pub chat_id: UniqueId,
+    pub title: String,
+    pub matches: Vec<MessageSearchMatch>,
+}
+
+/// A message that matches a search query.
+#[derive(Clone)]
+pub struct MessageSearchMatch {
+    pub message_id: UniqueId,
+    /// An excerpt of the message around the matched terms.
+    pub snippet: String,
+    /// Byte ranges of the matched terms within `snippet`.
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// Searches the content of messages in active chats, returning the best matching
+/// chats first.
+pub(super) fn search_messages(
+    db_connection: &Connection,
+    query: &str,
+    limit: usize,
+) -> rusqlite::Result<Vec<ChatSearchResult>> {
+    let Some(fts_query) = to_fts_query(query) else {
+        return Ok(Vec::new());
+    };
+
+    let mut stmt = db_connection.prepare(
+        r#"
+        SELECT
+            messages.chat_id,
+            chats.title,
+            messages.id,
+            snippet(messages_fts, 0, char(2), char(3), '…', 16)
+        FROM messages_fts
+        JOIN messages ON messages.rowid = messages_fts.rowid
+        JOIN chats ON chats.id = messages.chat_id
+        WHERE messages_fts MATCH ?1
+            AND chats.archived_at IS NULL
+            AND chats.deleted_at IS NULL
+        ORDER BY messages_fts.rank
+        LIMIT ?2
+        "#,
+    )?;
+
+    let rows = stmt.query_map((&fts_query, limit as i64), |row| {
+        Ok((
+            row.get::<_, UniqueId>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, UniqueId>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+
+    let mut results: Vec<ChatSearchResult> = Vec::new();
+
+    for row in rows {
+        let (chat_id, title, message_id, raw_snippet) = row?;
+        let (snippet, highlights) = parse_snippet(&raw_snippet);
+
+        let message_match = MessageSearchMatch {
+            message_id,
+            snippet,
+            highlights,
+        };
+
+        // Rows are ordered by relevance, so a chat is ranked by its best match.
+        match results.iter_mut().find(|result| result.chat_id == chat_id) {
+            Some(result) => result.matches.push(message_match),
+            None => results.push(ChatSearchResult {
+                chat_id,
+                title,
+                matches: vec![message_match],
+            }),
+        }
+    }
+
+    Ok(results)
+}
+
+/// Turns user input into an FTS5 query that matches every term, treating the last
+/// term as a prefix so results show up while typing.
+fn to_fts_query(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+
+    if terms.is_empty() {
+        return None;
+    }
+
+    Some(terms.join(" ") + "*")
+}
+
+/// Strips the highlight markers from a snippet, returning the clean text and the
+/// byte ranges that were highlighted.
+fn parse_snippet(raw_snippet: &str) -> (String, Vec<Range<usize>>) {
+    let mut snippet = String::with_capacity(raw_snippet.len());
+    let mut highlights = Vec::new();
+    let mut highlight_start = None;
+
+    for char in raw_snippet.chars() {
+        match char {
+            HIGHLIGHT_START => highlight_start = Some(snippet.len()),
+            HIGHLIGHT_END => {
+                if let Some(start) = highlight_start.take() {
+                    highlights.push(start..snippet.len());
+                }
+            }
+            _ => snippet.push(char),
+        }
+    }
+
+    (snippet.replace('\n', " "), highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_fts_query() {
+        assert_eq!(to_fts_query("  "), None);
+        assert_eq!(
+            to_fts_query("rust borrow"),
+            Some("\"rust\" \"borrow\"*".to_string())
+        );
+        assert_eq!(
+            to_fts_query("say \"hi\""),
+            Some("\"say\" \"\"\"hi\"\"\"*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_snippet() {
+        let (snippet, highlights) =
+            parse_snippet("…the \u{2}borrow\u{3} checker\nand \u{2}é\u{3}…");
+
+        assert_eq!(snippet, "…the borrow checker and é…");
+        assert_eq!(
+            highlights
+                .iter()
+                .map(|range| &snippet[range.clone()])
+                .collect::<Vec<_>>(),
+            vec!["borrow", "é"]
+        );
+    }
+
+    #[test]
+    fn test_search_messages() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        db_connection
+            .execute_batch(
+                "
+                CREATE TABLE chats (id TEXT PRIMARY KEY, title TEXT, archived_at DATETIME, deleted_at DATETIME);
+                CREATE TABLE messages (id TEXT PRIMARY KEY, chat_id TEXT NOT NULL, content TEXT NOT NULL, status TEXT);
+                ",
+            )
+            .unwrap();
+        db_connection
+            .execute_batch(super::super::MESSAGES_FTS_SQL)
+            .unwrap();
+        db_connection
+            .execute_batch(super::super::MESSAGES_FTS_STATUS_SQL)
+            .unwrap();
+        db_connection
+            .execute_batch(
+                "
+                INSERT INTO chats VALUES ('a', 'Rust', NULL, NULL), ('b', 'Trashed', NULL, '2024-01-01');
+                INSERT INTO messages VALUES ('a1', 'a', 'How does the borrow checker work?', NULL);
+                INSERT INTO messages VALUES ('a2', 'a', '', 'streaming');
+                INSERT INTO messages VALUES ('b1', 'b', 'The borrow checker is strict', NULL);
+                UPDATE messages SET content = 'It tracks borrowing' WHERE id = 'a2';
+                ",
+            )
+            .unwrap();
+
+        // Streaming replies aren't searchable until they finish.
+        let results = search_messages(&db_connection, "borrow", 10).unwrap();
+        assert_eq!(results[0].matches.len(), 1);
+
+        db_connection
+            .execute_batch(
+                "
+                UPDATE messages SET content = 'It tracks borrowing of values' WHERE id = 'a2';
+                UPDATE messages SET status = 'complete' WHERE id = 'a2';
+                ",
+            )
+            .unwrap();
+
+        let results = search_messages(&db_connection, "borrow", 10).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chat_id, UniqueId::from_string("a"));
+        assert_eq!(results[0].matches.len(), 2);
+
+        db_connection
+            .execute("DELETE FROM messages WHERE id = 'a1'", [])
+            .unwrap();
+
+        let results = search_messages(&db_connection, "checker", 10).unwrap();
+        assert!(results.is_empty());
+    }
+}
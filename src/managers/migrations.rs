@@ -0,0 +1,448 @@
+//! Versioned schema migrations for `db.sqlite`.
+//!
+//! Each manager owns a list of migrations for the tables it manages and registers
+//! it with a [`Migrator`]. The migrator keeps track of the version each manager's
+//! schema is at in the `schema_version` table, and applies every pending migration
+//! inside a single transaction on startup.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::managers::DbError;
+
+pub struct Migration {
+    /// Versions start at 1 and must be strictly increasing within a manager.
+    pub version: u32,
+    pub description: &'static str,
+    pub step: MigrationStep,
+}
+
+pub enum MigrationStep {
+    /// A batch of SQL statements.
+    Sql(&'static str),
+    /// Arbitrary logic, for changes that can't be expressed in plain SQL.
+    Rust(fn(&Connection) -> rusqlite::Result<()>),
+}
+
+impl Migration {
+    pub const fn sql(version: u32, description: &'static str, sql: &'static str) -> Self {
+        Self {
+            version,
+            description,
+            step: MigrationStep::Sql(sql),
+        }
+    }
+
+    pub const fn rust(
+        version: u32,
+        description: &'static str,
+        step: fn(&Connection) -> rusqlite::Result<()>,
+    ) -> Self {
+        Self {
+            version,
+            description,
+            step: MigrationStep::Rust(step),
+        }
+    }
+
+    fn apply(&self, db_connection: &Connection) -> rusqlite::Result<()> {
+        match self.step {
+            MigrationStep::Sql(sql) => db_connection.execute_batch(sql),
+            MigrationStep::Rust(step) => step(db_connection),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Migrator {
+    components: Vec<(&'static str, &'static [Migration])>,
+}
+
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the migrations of a component. Components are migrated in the
+    /// order they are registered in, so components whose tables are referenced
+    /// by foreign keys should be registered first.
+    pub fn register(&mut self, component: &'static str, migrations: &'static [Migration]) {
+        debug_assert!(
+            migrations
+                .windows(2)
+                .all(|pair| pair[0].version < pair[1].version),
+            "migrations for {component} must have strictly increasing versions"
+        );
+
+        self.components.push((component, migrations));
+    }
+
+    /// Applies every pending migration in a single transaction.
+    ///
+    /// Foreign keys are disabled while migrating so that tables can be rebuilt
+    /// (for example to change a `CHECK` constraint), and the tables the migrations
+    /// changed are verified before committing.
+    pub fn run(&self, db_connection: &Connection) -> Result<(), DbError> {
+        db_connection
+            .execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS schema_version (
+                    component   TEXT PRIMARY KEY,
+                    version     INTEGER NOT NULL,
+                    migrated_at DATETIME NOT NULL
+                );
+
+                PRAGMA foreign_keys = OFF;
+                ",
+            )
+            .map_err(DbError::SqliteError)?;
+
+        let result = self.run_in_transaction(db_connection);
+
+        db_connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(DbError::SqliteError)?;
+
+        result
+    }
+
+    fn run_in_transaction(&self, db_connection: &Connection) -> Result<(), DbError> {
+        let transaction = db_connection
+            .unchecked_transaction()
+            .map_err(DbError::SqliteError)?;
+
+        // Taken before the first pending migration, so there's none when nothing is migrated.
+        let mut tables_before = None;
+
+        for &(component, migrations) in &self.components {
+            let current_version = schema_version(&transaction, component)?;
+            let latest_version = migrations.last().map_or(0, |migration| migration.version);
+
+            if current_version > latest_version {
+                return Err(DbError::SchemaTooNew {
+                    component,
+                    found: current_version,
+                    supported: latest_version,
+                });
+            }
+
+            for migration in migrations
+                .iter()
+                .filter(|migration| migration.version > current_version)
+            {
+                if tables_before.is_none() {
+                    tables_before = Some(table_definitions(&transaction)?);
+                }
+
+                migration
+                    .apply(&transaction)
+                    .map_err(|source| DbError::MigrationFailed {
+                        component,
+                        version: migration.version,
+                        description: migration.description,
+                        source,
+                    })?;
+
+                tracing::info!(
+                    component,
+                    version = migration.version,
+                    description = migration.description,
+                    "Applied database migration"
+                );
+            }
+
+            if latest_version != current_version {
+                transaction
+                    .execute(
+                        r#"
+                        INSERT INTO schema_version (component, version, migrated_at)
+                        VALUES (?1, ?2, ?3)
+                        ON CONFLICT (component) DO UPDATE SET
+                            version = excluded.version,
+                            migrated_at = excluded.migrated_at
+                        "#,
+                        (component, latest_version, &Utc::now().naive_utc()),
+                    )
+                    .map_err(DbError::SqliteError)?;
+            }
+        }
+
+        if let Some(tables_before) = tables_before
+            && has_foreign_key_violations(&transaction, &tables_before)?
+        {
+            return Err(DbError::Error(
+                "migrations left rows that violate foreign key constraints",
+            ));
+        }
+
+        transaction.commit().map_err(DbError::SqliteError)
+    }
+}
+
+/// The `CREATE TABLE` statement of every table, by name.
+fn table_definitions(
+    db_connection: &Connection,
+) -> Result<HashMap<String, Option<String>>, DbError> {
+    let mut stmt = db_connection
+        .prepare("SELECT name, sql FROM sqlite_master WHERE type = 'table'")
+        .map_err(DbError::SqliteError)?;
+
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .and_then(Iterator::collect)
+        .map_err(DbError::SqliteError)
+}
+
+/// Checks the tables that were created or rebuilt since `tables_before`, along with the
+/// tables referencing them, which a rebuild can leave with rows pointing nowhere.
+fn has_foreign_key_violations(
+    db_connection: &Connection,
+    tables_before: &HashMap<String, Option<String>>,
+) -> Result<bool, DbError> {
+    let tables = table_definitions(db_connection)?;
+    let changed_tables = tables
+        .iter()
+        .filter(|&(name, sql)| tables_before.get(name) != Some(sql))
+        .map(|(name, _)| name.as_str())
+        .collect::<HashSet<_>>();
+
+    if changed_tables.is_empty() {
+        return Ok(false);
+    }
+
+    let mut references_stmt = db_connection
+        .prepare("SELECT \"table\" FROM pragma_foreign_key_list(?1)")
+        .map_err(DbError::SqliteError)?;
+    let mut check_stmt = db_connection
+        .prepare("SELECT 1 FROM pragma_foreign_key_check(?1) LIMIT 1")
+        .map_err(DbError::SqliteError)?;
+
+    for table in tables.keys() {
+        let references_changed_table = references_stmt
+            .query_map([table], |row| row.get::<_, String>(0))
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<_>>>)
+            .map_err(DbError::SqliteError)?
+            .iter()
+            .any(|parent| changed_tables.contains(parent.as_str()));
+
+        if !changed_tables.contains(table.as_str()) && !references_changed_table {
+            continue;
+        }
+
+        if check_stmt.exists([table]).map_err(DbError::SqliteError)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn schema_version(db_connection: &Connection, component: &str) -> Result<u32, DbError> {
+    db_connection
+        .query_row(
+            "SELECT version FROM schema_version WHERE component = ?1",
+            [component],
+            |row| row.get::<_, u32>(0),
+        )
+        .optional()
+        .map(|version| version.unwrap_or(0))
+        .map_err(DbError::SqliteError)
+}
+
+/// Adds a column to a table unless it already exists.
+pub fn add_column_if_missing(
+    db_connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt =
+        db_connection.prepare(&format!("SELECT name FROM pragma_table_info('{table}')"))?;
+
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        db_connection.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition};"
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FIRST: &[Migration] = &[Migration::sql(
+        1,
+        "create items",
+        "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+    )];
+
+    static SECOND: &[Migration] = &[
+        Migration::sql(
+            1,
+            "create items",
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        ),
+        Migration::rust(2, "add items.kind", add_kind_column),
+    ];
+
+    static BROKEN: &[Migration] = &[
+        Migration::sql(
+            1,
+            "create items",
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        ),
+        Migration::sql(
+            2,
+            "add items.kind",
+            "ALTER TABLE items ADD COLUMN kind TEXT;",
+        ),
+        Migration::sql(3, "broken", "THIS IS NOT SQL;"),
+    ];
+
+    static ORPHANING: &[Migration] = &[
+        Migration::sql(
+            1,
+            "create owners and pets",
+            "
+            CREATE TABLE owners (id INTEGER PRIMARY KEY);
+            CREATE TABLE pets (id INTEGER PRIMARY KEY, owner_id INTEGER REFERENCES owners(id));
+            INSERT INTO owners (id) VALUES (1);
+            INSERT INTO pets (id, owner_id) VALUES (1, 1);
+            ",
+        ),
+        Migration::sql(
+            2,
+            "rebuild owners without its rows",
+            "
+            CREATE TABLE owners_new (id INTEGER PRIMARY KEY, name TEXT);
+            DROP TABLE owners;
+            ALTER TABLE owners_new RENAME TO owners;
+            ",
+        ),
+    ];
+
+    fn add_kind_column(db_connection: &Connection) -> rusqlite::Result<()> {
+        add_column_if_missing(db_connection, "items", "kind", "TEXT")
+    }
+
+    fn migrate(
+        db_connection: &Connection,
+        migrations: &'static [Migration],
+    ) -> Result<(), DbError> {
+        let mut migrator = Migrator::new();
+        migrator.register("items", migrations);
+        migrator.run(db_connection)
+    }
+
+    #[test]
+    fn test_applies_pending_migrations_in_order() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        migrate(&db_connection, FIRST).unwrap();
+        assert_eq!(schema_version(&db_connection, "items").unwrap(), 1);
+
+        migrate(&db_connection, SECOND).unwrap();
+        assert_eq!(schema_version(&db_connection, "items").unwrap(), 2);
+
+        db_connection
+            .execute("INSERT INTO items (name, kind) VALUES ('a', 'b')", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_rerunning_is_a_no_op() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        migrate(&db_connection, SECOND).unwrap();
+        migrate(&db_connection, SECOND).unwrap();
+
+        assert_eq!(schema_version(&db_connection, "items").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_newer_schema_is_an_error() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        migrate(&db_connection, SECOND).unwrap();
+
+        assert!(matches!(
+            migrate(&db_connection, FIRST),
+            Err(DbError::SchemaTooNew {
+                found: 2,
+                supported: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        migrate(&db_connection, FIRST).unwrap();
+
+        assert!(matches!(
+            migrate(&db_connection, BROKEN),
+            Err(DbError::MigrationFailed { version: 3, .. })
+        ));
+
+        // Version 2 was applied in the same transaction, so it must have been rolled back too.
+        assert_eq!(schema_version(&db_connection, "items").unwrap(), 1);
+        assert!(
+            db_connection
+                .execute("INSERT INTO items (name, kind) VALUES ('a', 'b')", [])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_foreign_keys_are_checked_in_migrated_tables() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        let mut migrator = Migrator::new();
+        migrator.register("pets", &ORPHANING[..1]);
+        migrator.run(&db_connection).unwrap();
+
+        // Rows that already violate a foreign key don't stop other components from migrating.
+        db_connection
+            .execute_batch(
+                "
+                PRAGMA foreign_keys = OFF;
+                INSERT INTO pets (id, owner_id) VALUES (2, 2);
+                PRAGMA foreign_keys = ON;
+                ",
+            )
+            .unwrap();
+        migrate(&db_connection, FIRST).unwrap();
+        db_connection
+            .execute("DELETE FROM pets WHERE id = 2", [])
+            .unwrap();
+
+        // Rebuilding owners without its rows leaves the pets pointing nowhere.
+        let mut migrator = Migrator::new();
+        migrator.register("pets", ORPHANING);
+        assert!(matches!(
+            migrator.run(&db_connection),
+            Err(DbError::Error(_))
+        ));
+        assert_eq!(schema_version(&db_connection, "pets").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_add_column_if_missing_is_idempotent() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        migrate(&db_connection, FIRST).unwrap();
+        add_column_if_missing(&db_connection, "items", "kind", "TEXT").unwrap();
+        add_column_if_missing(&db_connection, "items", "kind", "TEXT").unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 mod unique_id;
 
+mod migrations;
+pub use migrations::*;
+
 use std::sync::Arc;
 
 use thiserror::Error;
@@ -10,12 +13,18 @@ pub use unique_id::*;
 mod models_manager;
 pub use models_manager::*;
 
+mod provider_endpoint;
+pub use provider_endpoint::*;
+
 mod chats_manager;
 pub use chats_manager::*;
 
 mod persistence_manager;
 pub use persistence_manager::*;
 
+mod assistants_manager;
+pub use assistants_manager::*;
+
 mod settings_manager;
 pub use settings_manager::*;
 
@@ -25,6 +34,7 @@ pub use update_manager::*;
 pub struct Managers {
     pub models: ModelsManager,
     pub chats: ChatsManager,
+    pub assistants: AssistantsManager,
     pub persistence: PersistenceManager,
     pub settings: SettingsManager,
     pub update: UpdateManager,
@@ -35,19 +45,39 @@ impl Managers {
         Self {
             models: ModelsManager::new(cx),
             chats: ChatsManager::new(cx),
+            assistants: AssistantsManager::new(cx),
             persistence: PersistenceManager::new(),
             settings: SettingsManager::new(cx),
             update: UpdateManager::new(cx),
         }
     }
 
-    pub fn init(&mut self, cx: &mut App) -> rusqlite::Result<()> {
+    pub fn init(&mut self, cx: &mut App) -> Result<(), DbError> {
         let db_dir = self.persistence.local_data_dir().unwrap().join("db.sqlite");
 
-        let db_connection = Arc::new(rusqlite::Connection::open(db_dir)?);
+        let db_connection =
+            Arc::new(rusqlite::Connection::open(db_dir).map_err(DbError::SqliteError)?);
+
+        // WAL keeps writes cheap while replies stream in, and lets reads run alongside them.
+        db_connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .map_err(DbError::SqliteError)?;
+        db_connection
+            .pragma_update(None, "synchronous", "NORMAL")
+            .map_err(DbError::SqliteError)?;
+
+        // Managers whose tables are referenced by other managers are registered first.
+        let mut migrator = Migrator::new();
+        migrator.register("models", ModelsManager::MIGRATIONS);
+        migrator.register("chats", ChatsManager::MIGRATIONS);
+        migrator.register("settings", SettingsManager::MIGRATIONS);
+        migrator.register("assistants", AssistantsManager::MIGRATIONS);
+        migrator.run(&db_connection)?;
 
         self.models.init(cx, db_connection.clone());
-        self.chats.init(cx, db_connection).unwrap();
+        self.chats.init(cx, db_connection.clone())?;
+        self.settings.init(cx, db_connection.clone())?;
+        self.assistants.init(cx, db_connection)?;
 
         Ok(())
     }
@@ -63,4 +93,29 @@ pub enum DbError {
 
     #[error("An error with sqlite.")]
     SqliteError(#[source] rusqlite::Error),
+
+    #[error(
+        "The database was created by a newer version of Astrum ({component} schema version {found}, \
+         this version supports up to {supported}). Please update Astrum."
+    )]
+    SchemaTooNew {
+        component: &'static str,
+        found: u32,
+        supported: u32,
+    },
+
+    #[error("Failed to apply migration {version} ({description}) for {component}.")]
+    MigrationFailed {
+        component: &'static str,
+        version: u32,
+        description: &'static str,
+        #[source]
+        source: rusqlite::Error,
+    },
+
+    #[error("Failed to serialize data.")]
+    SerializationError(#[source] serde_json::Error),
+
+    #[error("Failed to access the keyring.")]
+    KeyringError(#[source] keyring::Error),
 }
@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use anyml::{
     AnthropicProvider, OllamaProvider, OpenAiProvider,
@@ -12,15 +15,19 @@ use rusqlite::{
     types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
 };
 use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
 
 pub trait ProviderTrait: ChatProvider + ListModelsProvider {}
 impl<T: ChatProvider + ListModelsProvider> ProviderTrait for T {}
 
 use crate::{
-    anyhttp_gpui::GpuiHttpWrapper,
+    anyhttp_gpui::{GpuiHttpWrapper, RequestObserver},
     assets::AstrumLogoKind,
     blocks::models_menu::ModelsCache,
-    managers::{DbError, UniqueId},
+    managers::{
+        DbError, GenerationParameters, Migration, ProviderAuthorization, ProviderEndpoint,
+        ProviderParam, ProviderParams, UniqueId,
+    },
     secrets::{get_secret, remove_secret, set_secret},
     utils::FrontInsertMap,
 };
@@ -31,16 +38,136 @@ pub struct ProviderModelPair {
     pub model: Entity<Option<String>>,
 }
 
+/// A model of a specific provider, e.g. the one a chat uses.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ModelChoice {
+    pub provider_id: UniqueId,
+    /// Kept so the model can still be named after its provider is deleted.
+    pub provider_name: String,
+    pub model: String,
+}
+
 pub struct ModelsManager {
     db_connection: Option<Arc<rusqlite::Connection>>,
     pub providers: Entity<FrontInsertMap<UniqueId, Arc<Provider>>>,
+    /// The model of the chat that is shown, which is the default model when no chat is open.
     pub current_model: ProviderModelPair,
+    /// The model new chats start from.
+    pub default_model: Entity<Option<ModelChoice>>,
     pub chat_titles_model: ProviderModelPair,
+    /// Parameters every request to a model starts from, keyed by provider and model.
+    pub model_parameters: Entity<HashMap<(UniqueId, String), GenerationParameters>>,
     /// Cache for provider models
     pub models_cache: Entity<ModelsCache>,
 }
 
 impl<'a> ModelsManager {
+    pub const MIGRATIONS: &'static [Migration] = &[
+        Migration::sql(
+            1,
+            "create providers and model selections",
+            "
+        CREATE TABLE IF NOT EXISTS providers (
+            id         TEXT PRIMARY KEY,
+            kind       TEXT NOT NULL
+                CHECK (kind IN ('ollama', 'anthropic', 'openai')),
+            name       TEXT NOT NULL,
+            url        TEXT NOT NULL,
+            icon       TEXT,
+            created_at DATETIME NOT NULL,
+            edited_at  DATETIME NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS model_selections (
+            key           TEXT PRIMARY KEY CHECK (key IN ('current', 'chat_titles')),
+            provider_id   TEXT,
+            provider_name TEXT,
+            model         TEXT
+        );
+        ",
+        ),
+        Migration::sql(
+            2,
+            "default generation parameters per model",
+            "
+            CREATE TABLE IF NOT EXISTS model_parameters (
+                provider_id TEXT NOT NULL,
+                model       TEXT NOT NULL,
+                parameters  TEXT NOT NULL,
+
+                PRIMARY KEY (provider_id, model),
+                FOREIGN KEY (provider_id)
+                    REFERENCES providers(id)
+                    ON DELETE CASCADE
+            );
+            ",
+        ),
+        // Kinds are checked when providers are read, so new kinds don't need a migration.
+        Migration::sql(
+            3,
+            "provider headers and any provider kind",
+            "
+            CREATE TABLE providers_new (
+                id         TEXT PRIMARY KEY,
+                kind       TEXT NOT NULL,
+                name       TEXT NOT NULL,
+                url        TEXT NOT NULL,
+                icon       TEXT,
+                headers    TEXT,
+                created_at DATETIME NOT NULL,
+                edited_at  DATETIME NOT NULL
+            );
+
+            INSERT INTO providers_new (id, kind, name, url, icon, created_at, edited_at)
+                SELECT id, kind, name, url, icon, created_at, edited_at FROM providers;
+
+            DROP TABLE providers;
+            ALTER TABLE providers_new RENAME TO providers;
+            ",
+        ),
+        Migration::sql(
+            4,
+            "provider options",
+            "ALTER TABLE providers ADD COLUMN options TEXT;",
+        ),
+        Migration::sql(
+            5,
+            "provider query parameters",
+            "ALTER TABLE providers ADD COLUMN query_params TEXT;",
+        ),
+        // SQLite can't change a `CHECK` constraint, so the table is rebuilt to check
+        // the kind again. Adding a kind needs another rebuild.
+        Migration::sql(
+            6,
+            "provider kind check",
+            "
+            CREATE TABLE providers_new (
+                id           TEXT PRIMARY KEY,
+                kind         TEXT NOT NULL
+                    CHECK (kind IN (
+                        'ollama', 'anthropic', 'openai', 'openai_compatible', 'gemini',
+                        'azure_openai'
+                    )),
+                name         TEXT NOT NULL,
+                url          TEXT NOT NULL,
+                icon         TEXT,
+                headers      TEXT,
+                created_at   DATETIME NOT NULL,
+                edited_at    DATETIME NOT NULL,
+                options      TEXT,
+                query_params TEXT
+            );
+
+            INSERT INTO providers_new
+                SELECT id, kind, name, url, icon, headers, created_at, edited_at, options, query_params
+                FROM providers;
+
+            DROP TABLE providers;
+            ALTER TABLE providers_new RENAME TO providers;
+            ",
+        ),
+    ];
+
     pub fn new(cx: &mut App) -> Self {
         Self {
             db_connection: None,
@@ -50,51 +177,88 @@ impl<'a> ModelsManager {
                 provider_name: cx.new(|_cx| None),
                 model: cx.new(|_cx| None),
             },
+            default_model: cx.new(|_cx| None),
             chat_titles_model: ProviderModelPair {
                 provider_id: cx.new(|_cx| None),
                 provider_name: cx.new(|_cx| None),
                 model: cx.new(|_cx| None),
             },
+            model_parameters: cx.new(|_cx| HashMap::new()),
             models_cache: cx.new(|_cx| ModelsCache::new()),
         }
     }
 
     pub fn init(&mut self, cx: &mut App, db_connection: Arc<rusqlite::Connection>) {
-        db_connection
-            .execute_batch(
-                "
-                PRAGMA foreign_keys = ON;
-
-                CREATE TABLE IF NOT EXISTS providers (
-                    id         TEXT PRIMARY KEY,
-                    kind       TEXT NOT NULL
-                        CHECK (kind IN ('ollama', 'anthropic', 'openai')),
-                    name       TEXT NOT NULL,
-                    url        TEXT NOT NULL,
-                    icon       TEXT,
-                    created_at DATETIME NOT NULL,
-                    edited_at  DATETIME NOT NULL
-                );
-
-                CREATE TABLE IF NOT EXISTS model_selections (
-                    key           TEXT PRIMARY KEY CHECK (key IN ('current', 'chat_titles')),
-                    provider_id   TEXT,
-                    provider_name TEXT,
-                    model         TEXT
-                );
-                ",
-            )
-            .unwrap();
-
         let _ = self
             .load_providers_from_db(cx, db_connection.clone())
             .unwrap();
 
         self.load_model_selections_from_db(cx, &db_connection);
 
+        match load_model_parameters_from_db(&db_connection) {
+            Ok(model_parameters) => self.model_parameters.update(cx, |this, _cx| {
+                *this = model_parameters;
+            }),
+            Err(err) => tracing::error!("failed to load model parameters: {err}"),
+        }
+
         self.db_connection = Some(db_connection);
     }
 
+    /// The parameters requests to a model start from.
+    pub fn get_model_parameters(
+        &self,
+        cx: &App,
+        provider_id: &UniqueId,
+        model: &str,
+    ) -> GenerationParameters {
+        self.model_parameters
+            .read(cx)
+            .get(&(provider_id.clone(), model.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_model_parameters(
+        &self,
+        cx: &mut App,
+        provider_id: &UniqueId,
+        model: &str,
+        parameters: GenerationParameters,
+    ) -> Result<(), DbError> {
+        let db = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("db_connection"))?;
+
+        if parameters.is_empty() {
+            db.execute(
+                "DELETE FROM model_parameters WHERE provider_id = ?1 AND model = ?2",
+                (provider_id, model),
+            )
+        } else {
+            let serialized =
+                serde_json::to_string(&parameters).map_err(DbError::SerializationError)?;
+
+            db.execute(
+                "INSERT OR REPLACE INTO model_parameters (provider_id, model, parameters) VALUES (?1, ?2, ?3)",
+                (provider_id, model, &serialized),
+            )
+        }
+        .map_err(DbError::SqliteError)?;
+
+        self.model_parameters.update(cx, |model_parameters, cx| {
+            let key = (provider_id.clone(), model.to_string());
+            match parameters.is_empty() {
+                true => model_parameters.remove(&key),
+                false => model_parameters.insert(key, parameters),
+            };
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
     pub fn get_current_provider<'b>(&'b self, cx: &'b App) -> Option<&'b Arc<Provider>> {
         self.current_model
             .provider_id
@@ -125,12 +289,6 @@ impl<'a> ModelsManager {
                 cx.notify();
             },
         );
-        self.save_model_selection(
-            "current",
-            Some(&provider_id),
-            Some(&provider_name),
-            self.current_model.model.read(cx).as_deref(),
-        );
     }
 
     pub fn get_provider(
@@ -157,20 +315,26 @@ impl<'a> ModelsManager {
                         kind,
                         name,
                         url,
-                        icon
+                        icon,
+                        headers,
+                        query_params,
+                        options
                     FROM providers
                     WHERE id = ?1
                     "#,
             )
             .map_err(|err| DbError::SqliteError(err))?;
 
-        let (kind, name, url, icon) = stmt
+        let (kind, name, url, icon, headers, query_params, options) = stmt
             .query_row([provider_id.to_string()], |row| {
                 Ok((
                     row.get::<_, ProviderKind>(0)?,
                     row.get::<_, String>(1)?,
                     row.get::<_, String>(2)?,
                     row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
                 ))
             })
             .map_err(|err| DbError::SqliteError(err))?;
@@ -184,6 +348,11 @@ impl<'a> ModelsManager {
             name,
             url,
             icon.unwrap_or_else(|| kind.default_icon().to_string()),
+            ProviderParams {
+                headers: parse_json_column(headers),
+                query: parse_json_column(query_params),
+            },
+            parse_json_column(options),
             http_client,
         )
         .ok_or_else(|| DbError::MissingData("provider"))?;
@@ -234,6 +403,8 @@ impl<'a> ModelsManager {
             name,
             url,
             kind.default_icon().to_string(),
+            ProviderParams::default(),
+            ProviderOptions::default(),
             http_client,
         );
 
@@ -247,17 +418,79 @@ impl<'a> ModelsManager {
     pub fn set_current_model(&mut self, cx: &mut App, model_name: impl Into<String>) {
         let model_name = model_name.into();
         cx.update_entity(&self.current_model.model, |model, cx| {
-            *model = Some(model_name.clone());
+            *model = Some(model_name);
+            cx.notify();
+        });
+    }
+
+    /// The current provider and model, if both are selected.
+    pub fn current_model_choice(&self, cx: &App) -> Option<ModelChoice> {
+        Some(ModelChoice {
+            provider_id: self.current_model.provider_id.read(cx).clone()?,
+            provider_name: self.current_model.provider_name.read(cx).clone()?,
+            model: self.current_model.model.read(cx).clone()?,
+        })
+    }
+
+    /// Whether the current model belongs to a provider that has been deleted.
+    pub fn is_current_provider_missing(&self, cx: &App) -> bool {
+        self.current_model
+            .provider_id
+            .read(cx)
+            .as_ref()
+            .is_some_and(|provider_id| self.providers.read(cx).get(provider_id).is_none())
+    }
+
+    /// Shows a model in the picker without changing the default model.
+    pub fn show_model(&self, cx: &mut App, model_choice: Option<&ModelChoice>) {
+        cx.update_entity(&self.current_model.provider_id, |provider_id, cx| {
+            *provider_id = model_choice.map(|choice| choice.provider_id.clone());
+            cx.notify();
+        });
+        cx.update_entity(&self.current_model.provider_name, |provider_name, cx| {
+            *provider_name = model_choice.map(|choice| choice.provider_name.clone());
+            cx.notify();
+        });
+        cx.update_entity(&self.current_model.model, |model, cx| {
+            *model = model_choice.map(|choice| choice.model.clone());
             cx.notify();
         });
+    }
+
+    /// Shows the default model in the picker, e.g. once no chat is open.
+    pub fn show_default_model(&self, cx: &mut App) {
+        let default_model = self.default_model.read(cx).clone();
+        self.show_model(cx, default_model.as_ref());
+    }
+
+    /// Makes the current model the one new chats start from.
+    pub fn save_current_as_default(&mut self, cx: &mut App) {
+        let model_choice = self.current_model_choice(cx);
+
         self.save_model_selection(
             "current",
-            self.current_model.provider_id.read(cx).as_ref(),
-            self.current_model.provider_name.read(cx).as_deref(),
-            Some(&model_name),
+            model_choice.as_ref().map(|choice| &choice.provider_id),
+            model_choice
+                .as_ref()
+                .map(|choice| choice.provider_name.as_str()),
+            model_choice.as_ref().map(|choice| choice.model.as_str()),
         );
+
+        self.default_model.update(cx, |default_model, cx| {
+            *default_model = model_choice;
+            cx.notify();
+        });
+    }
+
+    pub fn clear_default_selection(&mut self, cx: &mut App) {
+        self.default_model.update(cx, |default_model, cx| {
+            *default_model = None;
+            cx.notify();
+        });
+        self.save_model_selection("current", None, None, None);
     }
 
+    /// Clears the model shown in the picker. The default model is left as it is.
     pub fn clear_current_selection(&mut self, cx: &mut App) {
         cx.update_entity(&self.current_model.provider_id, |provider_id, cx| {
             *provider_id = None;
@@ -271,7 +504,6 @@ impl<'a> ModelsManager {
             *model = None;
             cx.notify();
         });
-        self.save_model_selection("current", None, None, None);
     }
 
     pub fn clear_chat_titles_selection(&mut self, cx: &mut App) {
@@ -403,6 +635,19 @@ impl<'a> ModelsManager {
 
             match key.as_str() {
                 "current" => {
+                    if let (Some(provider_id), Some(provider_name), Some(model)) =
+                        (&provider_id, &provider_name, &model)
+                    {
+                        self.default_model.update(cx, |default_model, cx| {
+                            *default_model = Some(ModelChoice {
+                                provider_id: provider_id.clone(),
+                                provider_name: provider_name.clone(),
+                                model: model.clone(),
+                            });
+                            cx.notify();
+                        });
+                    }
+
                     if let Some(id) = provider_id {
                         self.current_model.provider_id.update(cx, |pid, cx| {
                             *pid = Some(id);
@@ -462,7 +707,10 @@ impl<'a> ModelsManager {
                 kind,
                 name,
                 url,
-                icon
+                icon,
+                headers,
+                query_params,
+                options
             FROM providers
             ORDER BY created_at
             "#,
@@ -476,6 +724,9 @@ impl<'a> ModelsManager {
                 let name = row.get::<_, String>(2)?;
                 let url = row.get::<_, String>(3)?;
                 let icon = row.get::<_, Option<String>>(4)?;
+                let headers = row.get::<_, Option<String>>(5)?;
+                let query_params = row.get::<_, Option<String>>(6)?;
+                let options = row.get::<_, Option<String>>(7)?;
 
                 let http_client = GpuiHttpWrapper::new(cx.http_client());
 
@@ -486,6 +737,11 @@ impl<'a> ModelsManager {
                     name,
                     url,
                     icon.unwrap_or_else(|| kind.default_icon().to_string()),
+                    ProviderParams {
+                        headers: parse_json_column(headers),
+                        query: parse_json_column(query_params),
+                    },
+                    parse_json_column(options),
                     http_client,
                 );
 
@@ -500,25 +756,36 @@ impl<'a> ModelsManager {
         Ok(())
     }
 
-    fn create_provider_client(
+    fn create_provider_connection(
         kind: &ProviderKind,
         provider_id: &UniqueId,
         name: &str,
         url: String,
+        mut params: ProviderParams,
+        options: ProviderOptions,
         http_client: GpuiHttpWrapper,
-    ) -> Arc<dyn ProviderTrait> {
-        match kind {
-            ProviderKind::Ollama => Arc::new(OllamaProvider::new(http_client).url(url)),
-            ProviderKind::OpenAi => {
-                let api_key = get_secret(Self::construct_provider_api_key_name(provider_id, name))
-                    .unwrap_or_default();
-                Arc::new(OpenAiProvider::new(http_client, api_key).url(url))
-            }
-            ProviderKind::Anthropic => {
-                let api_key = get_secret(Self::construct_provider_api_key_name(provider_id, name))
-                    .unwrap_or_default();
-                Arc::new(AnthropicProvider::new(http_client, api_key).url(url))
-            }
+    ) -> ProviderConnection {
+        // For Ollama this is the token or password of the proxy in front of it, if any.
+        let api_key = get_secret(Self::construct_provider_api_key_name(provider_id, name))
+            .unwrap_or_default();
+
+        for param in params.iter_mut().filter(|param| param.secret) {
+            param.value = get_secret(Self::construct_provider_param_secret_name(
+                provider_id,
+                name,
+                &param.id,
+            ))
+            .map(|value| value.expose_secret().to_string())
+            .unwrap_or_default();
+        }
+
+        ProviderConnection {
+            kind: *kind,
+            url,
+            api_key,
+            params,
+            options,
+            http_client,
         }
     }
 
@@ -530,13 +797,22 @@ impl<'a> ModelsManager {
         name: String,
         url: String,
         icon: String,
+        params: ProviderParams,
+        options: ProviderOptions,
         http_client: GpuiHttpWrapper,
     ) -> Option<()> {
-        let inner =
-            Self::create_provider_client(kind, provider_id, &name, url.clone(), http_client);
+        let connection = Self::create_provider_connection(
+            kind,
+            provider_id,
+            &name,
+            url.clone(),
+            params,
+            options,
+            http_client,
+        );
 
         self.providers.update(cx, |providers, cx| {
-            let provider = Arc::new(Provider::new(cx, inner, name, url, icon));
+            let provider = Arc::new(Provider::new(cx, connection, name, url, icon));
             providers.insert_front(provider_id.clone(), provider);
             cx.notify();
         });
@@ -563,13 +839,22 @@ impl<'a> ModelsManager {
         let name = provider.name.read(cx).to_string();
         let url = provider.url.read(cx).to_string();
         let icon = provider.icon.read(cx).to_string();
+        let params = provider.params.read(cx).clone();
+        let options = provider.options.read(cx).clone();
 
         let http_client = GpuiHttpWrapper::new(cx.http_client());
-        let inner =
-            Self::create_provider_client(&kind, provider_id, &name, url.clone(), http_client);
+        let connection = Self::create_provider_connection(
+            &kind,
+            provider_id,
+            &name,
+            url.clone(),
+            params,
+            options,
+            http_client,
+        );
 
         self.providers.update(cx, |providers, cx| {
-            let new_provider = Arc::new(Provider::new(cx, inner, name, url, icon));
+            let new_provider = Arc::new(Provider::new(cx, connection, name, url, icon));
             providers.insert(provider_id.clone(), new_provider);
             cx.notify();
         });
@@ -581,6 +866,18 @@ impl<'a> ModelsManager {
         format!("chat.astrum.astrum:provider:{}:{}", name, provider_id)
     }
 
+    /// Names the secret that the value of a header or query parameter is kept in.
+    fn construct_provider_param_secret_name(
+        provider_id: &UniqueId,
+        name: &str,
+        param_id: &UniqueId,
+    ) -> String {
+        format!(
+            "{}:param:{param_id}",
+            Self::construct_provider_api_key_name(provider_id, name)
+        )
+    }
+
     pub fn get_provider_api_key(&self, cx: &App, provider_id: &UniqueId) -> Option<String> {
         let provider = self.providers.read(cx).get(provider_id).cloned()?;
 
@@ -652,6 +949,109 @@ impl<'a> ModelsManager {
         Ok(())
     }
 
+    /// Sets the headers and query parameters sent with every request to a provider. The
+    /// values of secret params go in the keyring. Takes effect once the provider is
+    /// reinitialized.
+    pub fn edit_provider_params(
+        &mut self,
+        cx: &mut App,
+        provider_id: UniqueId,
+        mut params: ProviderParams,
+    ) -> Result<(), DbError> {
+        let provider = self.get_provider(cx, &provider_id)?;
+        let name = provider.name.read(cx).to_string();
+        let previous_params = provider.params.read(cx).clone();
+        params.keep_ids(&previous_params);
+
+        let db = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("db_connection"))?;
+
+        let headers = serialize_params(&params.headers)?;
+        let query_params = serialize_params(&params.query)?;
+        let edited_at = Utc::now().naive_utc();
+
+        // The secrets are written first, so the provider is left as it was if the keyring
+        // can't take them.
+        for param in params.iter().filter(|param| param.secret) {
+            let secret_name =
+                Self::construct_provider_param_secret_name(&provider_id, &name, &param.id);
+            set_secret(&secret_name, &param.value).map_err(DbError::KeyringError)?;
+        }
+
+        db.execute(
+            "UPDATE providers SET headers = ?1, query_params = ?2, edited_at = ?3 WHERE id = ?4",
+            (&headers, &query_params, &edited_at, &provider_id),
+        )
+        .map_err(DbError::SqliteError)?;
+
+        // Removes the secrets of params that were removed or aren't secret anymore.
+        let secret_ids = params
+            .iter()
+            .filter(|param| param.secret)
+            .map(|param| &param.id)
+            .collect::<HashSet<_>>();
+        for param in previous_params
+            .iter()
+            .filter(|param| param.secret && !secret_ids.contains(&param.id))
+        {
+            let _ = remove_secret(Self::construct_provider_param_secret_name(
+                &provider_id,
+                &name,
+                &param.id,
+            ));
+        }
+
+        provider.params.update(cx, |provider_params, cx| {
+            *provider_params = params;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    fn remove_param_secrets(provider_id: &UniqueId, name: &str, params: &ProviderParams) {
+        for param in params.iter().filter(|param| param.secret) {
+            let _ = remove_secret(Self::construct_provider_param_secret_name(
+                provider_id,
+                name,
+                &param.id,
+            ));
+        }
+    }
+
+    /// Sets the options of a provider. Takes effect once the provider is reinitialized.
+    pub fn edit_provider_options(
+        &mut self,
+        cx: &mut App,
+        provider_id: UniqueId,
+        options: ProviderOptions,
+    ) -> Result<(), DbError> {
+        let provider = self.get_provider(cx, &provider_id)?;
+
+        let db = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("db_connection"))?;
+
+        let serialized = serde_json::to_string(&options).map_err(DbError::SerializationError)?;
+        let edited_at = Utc::now().naive_utc();
+
+        db.execute(
+            "UPDATE providers SET options = ?1, edited_at = ?2 WHERE id = ?3",
+            (&serialized, &edited_at, &provider_id),
+        )
+        .map_err(DbError::SqliteError)?;
+
+        provider.options.update(cx, |provider_options, cx| {
+            *provider_options = options;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
     pub fn delete_provider(&mut self, cx: &mut App, provider_id: UniqueId) -> Result<(), DbError> {
         let provider = self.get_provider(cx, &provider_id)?;
 
@@ -666,12 +1066,23 @@ impl<'a> ModelsManager {
         let secret_name =
             Self::construct_provider_api_key_name(&provider_id, &provider.name.read(cx));
         let _ = remove_secret(&secret_name);
+        Self::remove_param_secrets(
+            &provider_id,
+            &provider.name.read(cx),
+            provider.params.read(cx),
+        );
 
         // Delete cached models for this provider
         self.models_cache.update(cx, |cache, _| {
             cache.delete_models_for_provider(&provider_id);
         });
 
+        // The rows themselves are removed by the cascade.
+        self.model_parameters.update(cx, |model_parameters, cx| {
+            model_parameters.retain(|(id, _), _| id != &provider_id);
+            cx.notify();
+        });
+
         self.providers.update(cx, |providers, cx| {
             providers.remove(&provider_id);
             cx.notify();
@@ -681,7 +1092,53 @@ impl<'a> ModelsManager {
     }
 }
 
-#[derive(Assoc)]
+/// Writes the headers or query parameters of a provider for the database, leaving out the
+/// values of secret ones.
+fn serialize_params(params: &[ProviderParam]) -> Result<Option<String>, DbError> {
+    if params.is_empty() {
+        return Ok(None);
+    }
+
+    let params = params
+        .iter()
+        .map(|param| match param.secret {
+            true => ProviderParam {
+                value: String::new(),
+                ..param.clone()
+            },
+            false => param.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string(&params)
+        .map(Some)
+        .map_err(DbError::SerializationError)
+}
+
+/// Reads a JSON column of a provider, falling back to the default if it can't be read.
+fn parse_json_column<T: for<'de> Deserialize<'de> + Default>(value: Option<String>) -> T {
+    value
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+fn load_model_parameters_from_db(
+    db: &rusqlite::Connection,
+) -> rusqlite::Result<HashMap<(UniqueId, String), GenerationParameters>> {
+    let mut stmt = db.prepare("SELECT provider_id, model, parameters FROM model_parameters")?;
+
+    stmt.query_map([], |row| {
+        let parameters = row.get::<_, String>(2)?;
+
+        Ok((
+            (UniqueId::from_string(row.get::<_, String>(0)?), row.get(1)?),
+            serde_json::from_str(&parameters).unwrap_or_default(),
+        ))
+    })?
+    .collect()
+}
+
+#[derive(Assoc, Clone, Copy, PartialEq, Eq)]
 #[func(pub fn as_str(&self) -> &'static str)]
 #[func(pub fn default_name(&self) -> SharedString)]
 #[func(pub fn default_url(&self) -> SharedString)]
@@ -704,6 +1161,45 @@ pub enum ProviderKind {
     #[assoc(default_url = "https://api.openai.com".into())]
     #[assoc(default_icon = AstrumLogoKind::OpenAi.into())]
     OpenAi,
+
+    /// Any server with an OpenAI-style API, like LM Studio, vLLM or OpenRouter.
+    #[assoc(as_str = "openai_compatible")]
+    #[assoc(default_name = "OpenAI-compatible".into())]
+    #[assoc(default_url = "http://localhost:1234/v1".into())]
+    #[assoc(default_icon = AstrumLogoKind::OpenAiCompatible.into())]
+    OpenAiCompatible,
+
+    /// Google's Gemini API, through its OpenAI-compatible endpoints.
+    #[assoc(as_str = "gemini")]
+    #[assoc(default_name = "Gemini".into())]
+    #[assoc(default_url = "https://generativelanguage.googleapis.com/v1beta/openai".into())]
+    #[assoc(default_icon = AstrumLogoKind::Gemini.into())]
+    Gemini,
+
+    /// OpenAI models deployed to an Azure OpenAI resource.
+    #[assoc(as_str = "azure_openai")]
+    #[assoc(default_name = "Azure OpenAI".into())]
+    #[assoc(default_url = "https://your-resource.openai.azure.com".into())]
+    #[assoc(default_icon = AstrumLogoKind::OpenAi.into())]
+    AzureOpenAi,
+}
+
+/// The `api-version` Azure OpenAI requests are made with, unless set on the provider.
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Settings that only some kinds of providers have.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ProviderOptions {
+    /// The `api-version` of Azure OpenAI requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// The deployments of an Azure OpenAI resource, which stand in for its models.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deployments: Vec<String>,
+    /// The username Ollama credentials are sent with, as basic auth. Without one, the API
+    /// key is sent as a bearer token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
 }
 
 impl ToSql for ProviderKind {
@@ -712,6 +1208,9 @@ impl ToSql for ProviderKind {
             Self::Ollama => "ollama",
             Self::Anthropic => "anthropic",
             Self::OpenAi => "openai",
+            Self::OpenAiCompatible => "openai_compatible",
+            Self::Gemini => "gemini",
+            Self::AzureOpenAi => "azure_openai",
         }))
     }
 }
@@ -722,6 +1221,9 @@ impl FromSql for ProviderKind {
             "ollama" => Ok(Self::Ollama),
             "anthropic" => Ok(Self::Anthropic),
             "openai" => Ok(Self::OpenAi),
+            "openai_compatible" => Ok(Self::OpenAiCompatible),
+            "gemini" => Ok(Self::Gemini),
+            "azure_openai" => Ok(Self::AzureOpenAi),
             other => Err(FromSqlError::Other(Box::new(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("unknown ProviderKind: {other}"),
@@ -730,27 +1232,278 @@ impl FromSql for ProviderKind {
     }
 }
 
+/// Everything needed to build a client for a provider.
+#[derive(Clone)]
+pub struct ProviderConnection {
+    kind: ProviderKind,
+    url: String,
+    api_key: SecretString,
+    params: ProviderParams,
+    options: ProviderOptions,
+    http_client: GpuiHttpWrapper,
+}
+
+impl ProviderConnection {
+    fn client(&self, http_client: GpuiHttpWrapper) -> Arc<dyn ProviderTrait> {
+        let url = self.url.clone();
+        let endpoint = ProviderEndpoint::new().params(self.params.clone());
+
+        match self.kind {
+            ProviderKind::Ollama => {
+                let endpoint = endpoint.authorization(self.ollama_authorization());
+                Arc::new(OllamaProvider::new(http_client.observer(Arc::new(endpoint))).url(url))
+            }
+            ProviderKind::OpenAi => Arc::new(
+                OpenAiProvider::new(
+                    http_client.observer(Arc::new(endpoint)),
+                    self.api_key.clone(),
+                )
+                .url(url),
+            ),
+            ProviderKind::Anthropic => Arc::new(
+                AnthropicProvider::new(
+                    http_client.observer(Arc::new(endpoint)),
+                    self.api_key.clone(),
+                )
+                .url(url),
+            ),
+            // The URL includes the path the API is served from, which the endpoint
+            // puts in place of the one the OpenAI client uses.
+            ProviderKind::OpenAiCompatible | ProviderKind::Gemini => {
+                let endpoint = endpoint
+                    .base_url(&url)
+                    .omit_authorization(self.api_key.expose_secret().is_empty());
+
+                Arc::new(
+                    OpenAiProvider::new(
+                        http_client.observer(Arc::new(endpoint)),
+                        self.api_key.clone(),
+                    )
+                    .url(url),
+                )
+            }
+            // Azure picks the model from the URL, so each model gets its own endpoint.
+            ProviderKind::AzureOpenAi => {
+                let api_version = self
+                    .options
+                    .api_version
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_AZURE_API_VERSION.to_string());
+                let endpoint = endpoint.base_url(&url).azure(api_version);
+
+                Arc::new(
+                    OpenAiProvider::new(
+                        http_client.observer(Arc::new(endpoint)),
+                        self.api_key.clone(),
+                    )
+                    .url(url),
+                )
+            }
+        }
+    }
+
+    /// Ollama has no auth of its own, but may sit behind a reverse proxy that asks for it.
+    fn ollama_authorization(&self) -> Option<ProviderAuthorization> {
+        match &self.options.username {
+            Some(username) => Some(ProviderAuthorization::Basic {
+                username: username.clone(),
+                password: self.api_key.clone(),
+            }),
+            None if !self.api_key.expose_secret().is_empty() => {
+                Some(ProviderAuthorization::Bearer(self.api_key.clone()))
+            }
+            None => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Provider {
     pub inner: Arc<dyn ProviderTrait>,
+    pub kind: ProviderKind,
+    connection: ProviderConnection,
     pub name: Entity<SharedString>,
     pub url: Entity<SharedString>,
     pub icon: Entity<SharedString>,
+    /// Sent with every request, including the values of secret params.
+    pub params: Entity<ProviderParams>,
+    pub options: Entity<ProviderOptions>,
 }
 
 impl Provider {
     fn new(
         cx: &mut App,
-        inner: Arc<dyn ProviderTrait>,
+        connection: ProviderConnection,
         name: impl Into<SharedString>,
         url: impl Into<SharedString>,
         icon: impl Into<SharedString>,
     ) -> Self {
+        let params = connection.params.clone();
+        let options = connection.options.clone();
+
         Self {
-            inner,
+            inner: connection.client(connection.http_client.clone()),
+            kind: connection.kind,
+            connection,
             name: cx.new(|_cx| name.into()),
             url: cx.new(|_cx| url.into()),
             icon: cx.new(|_cx| icon.into()),
+            params: cx.new(|_cx| params),
+            options: cx.new(|_cx| options),
+        }
+    }
+
+    /// Lists the ids of the models the provider serves, as they're passed to requests.
+    pub async fn list_model_ids(&self) -> Result<Vec<String>, String> {
+        // Azure has no way to list deployments with an API key alone.
+        if self.kind == ProviderKind::AzureOpenAi {
+            return Ok(self.connection.options.deployments.clone());
+        }
+
+        let models = self
+            .inner
+            .list_models()
+            .await
+            .map_err(|err| err.to_string())?;
+
+        Ok(models
+            .into_iter()
+            .map(|model| match self.kind {
+                // Gemini lists its models as `models/gemini-2.5-flash`, but takes them either way.
+                ProviderKind::Gemini => model
+                    .id
+                    .strip_prefix("models/")
+                    .map(ToString::to_string)
+                    .unwrap_or(model.id),
+                _ => model.id,
+            })
+            .collect())
+    }
+
+    /// Builds a client whose requests are passed through `observers`, in order.
+    pub fn observed_client(
+        &self,
+        observers: impl IntoIterator<Item = Arc<dyn RequestObserver>>,
+    ) -> Arc<dyn ProviderTrait> {
+        let http_client = observers.into_iter().fold(
+            self.connection.http_client.clone(),
+            |http_client, observer| http_client.observer(observer),
+        );
+
+        self.connection.client(http_client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::*;
+    use crate::managers::Migrator;
+
+    #[test]
+    fn test_provider_kinds_are_checked() {
+        let db_connection = Connection::open_in_memory().unwrap();
+        let mut migrator = Migrator::new();
+        migrator.register("models", ModelsManager::MIGRATIONS);
+        migrator.run(&db_connection).unwrap();
+
+        let insert = |id: &str, kind: &str| {
+            db_connection.execute(
+                "INSERT INTO providers (id, kind, name, url, created_at, edited_at) VALUES (?1, ?2, ?1, '', 0, 0)",
+                (id, kind),
+            )
+        };
+
+        for kind in [
+            ProviderKind::Ollama,
+            ProviderKind::Anthropic,
+            ProviderKind::OpenAi,
+            ProviderKind::OpenAiCompatible,
+            ProviderKind::Gemini,
+            ProviderKind::AzureOpenAi,
+        ] {
+            insert(kind.as_str(), kind.as_str()).unwrap();
         }
+        assert!(insert("unknown", "unknown").is_err());
+    }
+
+    #[test]
+    fn test_provider_kind_check_keeps_existing_providers() {
+        let db_connection = Connection::open_in_memory().unwrap();
+
+        // A database from before the kinds were checked again.
+        let mut migrator = Migrator::new();
+        migrator.register("models", &ModelsManager::MIGRATIONS[..4]);
+        migrator.run(&db_connection).unwrap();
+        db_connection
+            .execute(
+                "INSERT INTO providers (id, kind, name, url, headers, options, created_at, edited_at) VALUES ('gemini', 'gemini', 'Gemini', '', '[]', '{}', 0, 0)",
+                [],
+            )
+            .unwrap();
+
+        let mut migrator = Migrator::new();
+        migrator.register("models", ModelsManager::MIGRATIONS);
+        migrator.run(&db_connection).unwrap();
+
+        let (kind, headers, options) = db_connection
+            .query_row(
+                "SELECT kind, headers, options FROM providers WHERE id = 'gemini'",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            (kind.as_str(), headers.as_str(), options.as_str()),
+            ("gemini", "[]", "{}")
+        );
+
+        let insert = |id: &str, kind: &str| {
+            db_connection.execute(
+                "INSERT INTO providers (id, kind, name, url, created_at, edited_at) VALUES (?1, ?2, ?1, '', 0, 0)",
+                (id, kind),
+            )
+        };
+        insert("azure_openai", "azure_openai").unwrap();
+        assert!(insert("unknown", "unknown").is_err());
+    }
+
+    #[test]
+    fn test_secret_param_values_are_not_serialized() {
+        let params = [
+            ProviderParam {
+                id: UniqueId::new(),
+                name: String::from("X-Tenant"),
+                value: String::from("acme"),
+                secret: false,
+            },
+            ProviderParam {
+                id: UniqueId::new(),
+                name: String::from("X-Api-Token"),
+                value: String::from("sk-123"),
+                secret: true,
+            },
+        ];
+
+        let serialized = serialize_params(&params).unwrap().unwrap();
+        assert!(!serialized.contains("sk-123"));
+        assert_eq!(
+            serde_json::from_str::<Vec<ProviderParam>>(&serialized).unwrap(),
+            [
+                params[0].clone(),
+                ProviderParam {
+                    value: String::new(),
+                    ..params[1].clone()
+                },
+            ]
+        );
+        assert_eq!(serialize_params(&[]).unwrap(), None);
     }
 }
@@ -0,0 +1,397 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use http::{HeaderName, HeaderValue, Request, Uri, header::AUTHORIZATION};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::form_urlencoded;
+
+use crate::{anyhttp_gpui::RequestObserver, managers::UniqueId};
+
+/// The OpenAI client puts every endpoint under this path.
+const OPENAI_API_PREFIX: &str = "/v1/";
+
+/// A header or query parameter sent with every request to a provider.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ProviderParam {
+    /// Names the secret the value is kept in, so it stays the same when other params are
+    /// added, moved or removed.
+    #[serde(default = "UniqueId::new")]
+    pub id: UniqueId,
+    pub name: String,
+    pub value: String,
+    /// Whether the value is kept in the keyring rather than the database.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+/// The headers and query parameters added to every request to a provider, e.g. for a
+/// gateway in front of it.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct ProviderParams {
+    pub headers: Vec<ProviderParam>,
+    pub query: Vec<ProviderParam>,
+}
+
+impl ProviderParams {
+    /// Every param, headers first.
+    pub fn iter(&self) -> impl Iterator<Item = &ProviderParam> {
+        self.headers.iter().chain(&self.query)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut ProviderParam> {
+        self.headers.iter_mut().chain(&mut self.query)
+    }
+
+    /// Gives each param the id of the previous param with the same name, in order, so
+    /// params that were edited keep their secrets.
+    pub fn keep_ids(&mut self, previous: &ProviderParams) {
+        keep_ids(&mut self.headers, &previous.headers);
+        keep_ids(&mut self.query, &previous.query);
+    }
+}
+
+fn keep_ids(params: &mut [ProviderParam], previous: &[ProviderParam]) {
+    let mut previous = previous.iter().collect::<Vec<_>>();
+
+    for param in params {
+        if let Some(ix) = previous
+            .iter()
+            .position(|previous| previous.name == param.name)
+        {
+            param.id = previous.remove(ix).id.clone();
+        }
+    }
+}
+
+/// Credentials for a server behind a proxy that asks for them, for clients that don't send
+/// any of their own.
+#[derive(Clone)]
+pub enum ProviderAuthorization {
+    Bearer(SecretString),
+    Basic {
+        username: String,
+        password: SecretString,
+    },
+}
+
+impl ProviderAuthorization {
+    fn header_value(&self) -> Option<HeaderValue> {
+        let value = match self {
+            Self::Bearer(token) => format!("Bearer {}", token.expose_secret()),
+            Self::Basic { username, password } => {
+                let credentials = format!("{username}:{}", password.expose_secret());
+                format!("Basic {}", BASE64.encode(credentials))
+            }
+        };
+
+        let mut value = HeaderValue::try_from(value).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+/// Adapts the requests of a provider client to the server they're sent to, e.g. one
+/// that serves the OpenAI API from somewhere other than `/v1`.
+#[derive(Clone, Default)]
+pub struct ProviderEndpoint {
+    /// Where the OpenAI API is served, like `https://openrouter.ai/api/v1`.
+    base_url: Option<String>,
+    params: ProviderParams,
+    /// Leaves out the `Authorization` header, for servers that don't take an API key.
+    omit_authorization: bool,
+    authorization: Option<ProviderAuthorization>,
+    /// The `api-version` of Azure OpenAI, which serves each model from its own deployment.
+    azure_api_version: Option<String>,
+}
+
+impl ProviderEndpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn params(mut self, params: ProviderParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn omit_authorization(mut self, omit_authorization: bool) -> Self {
+        self.omit_authorization = omit_authorization;
+        self
+    }
+
+    pub fn authorization(mut self, authorization: Option<ProviderAuthorization>) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    /// Sends requests to the deployment named by their model, the way Azure OpenAI
+    /// expects, with the API key in an `api-key` header.
+    pub fn azure(mut self, api_version: impl Into<String>) -> Self {
+        self.azure_api_version = Some(api_version.into());
+        self
+    }
+
+    /// Where a request is sent, once moved under the base URL and given the extra query
+    /// parameters.
+    fn request_uri(&self, request: &Request<Vec<u8>>) -> Option<Uri> {
+        if self.base_url.is_none() && self.params.query.is_empty() {
+            return None;
+        }
+
+        let mut uri = self
+            .endpoint_uri(request)
+            .unwrap_or_else(|| request.uri().to_string());
+
+        if !self.params.query.is_empty() {
+            let query = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(
+                    self.params
+                        .query
+                        .iter()
+                        .map(|param| (param.name.trim(), param.value.trim())),
+                )
+                .finish();
+            push_query(&mut uri, &query);
+        }
+
+        uri.parse().ok()
+    }
+
+    /// Moves an OpenAI endpoint, e.g. `/v1/chat/completions`, under the base URL.
+    fn endpoint_uri(&self, request: &Request<Vec<u8>>) -> Option<String> {
+        let base_url = self.base_url.as_deref()?.trim_end_matches('/');
+
+        let uri = request.uri();
+        let path = uri.path();
+        let endpoint = &path[path.rfind(OPENAI_API_PREFIX)? + OPENAI_API_PREFIX.len()..];
+
+        let mut endpoint_uri = match &self.azure_api_version {
+            Some(api_version) => {
+                let deployment = serde_json::from_slice::<Value>(request.body())
+                    .ok()
+                    .and_then(|body| {
+                        let model = body["model"].as_str()?;
+                        Some(format!(
+                            "/deployments/{}",
+                            utf8_percent_encode(model, PATH_SEGMENT)
+                        ))
+                    })
+                    .unwrap_or_default();
+
+                format!("{base_url}/openai{deployment}/{endpoint}?api-version={api_version}")
+            }
+            None => format!("{base_url}/{endpoint}"),
+        };
+
+        if let Some(query) = uri.query() {
+            push_query(&mut endpoint_uri, query);
+        }
+
+        Some(endpoint_uri)
+    }
+}
+
+/// Everything but the unreserved characters is encoded in a path segment.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+fn push_query(uri: &mut String, query: &str) {
+    uri.push(match uri.contains('?') {
+        true => '&',
+        false => '?',
+    });
+    uri.push_str(query);
+}
+
+impl RequestObserver for ProviderEndpoint {
+    fn prepare(&self, request: &mut Request<Vec<u8>>) {
+        if let Some(uri) = self.request_uri(request) {
+            *request.uri_mut() = uri;
+        }
+
+        if self.omit_authorization {
+            request.headers_mut().remove(AUTHORIZATION);
+        }
+
+        if let Some(authorization) = self
+            .authorization
+            .as_ref()
+            .and_then(ProviderAuthorization::header_value)
+        {
+            request.headers_mut().insert(AUTHORIZATION, authorization);
+        }
+
+        if self.azure_api_version.is_some()
+            && let Some(authorization) = request.headers_mut().remove(AUTHORIZATION)
+            && let Some(api_key) = authorization
+                .to_str()
+                .ok()
+                .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            && let Ok(api_key) = HeaderValue::from_str(api_key)
+        {
+            request.headers_mut().insert("api-key", api_key);
+        }
+
+        for header in &self.params.headers {
+            let name = HeaderName::try_from(header.name.trim());
+            let value = HeaderValue::try_from(header.value.trim());
+
+            match (name, value) {
+                (Ok(name), Ok(value)) => {
+                    request.headers_mut().insert(name, value);
+                }
+                _ => tracing::warn!("skipping invalid header {:?}", header.name),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare(endpoint: &ProviderEndpoint, uri: &str) -> Request<Vec<u8>> {
+        let mut request = Request::post(uri)
+            .header(AUTHORIZATION, "Bearer ")
+            .body(Vec::new())
+            .unwrap();
+        endpoint.prepare(&mut request);
+        request
+    }
+
+    #[test]
+    fn test_endpoints_move_under_base_url() {
+        let endpoint = ProviderEndpoint::new().base_url("https://openrouter.ai/api/v1/");
+
+        assert_eq!(
+            prepare(
+                &endpoint,
+                "https://openrouter.ai/api/v1/v1/chat/completions"
+            )
+            .uri(),
+            "https://openrouter.ai/api/v1/chat/completions"
+        );
+        assert_eq!(
+            prepare(&endpoint, "https://openrouter.ai/v1/models?limit=5").uri(),
+            "https://openrouter.ai/api/v1/models?limit=5"
+        );
+        assert_eq!(
+            prepare(&ProviderEndpoint::new(), "http://localhost:1234/v1/models").uri(),
+            "http://localhost:1234/v1/models"
+        );
+    }
+
+    #[test]
+    fn test_azure_deployments() {
+        let endpoint = ProviderEndpoint::new()
+            .base_url("https://example.openai.azure.com/")
+            .azure("2024-10-21");
+
+        let mut request = Request::post("https://example.openai.azure.com/v1/chat/completions")
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(br#"{"model":"gpt-4o-mini","stream":true}"#.to_vec())
+            .unwrap();
+        endpoint.prepare(&mut request);
+
+        assert_eq!(
+            request.uri(),
+            "https://example.openai.azure.com/openai/deployments/gpt-4o-mini/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(request.headers()["api-key"], "secret");
+        assert!(!request.headers().contains_key(AUTHORIZATION));
+
+        let mut request = Request::post("https://example.openai.azure.com/v1/chat/completions")
+            .body(br#"{"model":"my deployment/v2?","stream":true}"#.to_vec())
+            .unwrap();
+        endpoint.prepare(&mut request);
+
+        assert_eq!(
+            request.uri(),
+            "https://example.openai.azure.com/openai/deployments/my%20deployment%2Fv2%3F/chat/completions?api-version=2024-10-21"
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let basic = ProviderEndpoint::new().authorization(Some(ProviderAuthorization::Basic {
+            username: String::from("user"),
+            password: SecretString::from("pass"),
+        }));
+        let bearer = ProviderEndpoint::new().authorization(Some(ProviderAuthorization::Bearer(
+            SecretString::from("token"),
+        )));
+
+        assert_eq!(
+            prepare(&basic, "http://localhost:11434/api/chat").headers()[AUTHORIZATION],
+            "Basic dXNlcjpwYXNz"
+        );
+        assert_eq!(
+            prepare(&bearer, "http://localhost:11434/api/chat").headers()[AUTHORIZATION],
+            "Bearer token"
+        );
+    }
+
+    fn param(name: &str, value: &str) -> ProviderParam {
+        ProviderParam {
+            id: UniqueId::new(),
+            name: name.to_string(),
+            value: value.to_string(),
+            secret: false,
+        }
+    }
+
+    #[test]
+    fn test_headers_and_query() {
+        let endpoint = ProviderEndpoint::new()
+            .params(ProviderParams {
+                headers: vec![param("X-Title", " Astrum "), param("Bad Name", "ignored")],
+                query: vec![param("tenant", "a b&c")],
+            })
+            .omit_authorization(true);
+
+        let request = prepare(&endpoint, "http://localhost:11434/api/tags?limit=5");
+
+        assert_eq!(request.headers()["x-title"], "Astrum");
+        assert_eq!(request.headers().len(), 1);
+        assert_eq!(
+            request.uri(),
+            "http://localhost:11434/api/tags?limit=5&tenant=a+b%26c"
+        );
+    }
+
+    #[test]
+    fn test_edited_params_keep_their_ids() {
+        let previous = ProviderParams {
+            headers: vec![param("X-Token", "a"), param("X-Token", "b")],
+            query: vec![param("key", "c")],
+        };
+
+        let mut params = ProviderParams {
+            headers: vec![
+                param("X-New", "d"),
+                param("X-Token", "b"),
+                param("X-Token", "a"),
+                param("X-Token", "e"),
+            ],
+            query: vec![param("X-Token", "f")],
+        };
+        params.keep_ids(&previous);
+
+        assert_eq!(params.headers[1].id, previous.headers[0].id);
+        assert_eq!(params.headers[2].id, previous.headers[1].id);
+        assert!(
+            [&params.headers[0], &params.headers[3], &params.query[0]]
+                .iter()
+                .all(|param| previous.iter().all(|previous| previous.id != param.id))
+        );
+    }
+}
@@ -1,13 +1,90 @@
+use std::sync::Arc;
+
 use gpui::{App, AppContext, Entity, SharedString};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::managers::{DbError, Migration};
 
 pub struct SettingsManager {
+    db_connection: Option<Arc<Connection>>,
     pub current_settings_page_name: Entity<SharedString>,
+    /// The system prompt of chats that don't set their own.
+    pub default_system_prompt: Entity<Option<String>>,
 }
 
 impl SettingsManager {
+    pub const MIGRATIONS: &'static [Migration] = &[Migration::sql(
+        1,
+        "create settings",
+        "
+        CREATE TABLE IF NOT EXISTS settings (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        ",
+    )];
+
     pub fn new(cx: &mut App) -> Self {
         Self {
+            db_connection: None,
             current_settings_page_name: cx.new(|_cx| SharedString::new("Providers")),
+            default_system_prompt: cx.new(|_cx| None),
         }
     }
+
+    pub fn init(&mut self, cx: &mut App, db_connection: Arc<Connection>) -> Result<(), DbError> {
+        let default_system_prompt = load_setting(&db_connection, "default_system_prompt")?;
+        self.default_system_prompt.update(cx, |this, _cx| {
+            *this = default_system_prompt;
+        });
+
+        self.db_connection = Some(db_connection);
+
+        Ok(())
+    }
+
+    pub fn set_default_system_prompt(
+        &self,
+        cx: &mut App,
+        system_prompt: Option<String>,
+    ) -> Result<(), DbError> {
+        let system_prompt = system_prompt.filter(|system_prompt| !system_prompt.trim().is_empty());
+
+        self.save_setting("default_system_prompt", system_prompt.as_deref())?;
+
+        self.default_system_prompt.update(cx, |this, cx| {
+            *this = system_prompt;
+            cx.notify();
+        });
+
+        Ok(())
+    }
+
+    /// Stores a setting, removing it when `value` is `None`.
+    fn save_setting(&self, key: &str, value: Option<&str>) -> Result<(), DbError> {
+        let db_connection = self
+            .db_connection
+            .as_ref()
+            .ok_or_else(|| DbError::MissingData("database connection"))?;
+
+        match value {
+            Some(value) => db_connection.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                (key, value),
+            ),
+            None => db_connection.execute("DELETE FROM settings WHERE key = ?1", [key]),
+        }
+        .map_err(DbError::SqliteError)?;
+
+        Ok(())
+    }
+}
+
+fn load_setting(db_connection: &Connection, key: &str) -> Result<Option<String>, DbError> {
+    db_connection
+        .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(DbError::SqliteError)
 }
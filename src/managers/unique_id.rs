@@ -4,8 +4,10 @@ use rusqlite::{
     ToSql,
     types::{FromSql, FromSqlError, ToSqlOutput, ValueRef},
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+#[derive(Hash, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct UniqueId(String);
 
 impl UniqueId {
@@ -0,0 +1,771 @@
+//! Parses the markdown of messages into flat blocks that can be laid out one after another.
+
+use std::{borrow::Cow, ops::Range};
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use crate::utils::math::{MathNode, math_to_text, parse_math};
+
+/// A run of text with inline styles, e.g. a paragraph or a table cell.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InlineText {
+    pub text: String,
+    /// Ranges of `text` and their styles. Ranges of nested styles overlap.
+    pub spans: Vec<(Range<usize>, InlineStyle)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InlineStyle {
+    Strong,
+    Emphasis,
+    Strikethrough,
+    Code,
+    /// A link, or an image shown by its alt text.
+    Link(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListMarker {
+    Bullet,
+    Ordered(u64),
+    Task { checked: bool },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColumnAlignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarkdownBlock {
+    /// How many block quotes the block is nested in.
+    pub quote_depth: usize,
+    /// How many lists the block is nested in.
+    pub list_depth: usize,
+    /// Set on the first block of a list item.
+    pub marker: Option<ListMarker>,
+    pub kind: MarkdownBlockKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkdownBlockKind {
+    Paragraph(InlineText),
+    Heading {
+        level: u8,
+        text: InlineText,
+    },
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+    Table {
+        alignments: Vec<ColumnAlignment>,
+        header: Vec<InlineText>,
+        rows: Vec<Vec<InlineText>>,
+    },
+    /// Display math, with its TeX source. `node` is unset if the TeX couldn't be parsed.
+    Math {
+        source: String,
+        node: Option<MathNode>,
+    },
+    Rule,
+}
+
+/// Where a top-level block starts in the source, and the index of its first parsed block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Section {
+    source_offset: usize,
+    block_ix: usize,
+}
+
+/// A parsed markdown document that can be updated as its source grows.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownDocument {
+    source: String,
+    blocks: Vec<MarkdownBlock>,
+    sections: Vec<Section>,
+}
+
+impl MarkdownDocument {
+    pub fn new(source: &str) -> Self {
+        let mut document = Self::default();
+        document.set_source(source);
+        document
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn blocks(&self) -> &[MarkdownBlock] {
+        &self.blocks
+    }
+
+    /// Updates the document to a new source, returning whether it changed.
+    ///
+    /// When text was only appended, as happens while a reply streams in, only the last two
+    /// top-level blocks are parsed again, since new text can extend the last block or turn
+    /// it into part of the one before, e.g. a row of a table. Reference-style link
+    /// definitions that arrive late don't apply to the kept blocks.
+    pub fn set_source(&mut self, source: &str) -> bool {
+        let source = normalize_math_delimiters(source);
+        let source = source.as_ref();
+
+        if source == self.source {
+            return false;
+        }
+
+        let reparsed_section = match source.starts_with(&self.source) {
+            true => {
+                let keep = self.sections.len().saturating_sub(2);
+                self.sections.drain(keep..).next()
+            }
+            false => None,
+        };
+
+        let source_offset = match reparsed_section {
+            Some(section) => {
+                self.blocks.truncate(section.block_ix);
+                section.source_offset
+            }
+            None => {
+                self.blocks.clear();
+                self.sections.clear();
+                0
+            }
+        };
+
+        let mut parser = BlockParser::new(&mut self.blocks);
+        for (event, range) in
+            Parser::new_ext(&source[source_offset..], parser_options()).into_offset_iter()
+        {
+            if parser.depth == 0 && starts_block(&event) {
+                self.sections.push(Section {
+                    source_offset: source_offset + range.start,
+                    block_ix: parser.blocks.len(),
+                });
+            }
+
+            parser.event(event);
+        }
+        parser.finish();
+
+        self.source = source.to_string();
+
+        true
+    }
+}
+
+/// Parses markdown into blocks.
+pub fn parse_markdown(source: &str) -> Vec<MarkdownBlock> {
+    MarkdownDocument::new(source).blocks
+}
+
+/// Whether a link in model output may be opened. Only web and mail links are,
+/// so a reply can't open local files or hand a URL to another app.
+pub fn is_openable_link(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "mailto"))
+}
+
+fn parser_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_MATH
+}
+
+/// Rewrites `\[...\]` and `\(...\)` math to the `$$...$$` and `$...$` the parser
+/// understands, leaving code as it is.
+fn normalize_math_delimiters(source: &str) -> Cow<'_, str> {
+    if !source.contains("\\[") && !source.contains("\\(") {
+        return Cow::Borrowed(source);
+    }
+
+    let code_ranges = code_ranges(source);
+    let find_delimiter = |delimiter: &str, from: usize| {
+        source[from..]
+            .match_indices(delimiter)
+            .map(|(ix, _)| from + ix)
+            .find(|ix| {
+                !code_ranges.iter().any(|range| range.contains(ix))
+                    && !source[..*ix].ends_with('\\')
+            })
+    };
+
+    let mut normalized = String::with_capacity(source.len());
+    let mut copied_up_to = 0;
+    let mut search_from = 0;
+
+    loop {
+        let display_open = find_delimiter("\\[", search_from);
+        let inline_open = find_delimiter("\\(", search_from);
+        let (open_ix, is_display) = match (display_open, inline_open) {
+            (Some(display_ix), Some(inline_ix)) if inline_ix < display_ix => (inline_ix, false),
+            (Some(display_ix), _) => (display_ix, true),
+            (None, Some(inline_ix)) => (inline_ix, false),
+            (None, None) => break,
+        };
+
+        let close = if is_display { "\\]" } else { "\\)" };
+        // Unclosed math, e.g. while it's still streaming in, is left as it is.
+        let Some(close_ix) = find_delimiter(close, open_ix + 2) else {
+            search_from = open_ix + 2;
+            continue;
+        };
+
+        let tex = &source[open_ix + 2..close_ix];
+        normalized.push_str(&source[copied_up_to..open_ix]);
+        match is_display {
+            true => {
+                normalized.push_str("$$");
+                normalized.push_str(tex);
+                normalized.push_str("$$");
+            }
+            false => {
+                normalized.push('$');
+                normalized.push_str(tex.trim());
+                normalized.push('$');
+            }
+        }
+
+        copied_up_to = close_ix + 2;
+        search_from = copied_up_to;
+    }
+
+    normalized.push_str(&source[copied_up_to..]);
+    Cow::Owned(normalized)
+}
+
+/// The ranges of fenced code blocks and inline code spans in markdown.
+fn code_ranges(source: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut fence_start: Option<(usize, &str)> = None;
+    let mut line_start = 0;
+
+    for line in source.split_inclusive('\n') {
+        let line_range = line_start..line_start + line.len();
+        line_start = line_range.end;
+
+        let trimmed = line.trim_start();
+        let fence = ["```", "~~~"]
+            .into_iter()
+            .find(|fence| trimmed.starts_with(fence));
+
+        match (fence_start, fence) {
+            (Some((start, open)), Some(fence)) if fence == open => {
+                ranges.push(start..line_range.end);
+                fence_start = None;
+            }
+            (Some(_), _) => {}
+            (None, Some(fence)) => fence_start = Some((line_range.start, fence)),
+            (None, None) => ranges.extend(
+                inline_code_ranges(line)
+                    .into_iter()
+                    .map(|range| line_range.start + range.start..line_range.start + range.end),
+            ),
+        }
+    }
+
+    if let Some((start, _)) = fence_start {
+        ranges.push(start..source.len());
+    }
+
+    ranges
+}
+
+fn inline_code_ranges(line: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut position = 0;
+
+    while let Some(start) = line[position..].find('`').map(|ix| position + ix) {
+        let ticks = line[start..].len() - line[start..].trim_start_matches('`').len();
+        let content_start = start + ticks;
+        let closing = "`".repeat(ticks);
+
+        match line[content_start..].find(&closing) {
+            Some(ix) => {
+                let end = content_start + ix + ticks;
+                ranges.push(start..end);
+                position = end;
+            }
+            None => position = content_start,
+        }
+    }
+
+    ranges
+}
+
+fn starts_block(event: &Event) -> bool {
+    matches!(event, Event::Start(_) | Event::Rule)
+}
+
+struct TableBuilder {
+    alignments: Vec<ColumnAlignment>,
+    header: Vec<InlineText>,
+    rows: Vec<Vec<InlineText>>,
+    in_header: bool,
+}
+
+/// Turns the events of a parser into blocks, flattening lists and quotes into
+/// the depths of the blocks inside them.
+struct BlockParser<'a> {
+    blocks: &'a mut Vec<MarkdownBlock>,
+    /// How deeply the current event is nested in tags.
+    depth: usize,
+    quote_depth: usize,
+    /// The number of the next item of each open list, or `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    marker: Option<ListMarker>,
+    /// The text being collected, with the styles opened so far.
+    inline: Option<InlineText>,
+    open_styles: Vec<(usize, InlineStyle)>,
+    heading: Option<u8>,
+    code: Option<(Option<String>, String)>,
+    table: Option<TableBuilder>,
+}
+
+impl<'a> BlockParser<'a> {
+    fn new(blocks: &'a mut Vec<MarkdownBlock>) -> Self {
+        Self {
+            blocks,
+            depth: 0,
+            quote_depth: 0,
+            lists: Vec::new(),
+            marker: None,
+            inline: None,
+            open_styles: Vec::new(),
+            heading: None,
+            code: None,
+            table: None,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => {
+                self.depth += 1;
+                self.start(tag);
+            }
+            Event::End(tag) => {
+                self.depth = self.depth.saturating_sub(1);
+                self.end(tag);
+            }
+            Event::Text(text) => match &mut self.code {
+                Some((_, code)) => code.push_str(&text),
+                None => self.push_text(&text, None),
+            },
+            Event::Code(text) => self.push_text(&text, Some(InlineStyle::Code)),
+            Event::Html(html) | Event::InlineHtml(html) => self.push_text(&html, None),
+            Event::InlineMath(tex) => self.push_inline_math(&tex),
+            Event::DisplayMath(tex) => {
+                // Display math breaks the paragraph it's in.
+                self.flush_inline();
+                self.push_block(MarkdownBlockKind::Math {
+                    node: parse_math(&tex),
+                    source: tex.to_string(),
+                });
+            }
+            Event::FootnoteReference(label) => self.push_text(&format!("[{label}]"), None),
+            // A break can start a paragraph when it follows display math.
+            Event::SoftBreak if self.inline.is_none() => {}
+            Event::SoftBreak => self.push_text(" ", None),
+            Event::HardBreak => self.push_text("\n", None),
+            Event::Rule => {
+                self.flush_inline();
+                self.push_block(MarkdownBlockKind::Rule);
+            }
+            Event::TaskListMarker(checked) => self.marker = Some(ListMarker::Task { checked }),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                self.flush_inline();
+                self.inline = Some(InlineText::default());
+            }
+            Tag::Heading { level, .. } => {
+                self.flush_inline();
+                self.heading = Some(level as u8);
+                self.inline = Some(InlineText::default());
+            }
+            Tag::BlockQuote(_) => {
+                self.flush_inline();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush_inline();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .filter(|language| !language.is_empty())
+                        .map(ToString::to_string),
+                    CodeBlockKind::Indented => None,
+                };
+                self.code = Some((language, String::new()));
+            }
+            Tag::List(start) => {
+                self.flush_inline();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush_inline();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        ListMarker::Ordered(*number - 1)
+                    }
+                    _ => ListMarker::Bullet,
+                };
+                self.marker = Some(marker);
+            }
+            Tag::Table(alignments) => {
+                self.flush_inline();
+                self.table = Some(TableBuilder {
+                    alignments: alignments.into_iter().map(column_alignment).collect(),
+                    header: Vec::new(),
+                    rows: Vec::new(),
+                    in_header: false,
+                });
+            }
+            Tag::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.in_header = true;
+                }
+            }
+            Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => self.inline = Some(InlineText::default()),
+            Tag::Emphasis => self.open_style(InlineStyle::Emphasis),
+            Tag::Strong => self.open_style(InlineStyle::Strong),
+            Tag::Strikethrough => self.open_style(InlineStyle::Strikethrough),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.open_style(InlineStyle::Link(dest_url.to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::HtmlBlock => self.flush_inline(),
+            TagEnd::Heading(_) => self.flush_inline(),
+            TagEnd::BlockQuote(_) => {
+                self.flush_inline();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            TagEnd::CodeBlock => {
+                if let Some((language, mut code)) = self.code.take() {
+                    if code.ends_with('\n') {
+                        code.pop();
+                    }
+                    self.push_block(MarkdownBlockKind::CodeBlock { language, code });
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush_inline();
+                self.lists.pop();
+            }
+            TagEnd::Item => {
+                self.flush_inline();
+                // An empty item still shows its marker.
+                if self.marker.is_some() {
+                    self.push_block(MarkdownBlockKind::Paragraph(InlineText::default()));
+                }
+            }
+            TagEnd::TableCell => {
+                let cell = self.take_inline();
+                if let Some(table) = &mut self.table {
+                    match (table.in_header, table.rows.last_mut()) {
+                        (false, Some(row)) => row.push(cell),
+                        _ => table.header.push(cell),
+                    }
+                }
+            }
+            TagEnd::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.in_header = false;
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.push_block(MarkdownBlockKind::Table {
+                        alignments: table.alignments,
+                        header: table.header,
+                        rows: table.rows,
+                    });
+                }
+            }
+            TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Link
+            | TagEnd::Image => self.close_style(),
+            _ => {}
+        }
+    }
+
+    /// Closes whatever is still open when the source ends, e.g. an unterminated code block.
+    fn finish(mut self) {
+        self.flush_inline();
+
+        if let Some((language, code)) = self.code.take() {
+            self.push_block(MarkdownBlockKind::CodeBlock { language, code });
+        }
+    }
+
+    fn push_text(&mut self, text: &str, style: Option<InlineStyle>) {
+        let inline = self.inline.get_or_insert_with(InlineText::default);
+        let start = inline.text.len();
+        inline.text.push_str(text);
+
+        if let Some(style) = style {
+            let end = inline.text.len();
+            inline.spans.push((start..end, style));
+        }
+    }
+
+    /// Writes math inside a paragraph as a line of text, or as its source if it
+    /// can't be parsed.
+    ///
+    /// This is only an approximation: text runs can't stack fractions or scripts,
+    /// so e.g. `e^{i\pi}` reads `e^(iπ)`. Only display math is laid out as math.
+    fn push_inline_math(&mut self, tex: &str) {
+        let Some(node) = parse_math(tex) else {
+            self.push_text(&format!("${tex}$"), Some(InlineStyle::Code));
+            return;
+        };
+
+        let (text, identifiers) = math_to_text(&node);
+        let start = self
+            .inline
+            .get_or_insert_with(InlineText::default)
+            .text
+            .len();
+        self.push_text(&text, None);
+
+        if let Some(inline) = &mut self.inline {
+            inline.spans.extend(identifiers.into_iter().map(|range| {
+                (
+                    start + range.start..start + range.end,
+                    InlineStyle::Emphasis,
+                )
+            }));
+        }
+    }
+
+    fn open_style(&mut self, style: InlineStyle) {
+        let start = self
+            .inline
+            .get_or_insert_with(InlineText::default)
+            .text
+            .len();
+        self.open_styles.push((start, style));
+    }
+
+    fn close_style(&mut self) {
+        let Some((start, style)) = self.open_styles.pop() else {
+            return;
+        };
+        let Some(inline) = &mut self.inline else {
+            return;
+        };
+
+        let end = inline.text.len();
+        if start < end {
+            inline.spans.push((start..end, style));
+        }
+    }
+
+    fn take_inline(&mut self) -> InlineText {
+        let mut inline = self.inline.take().unwrap_or_default();
+        inline.spans.sort_by_key(|(range, _)| range.start);
+        self.open_styles.clear();
+        inline
+    }
+
+    /// Ends the text being collected, e.g. the text of a tight list item
+    /// before a nested list starts.
+    fn flush_inline(&mut self) {
+        if self.inline.is_none() || self.table.is_some() {
+            return;
+        }
+
+        let text = self.take_inline();
+        let kind = match self.heading.take() {
+            Some(level) => MarkdownBlockKind::Heading { level, text },
+            // What's left around display math, e.g. the line break after it.
+            None if text.text.trim().is_empty() => return,
+            None => MarkdownBlockKind::Paragraph(text),
+        };
+
+        self.push_block(kind);
+    }
+
+    fn push_block(&mut self, kind: MarkdownBlockKind) {
+        self.blocks.push(MarkdownBlock {
+            quote_depth: self.quote_depth,
+            list_depth: self.lists.len(),
+            marker: self.marker.take(),
+            kind,
+        });
+    }
+}
+
+fn column_alignment(alignment: Alignment) -> ColumnAlignment {
+    match alignment {
+        Alignment::None | Alignment::Left => ColumnAlignment::Left,
+        Alignment::Center => ColumnAlignment::Center,
+        Alignment::Right => ColumnAlignment::Right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openable_links() {
+        assert!(is_openable_link("https://example.com/a?b=c"));
+        assert!(is_openable_link("HTTP://example.com"));
+        assert!(is_openable_link("mailto:someone@example.com"));
+
+        assert!(!is_openable_link("file:///etc/passwd"));
+        assert!(!is_openable_link("javascript:alert(1)"));
+        assert!(!is_openable_link("vscode://open?file=/tmp/x"));
+        assert!(!is_openable_link("/relative/path"));
+    }
+
+    fn paragraph(text: &str) -> MarkdownBlockKind {
+        MarkdownBlockKind::Paragraph(InlineText {
+            text: text.to_string(),
+            spans: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_parse_blocks() {
+        let blocks = parse_markdown(
+            "# Title\n\nSome **bold** and `code`.\n\n- one\n- two\n  1. nested\n\n> quoted\n\n```rust\nfn main() {}\n```\n\n---",
+        );
+
+        let kinds = blocks.iter().map(|block| &block.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds[0],
+            &MarkdownBlockKind::Heading {
+                level: 1,
+                text: InlineText {
+                    text: String::from("Title"),
+                    spans: Vec::new()
+                }
+            }
+        );
+        assert_eq!(
+            kinds[1],
+            &MarkdownBlockKind::Paragraph(InlineText {
+                text: String::from("Some bold and code."),
+                spans: vec![(5..9, InlineStyle::Strong), (14..18, InlineStyle::Code)],
+            })
+        );
+        assert_eq!(kinds[2], &paragraph("one"));
+        assert_eq!(blocks[2].marker, Some(ListMarker::Bullet));
+        assert_eq!(kinds[4], &paragraph("nested"));
+        assert_eq!(
+            (blocks[4].list_depth, blocks[4].marker),
+            (2, Some(ListMarker::Ordered(1)))
+        );
+        assert_eq!(kinds[5], &paragraph("quoted"));
+        assert_eq!(blocks[5].quote_depth, 1);
+        assert_eq!(
+            kinds[6],
+            &MarkdownBlockKind::CodeBlock {
+                language: Some(String::from("rust")),
+                code: String::from("fn main() {}"),
+            }
+        );
+        assert_eq!(kinds[7], &MarkdownBlockKind::Rule);
+        assert_eq!(blocks.len(), 8);
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let blocks = parse_markdown("| a | b |\n|:-|-:|\n| 1 | *2* |");
+
+        assert_eq!(
+            blocks[0].kind,
+            MarkdownBlockKind::Table {
+                alignments: vec![ColumnAlignment::Left, ColumnAlignment::Right],
+                header: vec![
+                    InlineText {
+                        text: String::from("a"),
+                        spans: Vec::new()
+                    },
+                    InlineText {
+                        text: String::from("b"),
+                        spans: Vec::new()
+                    },
+                ],
+                rows: vec![vec![
+                    InlineText {
+                        text: String::from("1"),
+                        spans: Vec::new()
+                    },
+                    InlineText {
+                        text: String::from("2"),
+                        spans: vec![(0..1, InlineStyle::Emphasis)]
+                    },
+                ]],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_math() {
+        let blocks = parse_markdown(
+            "Euler: \\( e^{i\\pi} \\) and $x$.\n\\[\nx^2\n\\]\n`\\(code\\)` and $\\oops$",
+        );
+
+        assert_eq!(
+            blocks[0].kind,
+            MarkdownBlockKind::Paragraph(InlineText {
+                text: String::from("Euler: e^(iπ) and x. "),
+                spans: vec![
+                    (7..8, InlineStyle::Emphasis),
+                    (19..20, InlineStyle::Emphasis)
+                ],
+            })
+        );
+        assert!(matches!(
+            &blocks[1].kind,
+            MarkdownBlockKind::Math { source, node: Some(_) } if source == "\nx^2\n"
+        ));
+        assert_eq!(
+            blocks[2].kind,
+            MarkdownBlockKind::Paragraph(InlineText {
+                text: String::from("\\(code\\) and $\\oops$"),
+                spans: vec![(0..8, InlineStyle::Code), (13..20, InlineStyle::Code)],
+            })
+        );
+    }
+
+    #[test]
+    fn test_streamed_source_matches_full_parse() {
+        let source = "Intro with [a link](https://example.com).\n\n1. first\n2. second\n\n```py\nprint('hi')\n```\n\n| x | y |\n|---|---|\n| 1 | 2 |\n\nDone\n===\n";
+
+        let mut document = MarkdownDocument::default();
+        for (ix, _) in source.char_indices().skip(1) {
+            document.set_source(&source[..ix]);
+            assert_eq!(document.blocks(), parse_markdown(&source[..ix]), "at {ix}");
+        }
+
+        document.set_source(source);
+        assert_eq!(document.blocks(), parse_markdown(source));
+    }
+}
@@ -0,0 +1,1117 @@
+//! Parses the subset of TeX math that shows up in replies, so formulas can be laid
+//! out without a TeX engine.
+
+use std::ops::Range;
+
+use phf::phf_map;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MathNode {
+    /// A variable, shown in italics.
+    Identifier(String),
+    /// A number, function name or other upright text.
+    Text(String),
+    /// A binary operator or relation, with space around it.
+    Operator(String),
+    /// Horizontal space, in ems.
+    Space(f32),
+    Row(Vec<MathNode>),
+    Fraction {
+        numerator: Box<MathNode>,
+        denominator: Box<MathNode>,
+        /// Unset for binomials.
+        line: bool,
+    },
+    Root {
+        index: Option<Box<MathNode>>,
+        radicand: Box<MathNode>,
+    },
+    Scripts {
+        base: Box<MathNode>,
+        superscript: Option<Box<MathNode>>,
+        subscript: Option<Box<MathNode>>,
+        /// Whether the scripts go above and below the base, as for sums and limits.
+        limits: bool,
+    },
+    Delimited {
+        open: String,
+        close: String,
+        body: Box<MathNode>,
+    },
+    Matrix(Vec<Vec<MathNode>>),
+    Accent {
+        accent: char,
+        body: Box<MathNode>,
+    },
+}
+
+enum Symbol {
+    Identifier(&'static str),
+    Text(&'static str),
+    Operator(&'static str),
+    /// A function name like `sin`, followed by a thin space.
+    Function(&'static str),
+    LargeOperator {
+        symbol: &'static str,
+        limits: bool,
+    },
+    Space(f32),
+    /// Commands that only affect sizing or numbering.
+    Ignored,
+}
+
+static SYMBOLS: phf::Map<&'static str, Symbol> = phf_map! {
+    "alpha" => Symbol::Identifier("α"),
+    "beta" => Symbol::Identifier("β"),
+    "gamma" => Symbol::Identifier("γ"),
+    "delta" => Symbol::Identifier("δ"),
+    "epsilon" => Symbol::Identifier("ϵ"),
+    "varepsilon" => Symbol::Identifier("ε"),
+    "zeta" => Symbol::Identifier("ζ"),
+    "eta" => Symbol::Identifier("η"),
+    "theta" => Symbol::Identifier("θ"),
+    "vartheta" => Symbol::Identifier("ϑ"),
+    "iota" => Symbol::Identifier("ι"),
+    "kappa" => Symbol::Identifier("κ"),
+    "lambda" => Symbol::Identifier("λ"),
+    "mu" => Symbol::Identifier("μ"),
+    "nu" => Symbol::Identifier("ν"),
+    "xi" => Symbol::Identifier("ξ"),
+    "pi" => Symbol::Identifier("π"),
+    "varpi" => Symbol::Identifier("ϖ"),
+    "rho" => Symbol::Identifier("ρ"),
+    "varrho" => Symbol::Identifier("ϱ"),
+    "sigma" => Symbol::Identifier("σ"),
+    "varsigma" => Symbol::Identifier("ς"),
+    "tau" => Symbol::Identifier("τ"),
+    "upsilon" => Symbol::Identifier("υ"),
+    "phi" => Symbol::Identifier("ϕ"),
+    "varphi" => Symbol::Identifier("φ"),
+    "chi" => Symbol::Identifier("χ"),
+    "psi" => Symbol::Identifier("ψ"),
+    "omega" => Symbol::Identifier("ω"),
+    "Gamma" => Symbol::Text("Γ"),
+    "Delta" => Symbol::Text("Δ"),
+    "Theta" => Symbol::Text("Θ"),
+    "Lambda" => Symbol::Text("Λ"),
+    "Xi" => Symbol::Text("Ξ"),
+    "Pi" => Symbol::Text("Π"),
+    "Sigma" => Symbol::Text("Σ"),
+    "Upsilon" => Symbol::Text("Υ"),
+    "Phi" => Symbol::Text("Φ"),
+    "Psi" => Symbol::Text("Ψ"),
+    "Omega" => Symbol::Text("Ω"),
+
+    "times" => Symbol::Operator("×"),
+    "cdot" => Symbol::Operator("⋅"),
+    "div" => Symbol::Operator("÷"),
+    "pm" => Symbol::Operator("±"),
+    "mp" => Symbol::Operator("∓"),
+    "ast" => Symbol::Operator("∗"),
+    "star" => Symbol::Operator("⋆"),
+    "circ" => Symbol::Operator("∘"),
+    "bullet" => Symbol::Operator("∙"),
+    "oplus" => Symbol::Operator("⊕"),
+    "otimes" => Symbol::Operator("⊗"),
+    "cup" => Symbol::Operator("∪"),
+    "cap" => Symbol::Operator("∩"),
+    "setminus" => Symbol::Operator("∖"),
+    "wedge" => Symbol::Operator("∧"),
+    "land" => Symbol::Operator("∧"),
+    "vee" => Symbol::Operator("∨"),
+    "lor" => Symbol::Operator("∨"),
+    "leq" => Symbol::Operator("≤"),
+    "le" => Symbol::Operator("≤"),
+    "geq" => Symbol::Operator("≥"),
+    "ge" => Symbol::Operator("≥"),
+    "neq" => Symbol::Operator("≠"),
+    "ne" => Symbol::Operator("≠"),
+    "approx" => Symbol::Operator("≈"),
+    "equiv" => Symbol::Operator("≡"),
+    "sim" => Symbol::Operator("∼"),
+    "simeq" => Symbol::Operator("≃"),
+    "cong" => Symbol::Operator("≅"),
+    "propto" => Symbol::Operator("∝"),
+    "ll" => Symbol::Operator("≪"),
+    "gg" => Symbol::Operator("≫"),
+    "in" => Symbol::Operator("∈"),
+    "notin" => Symbol::Operator("∉"),
+    "ni" => Symbol::Operator("∋"),
+    "subset" => Symbol::Operator("⊂"),
+    "subseteq" => Symbol::Operator("⊆"),
+    "supset" => Symbol::Operator("⊃"),
+    "supseteq" => Symbol::Operator("⊇"),
+    "perp" => Symbol::Operator("⊥"),
+    "parallel" => Symbol::Operator("∥"),
+    "mid" => Symbol::Operator("∣"),
+    "coloneqq" => Symbol::Operator("≔"),
+    "to" => Symbol::Operator("→"),
+    "rightarrow" => Symbol::Operator("→"),
+    "leftarrow" => Symbol::Operator("←"),
+    "gets" => Symbol::Operator("←"),
+    "leftrightarrow" => Symbol::Operator("↔"),
+    "Rightarrow" => Symbol::Operator("⇒"),
+    "Leftarrow" => Symbol::Operator("⇐"),
+    "Leftrightarrow" => Symbol::Operator("⇔"),
+    "implies" => Symbol::Operator("⟹"),
+    "iff" => Symbol::Operator("⟺"),
+    "mapsto" => Symbol::Operator("↦"),
+    "longrightarrow" => Symbol::Operator("⟶"),
+    "uparrow" => Symbol::Text("↑"),
+    "downarrow" => Symbol::Text("↓"),
+
+    "infty" => Symbol::Text("∞"),
+    "partial" => Symbol::Text("∂"),
+    "nabla" => Symbol::Text("∇"),
+    "forall" => Symbol::Text("∀"),
+    "exists" => Symbol::Text("∃"),
+    "nexists" => Symbol::Text("∄"),
+    "emptyset" => Symbol::Text("∅"),
+    "varnothing" => Symbol::Text("∅"),
+    "neg" => Symbol::Text("¬"),
+    "lnot" => Symbol::Text("¬"),
+    "hbar" => Symbol::Identifier("ℏ"),
+    "ell" => Symbol::Identifier("ℓ"),
+    "Re" => Symbol::Text("ℜ"),
+    "Im" => Symbol::Text("ℑ"),
+    "aleph" => Symbol::Text("ℵ"),
+    "angle" => Symbol::Text("∠"),
+    "triangle" => Symbol::Text("△"),
+    "prime" => Symbol::Text("′"),
+    "degree" => Symbol::Text("°"),
+    "ldots" => Symbol::Text("…"),
+    "dots" => Symbol::Text("…"),
+    "cdots" => Symbol::Text("⋯"),
+    "vdots" => Symbol::Text("⋮"),
+    "ddots" => Symbol::Text("⋱"),
+    "therefore" => Symbol::Text("∴"),
+    "because" => Symbol::Text("∵"),
+    "top" => Symbol::Text("⊤"),
+    "bot" => Symbol::Text("⊥"),
+    "langle" => Symbol::Text("⟨"),
+    "rangle" => Symbol::Text("⟩"),
+    "lfloor" => Symbol::Text("⌊"),
+    "rfloor" => Symbol::Text("⌋"),
+    "lceil" => Symbol::Text("⌈"),
+    "rceil" => Symbol::Text("⌉"),
+    "vert" => Symbol::Text("|"),
+    "lvert" => Symbol::Text("|"),
+    "rvert" => Symbol::Text("|"),
+    "Vert" => Symbol::Text("‖"),
+    "lVert" => Symbol::Text("‖"),
+    "rVert" => Symbol::Text("‖"),
+    "{" => Symbol::Text("{"),
+    "}" => Symbol::Text("}"),
+    "|" => Symbol::Text("‖"),
+    "%" => Symbol::Text("%"),
+    "$" => Symbol::Text("$"),
+    "&" => Symbol::Text("&"),
+    "#" => Symbol::Text("#"),
+    "_" => Symbol::Text("_"),
+
+    "sin" => Symbol::Function("sin"),
+    "cos" => Symbol::Function("cos"),
+    "tan" => Symbol::Function("tan"),
+    "cot" => Symbol::Function("cot"),
+    "sec" => Symbol::Function("sec"),
+    "csc" => Symbol::Function("csc"),
+    "arcsin" => Symbol::Function("arcsin"),
+    "arccos" => Symbol::Function("arccos"),
+    "arctan" => Symbol::Function("arctan"),
+    "sinh" => Symbol::Function("sinh"),
+    "cosh" => Symbol::Function("cosh"),
+    "tanh" => Symbol::Function("tanh"),
+    "log" => Symbol::Function("log"),
+    "ln" => Symbol::Function("ln"),
+    "lg" => Symbol::Function("lg"),
+    "exp" => Symbol::Function("exp"),
+    "dim" => Symbol::Function("dim"),
+    "ker" => Symbol::Function("ker"),
+    "deg" => Symbol::Function("deg"),
+    "arg" => Symbol::Function("arg"),
+    "Pr" => Symbol::Function("Pr"),
+
+    "sum" => Symbol::LargeOperator { symbol: "∑", limits: true },
+    "prod" => Symbol::LargeOperator { symbol: "∏", limits: true },
+    "coprod" => Symbol::LargeOperator { symbol: "∐", limits: true },
+    "bigcup" => Symbol::LargeOperator { symbol: "⋃", limits: true },
+    "bigcap" => Symbol::LargeOperator { symbol: "⋂", limits: true },
+    "bigoplus" => Symbol::LargeOperator { symbol: "⨁", limits: true },
+    "bigotimes" => Symbol::LargeOperator { symbol: "⨂", limits: true },
+    "int" => Symbol::LargeOperator { symbol: "∫", limits: false },
+    "iint" => Symbol::LargeOperator { symbol: "∬", limits: false },
+    "iiint" => Symbol::LargeOperator { symbol: "∭", limits: false },
+    "oint" => Symbol::LargeOperator { symbol: "∮", limits: false },
+    "lim" => Symbol::LargeOperator { symbol: "lim", limits: true },
+    "limsup" => Symbol::LargeOperator { symbol: "lim sup", limits: true },
+    "liminf" => Symbol::LargeOperator { symbol: "lim inf", limits: true },
+    "max" => Symbol::LargeOperator { symbol: "max", limits: true },
+    "min" => Symbol::LargeOperator { symbol: "min", limits: true },
+    "sup" => Symbol::LargeOperator { symbol: "sup", limits: true },
+    "inf" => Symbol::LargeOperator { symbol: "inf", limits: true },
+    "det" => Symbol::LargeOperator { symbol: "det", limits: true },
+    "gcd" => Symbol::LargeOperator { symbol: "gcd", limits: true },
+    "argmax" => Symbol::LargeOperator { symbol: "arg max", limits: true },
+    "argmin" => Symbol::LargeOperator { symbol: "arg min", limits: true },
+
+    "," => Symbol::Space(0.17),
+    ":" => Symbol::Space(0.22),
+    ">" => Symbol::Space(0.22),
+    ";" => Symbol::Space(0.28),
+    " " => Symbol::Space(0.33),
+    "!" => Symbol::Space(0.),
+    "thinspace" => Symbol::Space(0.17),
+    "enspace" => Symbol::Space(0.5),
+    "quad" => Symbol::Space(1.),
+    "qquad" => Symbol::Space(2.),
+
+    "displaystyle" => Symbol::Ignored,
+    "textstyle" => Symbol::Ignored,
+    "scriptstyle" => Symbol::Ignored,
+    "limits" => Symbol::Ignored,
+    "nolimits" => Symbol::Ignored,
+    "big" => Symbol::Ignored,
+    "Big" => Symbol::Ignored,
+    "bigg" => Symbol::Ignored,
+    "Bigg" => Symbol::Ignored,
+    "bigl" => Symbol::Ignored,
+    "bigr" => Symbol::Ignored,
+    "Bigl" => Symbol::Ignored,
+    "Bigr" => Symbol::Ignored,
+    "biggl" => Symbol::Ignored,
+    "biggr" => Symbol::Ignored,
+    "middle" => Symbol::Ignored,
+    "nonumber" => Symbol::Ignored,
+    "notag" => Symbol::Ignored,
+};
+
+/// Parses TeX math, or returns `None` if it uses anything that isn't supported.
+pub fn parse_math(tex: &str) -> Option<MathNode> {
+    let mut parser = MathParser {
+        tex,
+        position: 0,
+        depth: 0,
+    };
+
+    // Line breaks outside of an environment stack the lines, as in `gathered`.
+    let mut rows = parser.parse_rows()?;
+    if parser.next_token().is_some() {
+        return None;
+    }
+
+    match (rows.len(), rows.first().map(Vec::len)) {
+        (1, Some(1)) => rows.pop()?.pop(),
+        _ => Some(MathNode::Matrix(rows)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Char(char),
+    Command(&'a str),
+    GroupStart,
+    GroupEnd,
+    Superscript,
+    Subscript,
+    ColumnSeparator,
+    RowSeparator,
+}
+
+/// How deeply groups and commands may nest. The parser recurses for each level, so
+/// deeper TeX, which no real formula needs, could overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+struct MathParser<'a> {
+    tex: &'a str,
+    position: usize,
+    /// How many atoms are being parsed around the current one.
+    depth: usize,
+}
+
+impl<'a> MathParser<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.tex[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek_token(&mut self) -> Option<(Token<'a>, usize)> {
+        self.skip_whitespace();
+
+        let rest = &self.tex[self.position..];
+        let mut chars = rest.chars();
+        let char = chars.next()?;
+
+        let token = match char {
+            '{' => (Token::GroupStart, 1),
+            '}' => (Token::GroupEnd, 1),
+            '^' => (Token::Superscript, 1),
+            '_' => (Token::Subscript, 1),
+            '&' => (Token::ColumnSeparator, 1),
+            '\\' => {
+                let name_len = rest[1..]
+                    .find(|char: char| !char.is_ascii_alphabetic())
+                    .unwrap_or(rest.len() - 1);
+
+                match (name_len, chars.next()) {
+                    (0, Some('\\')) => (Token::RowSeparator, 2),
+                    (0, Some(char)) => (
+                        Token::Command(&rest[1..1 + char.len_utf8()]),
+                        1 + char.len_utf8(),
+                    ),
+                    (0, None) => return None,
+                    (name_len, _) => (Token::Command(&rest[1..1 + name_len]), 1 + name_len),
+                }
+            }
+            char => (Token::Char(char), char.len_utf8()),
+        };
+
+        Some(token)
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let (token, len) = self.peek_token()?;
+        self.position += len;
+        Some(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Option<()> {
+        (self.next_token()? == expected).then_some(())
+    }
+
+    /// Reads the raw text of a `{...}` group, e.g. the argument of `\text`.
+    fn parse_raw_group(&mut self) -> Option<&'a str> {
+        self.expect(Token::GroupStart)?;
+
+        let start = self.position;
+        let mut depth = 0;
+        for (ix, char) in self.tex[start..].char_indices() {
+            match char {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    self.position = start + ix + 1;
+                    return Some(&self.tex[start..start + ix]);
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Parses nodes until the end of the group, row or cell they're in.
+    fn parse_row(&mut self) -> Option<Vec<MathNode>> {
+        let mut nodes = Vec::new();
+
+        loop {
+            match self.peek_token() {
+                None
+                | Some((
+                    Token::GroupEnd
+                    | Token::ColumnSeparator
+                    | Token::RowSeparator
+                    | Token::Command("right" | "end"),
+                    _,
+                )) => return Some(nodes),
+                Some((Token::Superscript | Token::Subscript, _)) => {
+                    let is_superscript = self.next_token()? == Token::Superscript;
+                    let script = Box::new(self.parse_argument()?);
+                    let previous = nodes.pop().unwrap_or(MathNode::Row(Vec::new()));
+                    nodes.push(attach_script(previous, script, is_superscript)?);
+                }
+                Some(_) => nodes.extend(self.parse_atom()?),
+            }
+        }
+    }
+
+    /// Parses cells split by `&` and rows split by `\\`, up to the end of an environment.
+    fn parse_rows(&mut self) -> Option<Vec<Vec<MathNode>>> {
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+
+        loop {
+            cells.push(row_node(self.parse_row()?));
+
+            match self.peek_token() {
+                Some((Token::ColumnSeparator, _)) => {
+                    self.next_token();
+                }
+                Some((Token::RowSeparator, _)) => {
+                    self.next_token();
+                    rows.push(std::mem::take(&mut cells));
+                }
+                _ => break,
+            }
+        }
+
+        // A trailing `\\` doesn't start another row.
+        let is_empty_row = cells
+            .iter()
+            .all(|cell| matches!(cell, MathNode::Row(nodes) if nodes.is_empty()));
+        if !is_empty_row || rows.is_empty() {
+            rows.push(cells);
+        }
+
+        Some(rows)
+    }
+
+    /// Parses a single argument of a command or script: a group, or one character or command.
+    fn parse_argument(&mut self) -> Option<MathNode> {
+        match self.peek_token()? {
+            (Token::Char(char), len) => {
+                self.position += len;
+                Some(char_node(char))
+            }
+            (Token::GroupStart | Token::Command(_), _) => self.parse_atom().map(row_node),
+            _ => None,
+        }
+    }
+
+    /// Every nested group or command argument is parsed through here, so this is
+    /// where the nesting is limited.
+    fn parse_atom(&mut self) -> Option<Vec<MathNode>> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+
+        self.depth += 1;
+        let nodes = self.parse_atom_at_depth();
+        self.depth -= 1;
+
+        nodes
+    }
+
+    fn parse_atom_at_depth(&mut self) -> Option<Vec<MathNode>> {
+        match self.next_token()? {
+            Token::GroupStart => {
+                let nodes = self.parse_row()?;
+                self.expect(Token::GroupEnd)?;
+                Some(vec![row_node(nodes)])
+            }
+            Token::Char(char) if char.is_ascii_digit() => {
+                let start = self.position - 1;
+                let len = self.tex[start..]
+                    .find(|char: char| !char.is_ascii_digit() && char != '.')
+                    .unwrap_or(self.tex.len() - start);
+                self.position = start + len;
+                Some(vec![MathNode::Text(
+                    self.tex[start..start + len].to_string(),
+                )])
+            }
+            Token::Char(char) => Some(vec![char_node(char)]),
+            Token::Command(name) => self.parse_command(name),
+            _ => None,
+        }
+    }
+
+    fn parse_command(&mut self, name: &str) -> Option<Vec<MathNode>> {
+        if let Some(symbol) = SYMBOLS.get(name) {
+            let node = match symbol {
+                Symbol::Identifier(text) => MathNode::Identifier(text.to_string()),
+                Symbol::Text(text) => MathNode::Text(text.to_string()),
+                Symbol::Operator(text) => MathNode::Operator(text.to_string()),
+                Symbol::Function(text) => {
+                    return Some(vec![
+                        MathNode::Text(text.to_string()),
+                        MathNode::Space(0.17),
+                    ]);
+                }
+                Symbol::LargeOperator { symbol, limits } => MathNode::Scripts {
+                    base: Box::new(MathNode::Text(symbol.to_string())),
+                    superscript: None,
+                    subscript: None,
+                    limits: *limits,
+                },
+                Symbol::Space(width) => MathNode::Space(*width),
+                Symbol::Ignored => return Some(Vec::new()),
+            };
+            return Some(vec![node]);
+        }
+
+        let node = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => MathNode::Fraction {
+                numerator: Box::new(self.parse_argument()?),
+                denominator: Box::new(self.parse_argument()?),
+                line: true,
+            },
+            "binom" | "dbinom" | "tbinom" => MathNode::Delimited {
+                open: String::from("("),
+                close: String::from(")"),
+                body: Box::new(MathNode::Fraction {
+                    numerator: Box::new(self.parse_argument()?),
+                    denominator: Box::new(self.parse_argument()?),
+                    line: false,
+                }),
+            },
+            "sqrt" => {
+                let index = match self.peek_token() {
+                    Some((Token::Char('['), _)) => {
+                        self.next_token();
+                        let index = self.parse_until_char(']')?;
+                        Some(Box::new(row_node(index)))
+                    }
+                    _ => None,
+                };
+
+                MathNode::Root {
+                    index,
+                    radicand: Box::new(self.parse_argument()?),
+                }
+            }
+            "left" => {
+                let open = self.parse_delimiter()?;
+                let body = self.parse_row()?;
+                self.expect(Token::Command("right"))?;
+                let close = self.parse_delimiter()?;
+
+                MathNode::Delimited {
+                    open,
+                    close,
+                    body: Box::new(row_node(body)),
+                }
+            }
+            "text" | "textrm" | "textup" | "textnormal" | "mbox" => {
+                MathNode::Text(self.parse_raw_group()?.to_string())
+            }
+            "textit" => MathNode::Identifier(self.parse_raw_group()?.to_string()),
+            "textbf" | "mathrm" | "mathbf" | "mathsf" | "mathtt" | "mathfrak" | "operatorname"
+            | "boldsymbol" | "bm" => upright(self.parse_argument()?),
+            "mathit" | "underline" | "boxed" => self.parse_argument()?,
+            "mathbb" => map_letters(self.parse_argument()?, double_struck),
+            "mathcal" | "mathscr" => map_letters(self.parse_argument()?, script),
+            "hat" | "widehat" => self.parse_accent('\u{302}')?,
+            "bar" | "overline" => self.parse_accent('\u{305}')?,
+            "vec" | "overrightarrow" => self.parse_accent('\u{20d7}')?,
+            "dot" => self.parse_accent('\u{307}')?,
+            "ddot" => self.parse_accent('\u{308}')?,
+            "tilde" | "widetilde" => self.parse_accent('\u{303}')?,
+            "not" => match self.parse_argument()? {
+                MathNode::Operator(operator) => MathNode::Operator(format!("{operator}\u{338}")),
+                node => node,
+            },
+            "overset" | "stackrel" | "underset" => {
+                let script = Box::new(self.parse_argument()?);
+                let base = Box::new(self.parse_argument()?);
+                let (superscript, subscript) = match name {
+                    "underset" => (None, Some(script)),
+                    _ => (Some(script), None),
+                };
+
+                MathNode::Scripts {
+                    base,
+                    superscript,
+                    subscript,
+                    limits: true,
+                }
+            }
+            "label" | "tag" => {
+                self.parse_raw_group()?;
+                return Some(Vec::new());
+            }
+            "begin" => self.parse_environment()?,
+            _ => return None,
+        };
+
+        Some(vec![node])
+    }
+
+    fn parse_accent(&mut self, accent: char) -> Option<MathNode> {
+        Some(MathNode::Accent {
+            accent,
+            body: Box::new(self.parse_argument()?),
+        })
+    }
+
+    /// Parses nodes up to a closing character, e.g. the index of `\sqrt[3]{x}`.
+    fn parse_until_char(&mut self, end: char) -> Option<Vec<MathNode>> {
+        let mut nodes = Vec::new();
+
+        loop {
+            match self.peek_token()? {
+                (Token::Char(char), len) if char == end => {
+                    self.position += len;
+                    return Some(nodes);
+                }
+                _ => nodes.extend(self.parse_atom()?),
+            }
+        }
+    }
+
+    /// Parses the delimiter after `\left` or `\right`, where `.` stands for none.
+    fn parse_delimiter(&mut self) -> Option<String> {
+        match self.next_token()? {
+            Token::Char('.') => Some(String::new()),
+            Token::Char(char) => Some(char.to_string()),
+            Token::Command(name) => match SYMBOLS.get(name)? {
+                Symbol::Text(text) | Symbol::Operator(text) => Some(text.to_string()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn parse_environment(&mut self) -> Option<MathNode> {
+        let name = self.parse_raw_group()?;
+        if name == "array" {
+            // Column alignments aren't followed.
+            self.parse_raw_group()?;
+        }
+
+        let rows = self.parse_rows()?;
+        self.expect(Token::Command("end"))?;
+        if self.parse_raw_group()? != name {
+            return None;
+        }
+
+        let matrix = MathNode::Matrix(rows);
+        let (open, close) = match name {
+            "matrix" | "smallmatrix" | "array" | "aligned" | "align" | "align*" | "alignat"
+            | "gathered" | "gather" | "gather*" | "split" | "equation" | "equation*"
+            | "eqnarray" => return Some(matrix),
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "Bmatrix" => ("{", "}"),
+            "vmatrix" => ("|", "|"),
+            "Vmatrix" => ("‖", "‖"),
+            "cases" => ("{", ""),
+            _ => return None,
+        };
+
+        Some(MathNode::Delimited {
+            open: open.to_string(),
+            close: close.to_string(),
+            body: Box::new(matrix),
+        })
+    }
+}
+
+fn char_node(char: char) -> MathNode {
+    match char {
+        '+' | '=' | '<' | '>' => MathNode::Operator(char.to_string()),
+        '-' => MathNode::Operator(String::from("−")),
+        '*' => MathNode::Operator(String::from("∗")),
+        '\'' => MathNode::Text(String::from("′")),
+        '~' => MathNode::Space(0.33),
+        char if char.is_alphabetic() => MathNode::Identifier(char.to_string()),
+        char => MathNode::Text(char.to_string()),
+    }
+}
+
+/// A row of nodes, unwrapped if it only holds one.
+fn row_node(mut nodes: Vec<MathNode>) -> MathNode {
+    match nodes.len() {
+        1 => nodes.pop().unwrap_or(MathNode::Row(Vec::new())),
+        _ => MathNode::Row(nodes),
+    }
+}
+
+fn attach_script(base: MathNode, script: Box<MathNode>, is_superscript: bool) -> Option<MathNode> {
+    let (base, mut superscript, mut subscript, limits) = match base {
+        MathNode::Scripts {
+            base,
+            superscript,
+            subscript,
+            limits,
+        } => (base, superscript, subscript, limits),
+        base => (Box::new(base), None, None, false),
+    };
+
+    let slot = match is_superscript {
+        true => &mut superscript,
+        false => &mut subscript,
+    };
+    // A double superscript or subscript is an error in TeX as well.
+    if slot.replace(script).is_some() {
+        return None;
+    }
+
+    Some(MathNode::Scripts {
+        base,
+        superscript,
+        subscript,
+        limits,
+    })
+}
+
+/// Turns the variables in a node into upright text, e.g. for `\mathrm`.
+fn upright(node: MathNode) -> MathNode {
+    match node {
+        MathNode::Identifier(text) => MathNode::Text(text),
+        MathNode::Row(nodes) => MathNode::Row(nodes.into_iter().map(upright).collect()),
+        node => node,
+    }
+}
+
+fn map_letters(node: MathNode, map: fn(char) -> char) -> MathNode {
+    match node {
+        MathNode::Identifier(text) | MathNode::Text(text) => {
+            MathNode::Text(text.chars().map(map).collect())
+        }
+        MathNode::Row(nodes) => MathNode::Row(
+            nodes
+                .into_iter()
+                .map(|node| map_letters(node, map))
+                .collect(),
+        ),
+        node => node,
+    }
+}
+
+fn double_struck(char: char) -> char {
+    let offset = match char {
+        'C' => return 'ℂ',
+        'H' => return 'ℍ',
+        'N' => return 'ℕ',
+        'P' => return 'ℙ',
+        'Q' => return 'ℚ',
+        'R' => return 'ℝ',
+        'Z' => return 'ℤ',
+        'A'..='Z' => 0x1d538 + (char as u32 - 'A' as u32),
+        'a'..='z' => 0x1d552 + (char as u32 - 'a' as u32),
+        '0'..='9' => 0x1d7d8 + (char as u32 - '0' as u32),
+        _ => return char,
+    };
+
+    char::from_u32(offset).unwrap_or(char)
+}
+
+fn script(char: char) -> char {
+    let offset = match char {
+        'B' => return 'ℬ',
+        'E' => return 'ℰ',
+        'F' => return 'ℱ',
+        'H' => return 'ℋ',
+        'I' => return 'ℐ',
+        'L' => return 'ℒ',
+        'M' => return 'ℳ',
+        'R' => return 'ℛ',
+        'A'..='Z' => 0x1d49c + (char as u32 - 'A' as u32),
+        _ => return char,
+    };
+
+    char::from_u32(offset).unwrap_or(char)
+}
+
+fn superscript_char(char: char) -> Option<char> {
+    let superscript = match char {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '−' | '-' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'a' => 'ᵃ',
+        'b' => 'ᵇ',
+        'c' => 'ᶜ',
+        'd' => 'ᵈ',
+        'e' => 'ᵉ',
+        'f' => 'ᶠ',
+        'g' => 'ᵍ',
+        'h' => 'ʰ',
+        'i' => 'ⁱ',
+        'j' => 'ʲ',
+        'k' => 'ᵏ',
+        'l' => 'ˡ',
+        'm' => 'ᵐ',
+        'n' => 'ⁿ',
+        'o' => 'ᵒ',
+        'p' => 'ᵖ',
+        'r' => 'ʳ',
+        's' => 'ˢ',
+        't' => 'ᵗ',
+        'u' => 'ᵘ',
+        'v' => 'ᵛ',
+        'w' => 'ʷ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'z' => 'ᶻ',
+        'T' => 'ᵀ',
+        '′' | '∗' | '*' => char,
+        _ => return None,
+    };
+
+    Some(superscript)
+}
+
+fn subscript_char(char: char) -> Option<char> {
+    let subscript = match char {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '−' | '-' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        _ => return None,
+    };
+
+    Some(subscript)
+}
+
+/// Writes math as a single line of text, for formulas that sit inside a paragraph.
+/// Returns the text and the ranges of it that are variables.
+pub fn math_to_text(node: &MathNode) -> (String, Vec<Range<usize>>) {
+    let mut writer = LinearWriter::default();
+    writer.write(node);
+    (writer.text, writer.identifiers)
+}
+
+#[derive(Default)]
+struct LinearWriter {
+    text: String,
+    identifiers: Vec<Range<usize>>,
+    /// Whether an operator written now would be binary rather than a sign.
+    after_operand: bool,
+}
+
+impl LinearWriter {
+    fn write(&mut self, node: &MathNode) {
+        match node {
+            MathNode::Identifier(text) => {
+                let start = self.text.len();
+                self.text.push_str(text);
+                self.identifiers.push(start..self.text.len());
+                self.after_operand = true;
+            }
+            MathNode::Text(text) => {
+                self.text.push_str(text);
+                self.after_operand = !matches!(text.as_str(), "(" | "[" | "{" | ",");
+            }
+            MathNode::Operator(operator) => {
+                // Signs, e.g. of `-x`, stay next to what follows.
+                match self.after_operand {
+                    true => {
+                        self.text.push(' ');
+                        self.text.push_str(operator);
+                        self.text.push(' ');
+                    }
+                    false => self.text.push_str(operator),
+                }
+                self.after_operand = false;
+            }
+            MathNode::Space(width) => match width {
+                width if *width <= 0. => {}
+                width if *width < 0.25 => self.text.push('\u{2009}'),
+                width if *width < 1. => self.text.push(' '),
+                width => (0..*width as usize).for_each(|_| self.text.push('\u{2003}')),
+            },
+            MathNode::Row(nodes) => nodes.iter().for_each(|node| self.write(node)),
+            MathNode::Fraction {
+                numerator,
+                denominator,
+                line,
+            } => {
+                self.write_grouped(numerator);
+                self.text.push_str(if *line { "/" } else { " " });
+                self.write_grouped(denominator);
+                self.after_operand = true;
+            }
+            MathNode::Root { index, radicand } => {
+                let index = index.as_deref().map(linear_text);
+                match index.as_deref() {
+                    None | Some("2") => self.text.push('√'),
+                    Some("3") => self.text.push('∛'),
+                    Some("4") => self.text.push('∜'),
+                    Some(index) => {
+                        self.write_script(index, superscript_char, '^');
+                        self.text.push('√');
+                    }
+                }
+                self.write_grouped(radicand);
+                self.after_operand = true;
+            }
+            MathNode::Scripts {
+                base,
+                superscript,
+                subscript,
+                ..
+            } => {
+                self.write(base);
+                if let Some(subscript) = subscript {
+                    self.write_script(&linear_text(subscript), subscript_char, '_');
+                }
+                if let Some(superscript) = superscript {
+                    self.write_script(&linear_text(superscript), superscript_char, '^');
+                }
+                self.after_operand = true;
+            }
+            MathNode::Delimited { open, close, body } => {
+                self.text.push_str(open);
+                self.after_operand = false;
+                self.write(body);
+                self.text.push_str(close);
+                self.after_operand = true;
+            }
+            MathNode::Matrix(rows) => {
+                for (row_ix, row) in rows.iter().enumerate() {
+                    if row_ix > 0 {
+                        self.text.push_str("; ");
+                    }
+                    for (cell_ix, cell) in row.iter().enumerate() {
+                        if cell_ix > 0 {
+                            self.text.push(' ');
+                        }
+                        self.after_operand = false;
+                        self.write(cell);
+                    }
+                }
+                self.after_operand = true;
+            }
+            MathNode::Accent { accent, body } => {
+                self.write(body);
+                self.text.push(*accent);
+                self.after_operand = true;
+            }
+        }
+    }
+
+    /// Writes a node, in parentheses if it's made of several parts.
+    fn write_grouped(&mut self, node: &MathNode) {
+        let needs_parentheses = match node {
+            MathNode::Row(nodes) => nodes.len() > 1,
+            MathNode::Fraction { .. } => true,
+            _ => false,
+        };
+
+        if needs_parentheses {
+            self.text.push('(');
+        }
+        self.write(node);
+        if needs_parentheses {
+            self.text.push(')');
+        }
+    }
+
+    /// Writes a script with Unicode superscript or subscript characters if they all
+    /// exist, e.g. `x²`, and otherwise as `x^(2k)`.
+    fn write_script(&mut self, script: &str, map: fn(char) -> Option<char>, marker: char) {
+        if let Some(mapped) = script.chars().map(map).collect::<Option<String>>() {
+            self.text.push_str(&mapped);
+            return;
+        }
+
+        self.text.push(marker);
+        match script.chars().count() {
+            1 => self.text.push_str(script),
+            _ => {
+                self.text.push('(');
+                self.text.push_str(script);
+                self.text.push(')');
+            }
+        }
+    }
+}
+
+/// The text of a script or root index, without the spacing around its operators.
+fn linear_text(node: &MathNode) -> String {
+    math_to_text(node)
+        .0
+        .chars()
+        .filter(|char| !char.is_whitespace())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(tex: &str) -> String {
+        math_to_text(&parse_math(tex).expect(tex)).0
+    }
+
+    #[test]
+    fn test_math_to_text() {
+        assert_eq!(text(r"x^2 + y_1 = z^{n+1}"), "x² + y₁ = zⁿ⁺¹");
+        assert_eq!(text(r"\frac{a+b}{2}"), "(a + b)/2");
+        assert_eq!(text(r"\sqrt{\alpha} \leq -\infty"), "√α ≤ −∞");
+        assert_eq!(text(r"\sum_{i=1}^{n} i"), "∑ᵢ₌₁ⁿi");
+        assert_eq!(text(r"\mathbb{R}^n \to \mathbb{R}"), "ℝⁿ → ℝ");
+        assert_eq!(text(r"\sin\theta"), "sin\u{2009}θ");
+        assert_eq!(text(r"P(A \mid B)"), "P(A ∣ B)");
+        assert_eq!(text(r"e^{i\pi q}"), "e^(iπq)");
+    }
+
+    #[test]
+    fn test_nesting_is_limited() {
+        let nested = |depth: usize, open: &str, close: &str| {
+            format!("{}x{}", open.repeat(depth), close.repeat(depth))
+        };
+
+        assert!(parse_math(&nested(MAX_DEPTH - 1, "{", "}")).is_some());
+        assert_eq!(parse_math(&nested(100_000, "{", "}")), None);
+        assert_eq!(parse_math(&nested(100_000, r"\sqrt{", "}")), None);
+        assert_eq!(parse_math(&r"\sqrt".repeat(100_000)), None);
+    }
+
+    #[test]
+    fn test_parse_structure() {
+        assert_eq!(
+            parse_math(r"\frac{1}{x}"),
+            Some(MathNode::Fraction {
+                numerator: Box::new(MathNode::Text(String::from("1"))),
+                denominator: Box::new(MathNode::Identifier(String::from("x"))),
+                line: true,
+            })
+        );
+        assert_eq!(
+            parse_math(r"\begin{pmatrix} a & b \\ c & d \end{pmatrix}"),
+            Some(MathNode::Delimited {
+                open: String::from("("),
+                close: String::from(")"),
+                body: Box::new(MathNode::Matrix(vec![
+                    vec![
+                        MathNode::Identifier(String::from("a")),
+                        MathNode::Identifier(String::from("b"))
+                    ],
+                    vec![
+                        MathNode::Identifier(String::from("c")),
+                        MathNode::Identifier(String::from("d"))
+                    ],
+                ])),
+            })
+        );
+    }
+
+    #[test]
+    fn test_unsupported_math_fails() {
+        assert_eq!(parse_math(r"\frac{1}{"), None);
+        assert_eq!(parse_math(r"\unknowncommand x"), None);
+        assert_eq!(parse_math(r"x^2^3"), None);
+        assert_eq!(parse_math(r"\begin{pmatrix} a \end{bmatrix}"), None);
+        assert_eq!(parse_math(r"a}"), None);
+    }
+}
@@ -17,3 +17,7 @@ pub mod search;
 
 mod pixels;
 pub use pixels::*;
+
+pub mod markdown;
+pub mod math;
+pub mod syntax_highlighting;
@@ -0,0 +1,177 @@
+use std::{ops::Range, sync::LazyLock};
+
+use gpui::{FontStyle, FontWeight, HighlightStyle, Rgba};
+use syntect::{
+    highlighting::{self, HighlightIterator, HighlightState, Highlighter, Theme, ThemeSet},
+    parsing::{ParseState, ScopeStack, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+fn code_theme(dark: bool) -> &'static Theme {
+    let name = match dark {
+        true => "base16-ocean.dark",
+        false => "base16-ocean.light",
+    };
+
+    &THEME_SET.themes[name]
+}
+
+/// Highlights a code block in the language named by its info string, e.g. `rust` or `py`.
+/// When the code only grew since the last call, as it does while a reply streams in,
+/// highlighting picks up after the last complete line instead of starting over.
+#[derive(Default)]
+pub struct CodeHighlighter {
+    language: Option<String>,
+    dark: bool,
+    /// The code up to the end of the last complete line that was highlighted.
+    code: String,
+    /// Where the parser and highlighter were at the end of `code`.
+    state: Option<(ParseState, HighlightState)>,
+    highlights: Vec<(Range<usize>, HighlightStyle)>,
+}
+
+impl CodeHighlighter {
+    /// Returns no highlights when the language isn't known.
+    pub fn highlight(
+        &mut self,
+        code: &str,
+        language: Option<&str>,
+        dark: bool,
+    ) -> Vec<(Range<usize>, HighlightStyle)> {
+        let Some(syntax) = language.and_then(|language| SYNTAX_SET.find_syntax_by_token(language))
+        else {
+            return Vec::new();
+        };
+        let highlighter = Highlighter::new(code_theme(dark));
+
+        let is_continued = self.dark == dark
+            && self.language.as_deref() == language
+            && code.starts_with(&self.code);
+        let (mut parse_state, mut highlight_state) = match self.state.take() {
+            Some(state) if is_continued => state,
+            _ => {
+                self.language = language.map(ToString::to_string);
+                self.dark = dark;
+                self.code.clear();
+                self.highlights.clear();
+
+                (
+                    ParseState::new(syntax),
+                    HighlightState::new(&highlighter, ScopeStack::new()),
+                )
+            }
+        };
+
+        // The last line may still be incomplete, so it's highlighted again next time.
+        let mut incomplete_line = None;
+        for line in LinesWithEndings::from(&code[self.code.len()..]) {
+            if !line.ends_with('\n') {
+                incomplete_line = Some(line);
+                break;
+            }
+
+            let highlighted = highlight_line(
+                &highlighter,
+                &mut parse_state,
+                &mut highlight_state,
+                line,
+                self.code.len(),
+                &mut self.highlights,
+            );
+            if !highlighted {
+                // Starts over next time, as the state is lost.
+                return self.highlights.clone();
+            }
+
+            self.code.push_str(line);
+        }
+
+        let mut highlights = self.highlights.clone();
+        if let Some(line) = incomplete_line {
+            highlight_line(
+                &highlighter,
+                &mut parse_state.clone(),
+                &mut highlight_state.clone(),
+                line,
+                self.code.len(),
+                &mut highlights,
+            );
+        }
+
+        self.state = Some((parse_state, highlight_state));
+        highlights
+    }
+}
+
+/// Highlights a line starting at `offset` in the code. Returns `false` if it couldn't be parsed.
+fn highlight_line(
+    highlighter: &Highlighter,
+    parse_state: &mut ParseState,
+    highlight_state: &mut HighlightState,
+    line: &str,
+    mut offset: usize,
+    highlights: &mut Vec<(Range<usize>, HighlightStyle)>,
+) -> bool {
+    let Ok(ops) = parse_state.parse_line(line, &SYNTAX_SET) else {
+        return false;
+    };
+
+    for (style, token) in HighlightIterator::new(highlight_state, &ops, line, highlighter) {
+        let range = offset..offset + token.len();
+        offset = range.end;
+
+        if !token.trim().is_empty() {
+            highlights.push((range, highlight_style(style)));
+        }
+    }
+
+    true
+}
+
+fn highlight_style(style: highlighting::Style) -> HighlightStyle {
+    let color = style.foreground;
+
+    HighlightStyle {
+        color: Some(
+            Rgba {
+                r: color.r as f32 / 255.,
+                g: color.g as f32 / 255.,
+                b: color.b as f32 / 255.,
+                a: color.a as f32 / 255.,
+            }
+            .into(),
+        ),
+        font_weight: style
+            .font_style
+            .contains(highlighting::FontStyle::BOLD)
+            .then_some(FontWeight::BOLD),
+        font_style: style
+            .font_style
+            .contains(highlighting::FontStyle::ITALIC)
+            .then_some(FontStyle::Italic),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streamed_code_is_highlighted_like_the_whole_block() {
+        let code = "fn main() {\n    let s = \"a\\nb\";\n    /* multi\n    line */ s.len();\n}\n";
+
+        let mut streamed = CodeHighlighter::default();
+        let mut highlights = Vec::new();
+        for end in (1..=code.len()).filter(|end| code.is_char_boundary(*end)) {
+            highlights = streamed.highlight(&code[..end], Some("rust"), true);
+        }
+
+        let whole = CodeHighlighter::default().highlight(code, Some("rust"), true);
+        assert!(!whole.is_empty());
+        assert_eq!(highlights, whole);
+    }
+}
@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+
+use gpui::{
+    App, AsyncApp, ClipboardEntry, Div, ElementId, Entity, ExternalPaths, ObjectFit,
+    PathPromptOptions, Pixels, Window, div, img, prelude::*, px,
+};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant, Icon},
+    theme::{ThemeExt, ThemeLayerKind},
+};
+
+use crate::{
+    assets::AstrumIconKind,
+    managers::{Attachment, AttachmentContent, MAX_ATTACHMENT_SIZE},
+};
+
+/// How big the previews of attachments in the chat box are.
+const PENDING_THUMBNAIL_SIZE: Pixels = px(56.);
+
+/// How wide the chips of attached documents get before their names are cut off.
+const DOCUMENT_CHIP_MAX_WIDTH: Pixels = px(220.);
+
+/// The attachments added to the chat box, which are sent with the next message.
+pub fn use_pending_attachments(
+    base_id: &ElementId,
+    window: &mut Window,
+    cx: &mut App,
+) -> Entity<Vec<Attachment>> {
+    window.use_keyed_state(
+        base_id.with_suffix("state:attachments"),
+        cx,
+        |_window, _cx| Vec::new(),
+    )
+}
+
+/// A button next to the model picker that attaches files picked from disk.
+pub fn render_attach_button(base_id: &ElementId, attachments: &Entity<Vec<Attachment>>) -> Button {
+    let attachments = attachments.clone();
+
+    Button::new(base_id.with_suffix("attach_btn"))
+        .variant(ButtonVariant::SecondaryGhost)
+        .icon(AstrumIconKind::Attach)
+        .icon_size(px(14.))
+        .p(px(7.))
+        .on_click(move |_event, _window, cx| choose_files(attachments.clone(), cx))
+}
+
+fn choose_files(attachments: Entity<Vec<Attachment>>, cx: &mut App) {
+    let paths = cx.prompt_for_paths(PathPromptOptions {
+        files: true,
+        directories: false,
+        multiple: true,
+        prompt: Some("Attach".into()),
+    });
+
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        let Ok(Ok(Some(paths))) = paths.await else {
+            return;
+        };
+
+        attach_files(attachments, paths, cx).await;
+    })
+    .detach();
+}
+
+/// Attaches files dropped onto the chat box.
+pub fn attach_dropped_files(
+    attachments: &Entity<Vec<Attachment>>,
+    paths: &ExternalPaths,
+    cx: &mut App,
+) {
+    let attachments = attachments.clone();
+    let paths = paths.paths().to_vec();
+
+    cx.spawn(async move |cx: &mut AsyncApp| attach_files(attachments, paths, cx).await)
+        .detach();
+}
+
+/// Reads files in the background and attaches the ones that can be sent to a model.
+async fn attach_files(
+    attachments: Entity<Vec<Attachment>>,
+    paths: Vec<PathBuf>,
+    cx: &mut AsyncApp,
+) {
+    let read = cx
+        .background_executor()
+        .spawn(async move {
+            let mut read = Vec::new();
+
+            for path in paths {
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                let is_too_large = smol::fs::metadata(&path)
+                    .await
+                    .is_ok_and(|metadata| metadata.len() > MAX_ATTACHMENT_SIZE as u64);
+                if is_too_large {
+                    tracing::warn!("{} is too large to attach", path.display());
+                    continue;
+                }
+
+                match smol::fs::read(&path).await {
+                    Ok(bytes) => match Attachment::from_file(name, bytes) {
+                        Some(attachment) => read.push(attachment),
+                        None => tracing::warn!("{} can't be attached", path.display()),
+                    },
+                    Err(err) => tracing::error!("failed to read {}: {err}", path.display()),
+                }
+            }
+
+            read
+        })
+        .await;
+
+    let _ = attachments.update(cx, |attachments, cx| {
+        attachments.extend(read);
+        cx.notify();
+    });
+}
+
+/// Attaches the images on the clipboard. Returns whether there were any.
+pub fn paste_images(attachments: &Entity<Vec<Attachment>>, cx: &mut App) -> bool {
+    let Some(clipboard) = cx.read_from_clipboard() else {
+        return false;
+    };
+
+    let images = clipboard
+        .entries()
+        .iter()
+        .filter_map(|entry| match entry {
+            ClipboardEntry::Image(image) => {
+                Attachment::from_file("Pasted image", image.bytes.clone())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if images.is_empty() {
+        return false;
+    }
+
+    attachments.update(cx, |attachments, cx| {
+        attachments.extend(images);
+        cx.notify();
+    });
+
+    true
+}
+
+/// Previews of the attachments in the chat box, each with a button to remove it.
+pub fn render_pending_attachments(
+    base_id: &ElementId,
+    attachments: &Entity<Vec<Attachment>>,
+    cx: &App,
+) -> Option<Div> {
+    let pending = attachments.read(cx);
+    if pending.is_empty() {
+        return None;
+    }
+
+    let thumbnails = pending
+        .iter()
+        .enumerate()
+        .map(|(attachment_ix, attachment)| {
+            let attachments = attachments.clone();
+
+            div()
+                .relative()
+                .child(render_attachment_preview(
+                    attachment,
+                    PENDING_THUMBNAIL_SIZE,
+                    cx,
+                ))
+                .child(
+                    div().absolute().top(px(-6.)).right(px(-6.)).child(
+                        Button::new(
+                            base_id.with_suffix(format!("remove_attachment_btn_{attachment_ix}")),
+                        )
+                        .variant(ButtonVariant::Secondary)
+                        .icon(AstrumIconKind::Close)
+                        .icon_size(px(8.))
+                        .p(px(4.))
+                        .rounded(px(10.))
+                        .on_click(move |_event, _window, cx| {
+                            attachments.update(cx, |attachments, cx| {
+                                if attachment_ix < attachments.len() {
+                                    attachments.remove(attachment_ix);
+                                    cx.notify();
+                                }
+                            });
+                        }),
+                    ),
+                )
+        })
+        .collect::<Vec<_>>();
+
+    Some(
+        div()
+            .w_full()
+            .flex()
+            .flex_wrap()
+            .gap(px(8.))
+            .pt(px(6.))
+            .px(px(4.))
+            .children(thumbnails),
+    )
+}
+
+/// A preview of an attachment. Images are square thumbnails `size` across, while documents
+/// are chips `size` tall with their name and roughly how many tokens they add to the context.
+pub fn render_attachment_preview(attachment: &Attachment, size: Pixels, cx: &App) -> Div {
+    let preview = div()
+        .flex_none()
+        .rounded(px(8.))
+        .overflow_hidden()
+        .border(px(1.))
+        .border_color(ThemeLayerKind::Tertiary.next().resolve(cx));
+
+    match &attachment.content {
+        AttachmentContent::Image(image) => preview
+            .size(size)
+            .child(img(image.clone()).size_full().object_fit(ObjectFit::Cover)),
+        AttachmentContent::Document { .. } => {
+            let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
+            let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+            let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+            preview
+                .h(size)
+                .max_w(DOCUMENT_CHIP_MAX_WIDTH)
+                .flex()
+                .items_center()
+                .gap(px(8.))
+                .px(px(10.))
+                .bg(ThemeLayerKind::Tertiary.resolve(cx))
+                .child(
+                    Icon::new(AstrumIconKind::File)
+                        .size(px(16.))
+                        .color(secondary_text_color),
+                )
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .min_w_0()
+                        .child(
+                            div()
+                                .text_size(caption_size)
+                                .text_color(primary_text_color)
+                                .truncate()
+                                .child(attachment.name.clone()),
+                        )
+                        .when_some(attachment.estimated_tokens(), |this, tokens| {
+                            this.child(
+                                div()
+                                    .text_size(caption_size)
+                                    .text_color(secondary_text_color)
+                                    .child(format_token_estimate(tokens)),
+                            )
+                        }),
+                )
+        }
+    }
+}
+
+/// Writes a token estimate compactly, like "~1.2k tokens".
+fn format_token_estimate(tokens: usize) -> String {
+    match tokens {
+        0..1_000 => format!("~{tokens} tokens"),
+        1_000..100_000 => format!("~{:.1}k tokens", tokens as f32 / 1_000.),
+        _ => format!("~{}k tokens", tokens / 1_000),
+    }
+}
@@ -1,22 +1,85 @@
+use std::sync::Arc;
+
 use anyml::MessageRole;
 use gpui::{
-    App, Div, ElementId, Entity, IntoElement, Overflow, PointRefinement, SharedString, Stateful,
-    Window, div, prelude::*, px,
+    App, Div, ElementId, Entity, IntoElement, Overflow, Pixels, PointRefinement, ScrollHandle,
+    SharedString, Stateful, Window, div, prelude::*, px,
 };
 use gpui_tesserae::{
     ElementIdExt,
-    components::ChatBubble,
-    primitives::selectable_text::{SelectableText, SelectableTextState},
-    theme::ThemeExt,
+    components::{Button, ButtonVariant, ChatBubble, Input},
+    primitives::{
+        input::InputState,
+        selectable_text::{SelectableText, SelectableTextState},
+    },
+    theme::{ThemeExt, ThemeLayerKind},
+};
+use smol::lock::RwLock;
+
+use crate::{
+    RgbaExt,
+    assets::AstrumIconKind,
+    blocks::Markdown,
+    managers::{
+        Attachment, Chat, GenerationError, GenerationMetadata, Managers, ModelsManager, UniqueId,
+    },
+};
+
+use super::{
+    attachments::render_attachment_preview, continue_reply, edit_message, regenerate_reply,
+    retry_reply,
 };
 
-use crate::{RgbaExt, managers::Chat};
+/// How close to the top of the chat, in pixels, older messages start loading.
+const LOAD_OLDER_MESSAGES_THRESHOLD: Pixels = px(400.);
+
+/// How big the previews of images sent with a message are.
+const ATTACHMENT_THUMBNAIL_SIZE: Pixels = px(120.);
 
 pub fn render_existing_chat(
     base_id: &ElementId,
     current_chat: &Entity<Chat>,
-    cx: &App,
+    managers: &Arc<RwLock<Managers>>,
+    managers_guard: &Managers,
+    window: &mut Window,
+    cx: &mut App,
 ) -> Stateful<Div> {
+    let scroll_handle = window
+        .use_keyed_state(
+            base_id.with_suffix("state:scroll_handle"),
+            cx,
+            |_window, _cx| ScrollHandle::new(),
+        )
+        .read(cx)
+        .clone();
+
+    // Scroll to a message that was requested elsewhere, e.g. from a search result.
+    let scroll_target = managers_guard.chats.scroll_target.clone();
+    if let Some(target_id) = scroll_target.read(cx).clone() {
+        let target_ix = current_chat
+            .read(cx)
+            .active_path(cx)
+            .iter()
+            .position(|message| message.message_id() == &target_id);
+
+        if let Some(target_ix) = target_ix {
+            scroll_handle.scroll_to_item(target_ix);
+        }
+
+        scroll_target.update(cx, |scroll_target, _cx| *scroll_target = None);
+    }
+
+    // Checked on every render rather than on scroll events, so the chat pages in however
+    // it's scrolled, and when the loaded messages don't fill it.
+    if is_near_top(&scroll_handle) && current_chat.read(cx).has_older_messages(cx) {
+        let current_chat = current_chat.clone();
+        let scroll_handle = scroll_handle.clone();
+
+        window.on_next_frame(move |_window, cx| {
+            load_older_messages_near_top(&current_chat, &scroll_handle, cx)
+        });
+    }
+
     div()
         .id(base_id.with_suffix("existing_messages"))
         .w_full()
@@ -36,7 +99,49 @@ pub fn render_existing_chat(
             };
             this
         })
-        .children(render_messages(&current_chat.read(cx), cx))
+        .track_scroll(&scroll_handle)
+        .children(render_messages(current_chat, managers, managers_guard, cx))
+}
+
+fn is_near_top(scroll_handle: &ScrollHandle) -> bool {
+    scroll_handle.offset().y >= -LOAD_OLDER_MESSAGES_THRESHOLD
+}
+
+/// Pages in older messages while the top of the chat is close to being shown,
+/// keeping the messages that were visible in place.
+fn load_older_messages_near_top(
+    current_chat: &Entity<Chat>,
+    scroll_handle: &ScrollHandle,
+    cx: &mut App,
+) {
+    // The chat may have been scrolled, or paged in already, since this was scheduled.
+    if !is_near_top(scroll_handle) || !current_chat.read(cx).has_older_messages(cx) {
+        return;
+    }
+
+    let first_id = current_chat
+        .read(cx)
+        .active_path(cx)
+        .first()
+        .map(|message| message.message_id().clone());
+
+    if let Err(err) = current_chat.update(cx, |chat, cx| chat.load_older_messages(cx)) {
+        tracing::error!("failed to load older messages: {err}");
+        return;
+    }
+
+    // Messages are rendered in the order of the active path, so the message that was
+    // first keeps its place wherever the new ones put it.
+    let first_ix = first_id.and_then(|first_id| {
+        current_chat
+            .read(cx)
+            .active_path(cx)
+            .iter()
+            .position(|message| message.message_id() == &first_id)
+    });
+    if let Some(first_ix) = first_ix.filter(|first_ix| *first_ix > 0) {
+        scroll_handle.scroll_to_top_of_item(first_ix);
+    }
 }
 
 fn right_align(child: impl IntoElement) -> Div {
@@ -50,21 +155,169 @@ fn right_align(child: impl IntoElement) -> Div {
         .child(child)
 }
 
-fn render_messages<'a>(chat: &'a Chat, cx: &'a App) -> impl Iterator<Item = ChatMessage> + 'a {
-    chat.read_messages(cx).iter().map(|(id, message)| {
+fn render_messages<'a>(
+    current_chat: &'a Entity<Chat>,
+    managers: &'a Arc<RwLock<Managers>>,
+    managers_guard: &'a Managers,
+    cx: &'a App,
+) -> impl Iterator<Item = ChatMessage> + 'a {
+    let chat = current_chat.read(cx);
+    let is_streaming = chat.is_streaming(cx);
+    let active_leaf_id = chat.active_leaf_id(cx);
+
+    chat.active_path(cx).into_iter().map(move |message| {
+        let message_id = message.message_id().clone();
+        // Only the last reply of the branch can be continued or retried.
+        let is_leaf = active_leaf_id == Some(&message_id);
+        let can_continue = is_leaf
+            && message
+                .generation
+                .as_ref()
+                .is_some_and(|generation| generation.status.can_continue());
+        let error = message.generation.as_ref().and_then(|generation| {
+            generation
+                .error
+                .as_ref()
+                .map(|error| FailedRequest::new(error, generation, &managers_guard.models, cx))
+        });
+        let can_retry = is_leaf && error.is_some();
+        let branches = chat
+            .branches(cx, &message_id)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
         ChatMessage::new(
-            id.to_string(),
+            message_id.to_string(),
             message.message.role.clone(),
             &message.message.content,
         )
+        .attachments(message.attachments.clone())
+        .footer(
+            message
+                .generation
+                .as_ref()
+                .and_then(|generation| generation_footer(generation, &managers_guard.models, cx)),
+        )
+        .error(error)
+        .actions(MessageActions {
+            managers: managers.clone(),
+            chat: current_chat.clone(),
+            message_id,
+            branches,
+            can_continue,
+            can_retry,
+            disabled: is_streaming,
+        })
     })
 }
 
+/// Summarizes how a reply was generated, e.g. `Ollama · llama3.2 · 12 → 345 tokens · 8.1s`.
+fn generation_footer(
+    generation: &GenerationMetadata,
+    models: &ModelsManager,
+    cx: &App,
+) -> Option<SharedString> {
+    let provider_name = generation
+        .provider_id
+        .as_ref()
+        .and_then(|provider_id| models.providers.read(cx).get(provider_id))
+        .map(|provider| provider.name.read(cx).to_string());
+
+    let tokens = match (generation.prompt_tokens, generation.completion_tokens) {
+        (Some(prompt_tokens), Some(completion_tokens)) => {
+            Some(format!("{prompt_tokens} → {completion_tokens} tokens"))
+        }
+        (None, Some(completion_tokens)) => Some(format!("{completion_tokens} tokens")),
+        (Some(prompt_tokens), None) => Some(format!("{prompt_tokens} prompt tokens")),
+        (None, None) => None,
+    };
+
+    let parts = [
+        provider_name,
+        generation.model.clone(),
+        tokens,
+        generation
+            .time_to_first_token
+            .map(|ttft| format!("{:.2}s to first token", ttft.as_secs_f64())),
+        generation
+            .duration
+            .map(|duration| format!("{:.1}s", duration.as_secs_f64())),
+        generation
+            .tokens_per_second()
+            .map(|tokens_per_second| format!("{tokens_per_second:.1} tok/s")),
+        generation.finish_reason.clone(),
+        generation.status.label().map(ToString::to_string),
+    ];
+
+    let footer = parts.into_iter().flatten().collect::<Vec<_>>().join(" · ");
+
+    (!footer.is_empty()).then(|| footer.into())
+}
+
+/// A request that failed, shown in place of a reply.
+struct FailedRequest {
+    message: SharedString,
+    /// e.g. `HTTP 401 · OpenAI · gpt-4o`
+    details: Option<SharedString>,
+}
+
+impl FailedRequest {
+    fn new(
+        error: &GenerationError,
+        generation: &GenerationMetadata,
+        models: &ModelsManager,
+        cx: &App,
+    ) -> Self {
+        let provider_name = generation
+            .provider_id
+            .as_ref()
+            .and_then(|provider_id| models.providers.read(cx).get(provider_id))
+            .map(|provider| provider.name.read(cx).to_string());
+
+        let details = [
+            error
+                .status_code
+                .map(|status_code| format!("HTTP {status_code}")),
+            provider_name,
+            generation.model.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ");
+
+        Self {
+            message: error.message.clone().into(),
+            details: (!details.is_empty()).then(|| details.into()),
+        }
+    }
+}
+
+/// What is needed to edit, regenerate, continue or switch the branch of a message.
+struct MessageActions {
+    managers: Arc<RwLock<Managers>>,
+    chat: Entity<Chat>,
+    message_id: UniqueId,
+    /// The message and its siblings.
+    branches: Vec<UniqueId>,
+    /// Whether the reply was stopped or cut off, and can be continued.
+    can_continue: bool,
+    /// Whether the request for the reply failed, and can be sent again.
+    can_retry: bool,
+    /// Branches can't be changed while a reply is being streamed.
+    disabled: bool,
+}
+
 #[derive(IntoElement)]
 struct ChatMessage {
     id: ElementId,
     role: MessageRole,
     content: SharedString,
+    attachments: Vec<Attachment>,
+    footer: Option<SharedString>,
+    error: Option<FailedRequest>,
+    actions: Option<MessageActions>,
 }
 
 impl ChatMessage {
@@ -73,66 +326,430 @@ impl ChatMessage {
             id: id.into(),
             role,
             content: content.into(),
+            attachments: Vec::new(),
+            footer: None,
+            error: None,
+            actions: None,
         }
     }
+
+    fn attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    fn footer(mut self, footer: Option<SharedString>) -> Self {
+        self.footer = footer;
+        self
+    }
+
+    fn error(mut self, error: Option<FailedRequest>) -> Self {
+        self.error = error;
+        self
+    }
+
+    fn actions(mut self, actions: MessageActions) -> Self {
+        self.actions = Some(actions);
+        self
+    }
 }
 
 impl RenderOnce for ChatMessage {
     fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
-        let selectable_content_state =
-            window.use_keyed_state(self.id.with_suffix("state:content"), cx, |_window, cx| {
-                SelectableTextState::new(cx)
+        let editing_state =
+            window.use_keyed_state(self.id.with_suffix("state:editing"), cx, |_window, _cx| {
+                None::<Entity<InputState>>
             });
 
-        selectable_content_state.update(cx, |this, cx| {
-            if self.content == this.get_text() {
-                return;
-            };
-
-            this.text(self.content.clone());
-            cx.notify();
-        });
-
-        let font_family = cx.get_theme().layout.text.default_font.family[0].clone();
-
-        let text_size = cx.get_theme().layout.text.default_font.sizes.heading_sm;
-        let selection_color = cx
-            .get_theme()
-            .variants
-            .active(cx)
-            .colors
-            .accent
-            .primary
-            .alpha(0.3);
-
-        let selectable_content =
-            SelectableText::new(self.id.with_suffix("content"), selectable_content_state)
-                .selection_color(selection_color)
-                .selection_rounded(px(6.))
-                .selection_rounded_smoothing(1.)
-                .w_auto()
-                .max_w_full()
-                .word_wrap(true)
-                .font_family(font_family)
-                .text_size(text_size);
+        let edit_input_state = editing_state.read(cx).clone();
 
         match self.role {
             MessageRole::User => {
                 let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+                let selectable_content =
+                    render_selectable_content(&self.id, &self.content, window, cx);
+                let attachments = (!self.attachments.is_empty()).then(|| {
+                    div().flex().flex_wrap().justify_end().gap(px(6.)).children(
+                        self.attachments.iter().map(|attachment| {
+                            render_attachment_preview(attachment, ATTACHMENT_THUMBNAIL_SIZE, cx)
+                        }),
+                    )
+                });
 
-                right_align(
-                    ChatBubble::new("chat_bubble")
-                        .child(selectable_content.text_color(secondary_text_color)),
-                )
+                match (edit_input_state, self.actions) {
+                    (Some(edit_input_state), Some(actions)) => right_align(render_edit_input(
+                        &self.id,
+                        edit_input_state,
+                        &editing_state,
+                        &actions,
+                    )),
+                    (_, actions) => right_align(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .items_end()
+                            .gap(px(6.))
+                            .children(attachments)
+                            // Messages can be sent with only attachments.
+                            .when(!self.content.is_empty(), |this| {
+                                this.child(
+                                    ChatBubble::new("chat_bubble")
+                                        .child(selectable_content.text_color(secondary_text_color)),
+                                )
+                            }),
+                    )
+                    .gap(px(4.))
+                    .children(actions.map(|actions| {
+                        render_message_actions(
+                            &self.id,
+                            &self.role,
+                            &self.content,
+                            actions,
+                            &editing_state,
+                        )
+                    })),
+                }
                 .into_any_element()
             }
             _ => {
+                let font_family = cx.get_theme().layout.text.default_font.family[0].clone();
+                let text_size = cx.get_theme().layout.text.default_font.sizes.heading_sm;
                 let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
+                let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+                let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
 
-                selectable_content
-                    .text_color(primary_text_color)
+                div()
+                    .w_full()
+                    .flex()
+                    .flex_col()
+                    .gap(px(8.))
+                    .when(!self.content.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .w_full()
+                                .font_family(font_family)
+                                .text_color(primary_text_color)
+                                .child(
+                                    Markdown::new(
+                                        self.id.with_suffix("content"),
+                                        self.content.clone(),
+                                    )
+                                    .text_size(text_size),
+                                ),
+                        )
+                    })
+                    .when_some(self.error, |this, error| {
+                        this.child(render_failed_request(error, cx))
+                    })
+                    .when_some(self.footer, |this, footer| {
+                        this.child(
+                            div()
+                                .text_size(caption_size)
+                                .text_color(secondary_text_color)
+                                .child(footer),
+                        )
+                    })
+                    .children(self.actions.map(|actions| {
+                        render_message_actions(
+                            &self.id,
+                            &self.role,
+                            &self.content,
+                            actions,
+                            &editing_state,
+                        )
+                    }))
                     .into_any_element()
             }
         }
     }
 }
+
+fn render_selectable_content(
+    id: &ElementId,
+    content: &SharedString,
+    window: &mut Window,
+    cx: &mut App,
+) -> SelectableText {
+    let selectable_content_state =
+        window.use_keyed_state(id.with_suffix("state:content"), cx, |_window, cx| {
+            SelectableTextState::new(cx)
+        });
+
+    selectable_content_state.update(cx, |this, cx| {
+        if *content == this.get_text() {
+            return;
+        };
+
+        this.text(content.clone());
+        cx.notify();
+    });
+
+    let font_family = cx.get_theme().layout.text.default_font.family[0].clone();
+    let text_size = cx.get_theme().layout.text.default_font.sizes.heading_sm;
+    let selection_color = cx
+        .get_theme()
+        .variants
+        .active(cx)
+        .colors
+        .accent
+        .primary
+        .alpha(0.3);
+
+    SelectableText::new(id.with_suffix("content"), selectable_content_state)
+        .selection_color(selection_color)
+        .selection_rounded(px(6.))
+        .selection_rounded_smoothing(1.)
+        .w_auto()
+        .max_w_full()
+        .word_wrap(true)
+        .font_family(font_family)
+        .text_size(text_size)
+}
+
+/// Shows why a request failed, set apart from the text of the conversation.
+fn render_failed_request(error: FailedRequest, cx: &App) -> Div {
+    let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    div()
+        .w_full()
+        .flex()
+        .flex_col()
+        .gap(px(4.))
+        .p(px(12.))
+        .rounded(px(8.))
+        .bg(ThemeLayerKind::Tertiary.resolve(cx))
+        .border(px(1.))
+        .border_color(ThemeLayerKind::Tertiary.next().resolve(cx))
+        .child(
+            div()
+                .text_color(primary_text_color)
+                .child("The request failed"),
+        )
+        .child(
+            div()
+                .text_size(caption_size)
+                .text_color(secondary_text_color)
+                .child(error.message),
+        )
+        .when_some(error.details, |this, details| {
+            this.child(
+                div()
+                    .text_size(caption_size)
+                    .text_color(secondary_text_color)
+                    .child(details),
+            )
+        })
+}
+
+fn render_message_actions(
+    id: &ElementId,
+    role: &MessageRole,
+    content: &SharedString,
+    actions: MessageActions,
+    editing_state: &Entity<Option<Entity<InputState>>>,
+) -> Div {
+    let branch_controls = (actions.branches.len() > 1).then(|| {
+        render_branch_controls(
+            id,
+            &actions.chat,
+            &actions.message_id,
+            &actions.branches,
+            actions.disabled,
+        )
+    });
+
+    let action_button = match role {
+        MessageRole::User => {
+            let editing_state = editing_state.clone();
+            let content = content.clone();
+
+            message_action_button(id.with_suffix("edit_btn"), AstrumIconKind::Edit)
+                .disabled(actions.disabled)
+                .on_click(move |_event, _window, cx| {
+                    let content = content.clone();
+                    let edit_input_state = cx.new(|cx| InputState::new(cx).initial_value(content));
+
+                    editing_state.update(cx, |editing_state, cx| {
+                        *editing_state = Some(edit_input_state);
+                        cx.notify();
+                    });
+                })
+        }
+        _ => {
+            let managers = actions.managers.clone();
+            let chat = actions.chat.clone();
+            let message_id = actions.message_id.clone();
+
+            message_action_button(id.with_suffix("regenerate_btn"), AstrumIconKind::Regenerate)
+                .disabled(actions.disabled)
+                .on_click(move |_event, _window, cx| {
+                    regenerate_reply(managers.clone(), chat.clone(), &message_id, cx);
+                })
+        }
+    };
+
+    let retry_button = actions.can_retry.then(|| {
+        let managers = actions.managers.clone();
+        let chat = actions.chat.clone();
+        let message_id = actions.message_id.clone();
+
+        Button::new(id.with_suffix("retry_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .text("Retry")
+            .p(px(6.))
+            .rounded(px(6.))
+            .disabled(actions.disabled)
+            .on_click(move |_event, _window, cx| {
+                retry_reply(managers.clone(), chat.clone(), &message_id, cx);
+            })
+    });
+
+    let continue_button = actions.can_continue.then(|| {
+        let managers = actions.managers.clone();
+        let chat = actions.chat.clone();
+        let message_id = actions.message_id.clone();
+
+        Button::new(id.with_suffix("continue_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .text("Continue")
+            .p(px(6.))
+            .rounded(px(6.))
+            .disabled(actions.disabled)
+            .on_click(move |_event, _window, cx| {
+                continue_reply(managers.clone(), chat.clone(), &message_id, cx);
+            })
+    });
+
+    div()
+        .flex()
+        .items_center()
+        .gap(px(2.))
+        .children(branch_controls)
+        .child(action_button)
+        .children(continue_button)
+        .children(retry_button)
+}
+
+/// Renders the "< 2/3 >" controls used to switch between the branches of a message.
+fn render_branch_controls(
+    id: &ElementId,
+    chat: &Entity<Chat>,
+    message_id: &UniqueId,
+    branches: &[UniqueId],
+    disabled: bool,
+) -> Div {
+    let branch_idx = branches
+        .iter()
+        .position(|branch_id| branch_id == message_id)
+        .unwrap_or_default();
+
+    let switch_button = |suffix: &str, icon: AstrumIconKind, target: Option<&UniqueId>| {
+        let chat = chat.clone();
+        let target = target.cloned();
+
+        message_action_button(id.with_suffix(suffix), icon)
+            .disabled(disabled || target.is_none())
+            .on_click(move |_event, _window, cx| {
+                let Some(target) = &target else { return };
+                chat.update(cx, |chat, cx| {
+                    let _ = chat.switch_branch(cx, target);
+                });
+            })
+    };
+
+    div()
+        .flex()
+        .items_center()
+        .gap(px(2.))
+        .child(switch_button(
+            "previous_branch_btn",
+            AstrumIconKind::ChevronLeft,
+            branch_idx.checked_sub(1).and_then(|idx| branches.get(idx)),
+        ))
+        .child(format!("{}/{}", branch_idx + 1, branches.len()))
+        .child(switch_button(
+            "next_branch_btn",
+            AstrumIconKind::ChevronRight,
+            branches.get(branch_idx + 1),
+        ))
+}
+
+fn render_edit_input(
+    id: &ElementId,
+    edit_input_state: Entity<InputState>,
+    editing_state: &Entity<Option<Entity<InputState>>>,
+    actions: &MessageActions,
+) -> Div {
+    let submit = {
+        let edit_input_state = edit_input_state.clone();
+        let editing_state = editing_state.clone();
+        let managers = actions.managers.clone();
+        let chat = actions.chat.clone();
+        let message_id = actions.message_id.clone();
+
+        move |cx: &mut App| {
+            let contents = edit_input_state.read(cx).value().clone();
+            if contents.trim().is_empty() {
+                return;
+            }
+
+            close_editing(&editing_state, cx);
+            edit_message(managers.clone(), chat.clone(), &message_id, contents, cx);
+        }
+    };
+
+    let editing_state = editing_state.clone();
+
+    div()
+        .w_full()
+        .flex()
+        .flex_col()
+        .items_end()
+        .gap(px(7.))
+        .child(
+            Input::new(id.with_suffix("edit_input"), edit_input_state)
+                .w_full()
+                .line_clamp(12)
+                .word_wrap(true)
+                .submit_disabled(actions.disabled)
+                .on_submit({
+                    let submit = submit.clone();
+                    move |_window, cx| submit(cx)
+                }),
+        )
+        .child(
+            div()
+                .flex()
+                .gap(px(7.))
+                .child(
+                    Button::new(id.with_suffix("cancel_edit_btn"))
+                        .text("Cancel")
+                        .variant(ButtonVariant::SecondaryGhost)
+                        .on_click(move |_event, _window, cx| close_editing(&editing_state, cx)),
+                )
+                .child(
+                    Button::new(id.with_suffix("submit_edit_btn"))
+                        .text("Send")
+                        .disabled(actions.disabled)
+                        .on_click(move |_event, _window, cx| submit(cx)),
+                ),
+        )
+}
+
+fn close_editing(editing_state: &Entity<Option<Entity<InputState>>>, cx: &mut App) {
+    editing_state.update(cx, |editing_state, cx| {
+        *editing_state = None;
+        cx.notify();
+    });
+}
+
+fn message_action_button(id: impl Into<ElementId>, icon: AstrumIconKind) -> Button {
+    Button::new(id)
+        .variant(ButtonVariant::SecondaryGhost)
+        .icon(icon)
+        .icon_size(px(12.))
+        .p(px(6.))
+        .rounded(px(6.))
+}
@@ -1,10 +1,11 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use anyml::{ChatOptions, MessageRole, models::Message};
-use futures::future::{AbortHandle, Abortable};
+use futures::future::{AbortHandle, Abortable, Aborted};
 use gpui::{
-    App, AppContext, AsyncApp, ElementId, InteractiveElement, IntoElement, RenderOnce,
-    SharedString, Window, deferred, div, prelude::*, px, radians, relative,
+    App, AppContext, AsyncApp, Div, ElementId, Entity, ExternalPaths, InteractiveElement,
+    IntoElement, RenderOnce, SharedString, Window, deferred, div, prelude::*, px, radians,
+    relative,
 };
 use gpui_squircle::{SquircleStyled, squircle};
 use gpui_tesserae::{
@@ -17,14 +18,35 @@ use gpui_tesserae::{
 use serde_json::value::RawValue;
 use smol::lock::RwLock;
 
-use crate::{Managers, assets::AstrumIconKind, blocks::ModelPicker, managers::ValuesOnly};
+use crate::{
+    Managers,
+    anyhttp_gpui::RequestObserver,
+    assets::AstrumIconKind,
+    blocks::ModelPicker,
+    managers::{
+        Attachment, Chat, GenerationMetadata, GenerationParametersObserver, MessageStatus,
+        MessageWithMetadata, UniqueId, UsageTracker, message_to_json,
+    },
+};
+
+mod attachments;
+use attachments::{
+    attach_dropped_files, paste_images, render_attach_button, render_pending_attachments,
+    use_pending_attachments,
+};
 
 mod existing_chat;
 use existing_chat::render_existing_chat;
 
+mod parameters_popover;
+use parameters_popover::render_parameters_button;
+
 mod prompt_new_chat;
 use prompt_new_chat::render_prompt_new_chat;
 
+mod system_prompt;
+use system_prompt::render_system_prompt_header;
+
 #[derive(IntoElement)]
 pub struct ChatArea {
     id: ElementId,
@@ -42,6 +64,8 @@ impl ChatArea {
 
 impl RenderOnce for ChatArea {
     fn render(self, window: &mut gpui::Window, cx: &mut App) -> impl IntoElement {
+        sync_model_with_current_chat(&self.id, &self.managers, window, cx);
+
         let secondary_bg_color = cx
             .get_theme()
             .variants
@@ -81,10 +105,35 @@ impl RenderOnce for ChatArea {
                         let current_chat = managers.chats.get_current_chat(cx);
 
                         match current_chat {
-                            Ok(Some(current_chat)) => {
-                                this.child(render_existing_chat(&self.id, &current_chat, cx))
-                            }
-                            _ => this.child(render_prompt_new_chat(window, cx)),
+                            Ok(Some(current_chat)) => this.child(
+                                div()
+                                    .w_full()
+                                    .min_h_0()
+                                    .flex()
+                                    .flex_col()
+                                    .child(render_system_prompt_header(
+                                        &self.id,
+                                        &current_chat,
+                                        &managers,
+                                        window,
+                                        cx,
+                                    ))
+                                    .child(render_existing_chat(
+                                        &self.id,
+                                        &current_chat,
+                                        &self.managers,
+                                        &managers,
+                                        window,
+                                        cx,
+                                    )),
+                            ),
+                            _ => this.child(render_prompt_new_chat(
+                                &self.id,
+                                &self.managers,
+                                &managers,
+                                window,
+                                cx,
+                            )),
                         }
                     })
                     .child(
@@ -98,11 +147,49 @@ impl RenderOnce for ChatArea {
     }
 }
 
-fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
+/// Shows the model of the open chat in the picker whenever another chat is opened,
+/// or the default model once no chat is open.
+fn sync_model_with_current_chat(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    window: &mut Window,
+    cx: &mut App,
+) {
+    let synced_chat_id = window.use_keyed_state(
+        base_id.with_suffix("state:model_chat_id"),
+        cx,
+        |_window, _cx| None::<Option<UniqueId>>,
+    );
+
+    let managers = managers.read_blocking();
+    let current_chat_id = managers.chats.get_current_chat_id().read(cx).clone();
+
+    if synced_chat_id.read(cx).as_ref() == Some(&current_chat_id) {
+        return;
+    }
+
+    synced_chat_id.update(cx, |synced_chat_id, _cx| {
+        *synced_chat_id = Some(current_chat_id);
+    });
+
+    let chat_model = match managers.chats.get_current_chat(cx) {
+        Ok(Some(current_chat)) => Some(current_chat.read(cx).model.read(cx).clone()),
+        _ => None,
+    };
+
+    match chat_model {
+        Some(Some(chat_model)) => managers.models.show_model(cx, Some(&chat_model)),
+        // Chats that haven't picked a model yet use the default one.
+        _ => managers.models.show_default_model(cx),
+    }
+}
+
+fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Div {
     let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
     let text_heading_sm_size = cx.get_theme().layout.text.default_font.sizes.heading_sm;
 
     let chat_box_input_state = window.use_state(cx, |_window, cx| InputState::new(cx));
+    let attachments = use_pending_attachments(&elem.id, window, cx);
 
     // Get the models cache from the manager
     let models_cache = elem.managers.read_blocking().models.models_cache.clone();
@@ -129,6 +216,9 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
 
     let chat_box_left_items = div()
         .max_w_full()
+        .flex()
+        .items_center()
+        .gap(px(4.))
         .child(deferred(
             Toggle::new(elem.id.with_suffix("switch_llm_btn"))
                 .w_auto()
@@ -144,6 +234,13 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
                             if managers.models.providers.read(cx).is_empty() {
                                 return "No provider exists".to_string();
                             }
+                            // The chat was using a provider that has since been deleted.
+                            if managers.models.is_current_provider_missing(cx) {
+                                return match managers.models.get_current_model(cx) {
+                                    Some(model) => format!("{model} (provider deleted)"),
+                                    None => "Provider deleted".to_string(),
+                                };
+                            }
                             let provider_name =
                                 managers.models.current_model.provider_name.read(cx).clone();
                             let model = managers.models.get_current_model(cx).cloned();
@@ -171,6 +268,13 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
                     models_state_for_toggle.toggle_menu(cx);
                 }),
         ))
+        .child(render_attach_button(&elem.id, &attachments))
+        .child(render_parameters_button(
+            &elem.id,
+            &elem.managers,
+            window,
+            cx,
+        ))
         .child(
             div()
                 .w(px(250.))
@@ -189,12 +293,20 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
                 ),
         );
 
-    // Check if currently streaming to determine button behavior
-    let is_streaming = *elem.managers.read_blocking().chats.is_streaming.read(cx);
+    // Only the visible chat's reply can be stopped from here.
+    let is_streaming = current_chat_is_streaming(&elem.managers, cx);
     let has_input_text = !chat_box_input_state.read(cx).value().is_empty();
+    let has_attachments = !attachments.read(cx).is_empty();
+
+    let is_provider_missing = elem
+        .managers
+        .read_blocking()
+        .models
+        .is_current_provider_missing(cx);
 
-    let submit_disabled =
-        picker.has_no_providers || picker.has_no_model || (!is_streaming && !has_input_text);
+    let submit_disabled = picker.has_no_providers
+        || picker.has_no_model
+        || (!is_streaming && (is_provider_missing || !(has_input_text || has_attachments)));
 
     let chat_box_right_items = div()
         .flex()
@@ -214,28 +326,21 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
                 .disabled(submit_disabled)
                 .map(|this| {
                     let chat_box_input_state = chat_box_input_state.clone();
+                    let attachments = attachments.clone();
                     let managers = elem.managers.clone();
 
                     this.on_click(move |_event, _window, cx| {
-                        let managers_guard = managers.read_blocking();
-                        let is_streaming = *managers_guard.chats.is_streaming.read(cx);
-
-                        if is_streaming {
-                            // Cancel the current streaming response
-                            managers_guard.chats.cancel_streaming(cx);
-                        } else {
-                            // Send a new message
-                            let contents =
-                                chat_box_input_state.update(cx, |this, _cx| this.clear());
-                            let Some(contents) = contents else { return };
-                            drop(managers_guard);
-                            send_message(managers.clone(), contents, cx);
+                        if cancel_current_chat_streaming(&managers, cx) {
+                            return;
                         }
+
+                        // Send a new message
+                        submit_chat_box(&managers, &chat_box_input_state, &attachments, cx);
                     })
                 }),
         );
 
-    Input::new(
+    let input = Input::new(
         elem.id.with_suffix("chat_box"),
         chat_box_input_state.clone(),
     )
@@ -244,16 +349,14 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
     .word_wrap(true)
     .on_submit({
         let chat_box_input_state = chat_box_input_state.clone();
+        let attachments = attachments.clone();
         let managers = elem.managers.clone();
 
         move |window, cx| {
-            let managers_guard = managers.read_blocking();
-            let is_streaming = *managers_guard.chats.is_streaming.read(cx);
+            // Sending while a reply streams stops it first.
+            cancel_current_chat_streaming(&managers, cx);
 
-            if is_streaming {
-                // Cancel the current streaming response
-                managers_guard.chats.cancel_streaming(cx);
-            }
+            let managers_guard = managers.read_blocking();
 
             // Don't send if no provider or model is selected
             if managers_guard.models.get_current_provider(cx).is_none()
@@ -262,14 +365,14 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
                 return;
             }
 
-            let contents = chat_box_input_state.update(cx, |this, _cx| this.clear());
-
-            let Some(contents) = contents else { return };
             drop(managers_guard);
-            send_message(managers.clone(), contents, cx);
+            submit_chat_box(&managers, &chat_box_input_state, &attachments, cx);
         }
     })
-    .placeholder("Type your message here...")
+    .placeholder(match is_provider_missing {
+        true => "This chat's provider was deleted. Pick another model to continue...",
+        false => "Type your message here...",
+    })
     .rounded(cx.get_theme().layout.corner_radii.lg)
     .gap(px(4.))
     .p(px(14.))
@@ -282,30 +385,123 @@ fn chat_box(elem: &ChatArea, window: &mut Window, cx: &mut App) -> Input {
         div()
             .max_w_full()
             .flex()
-            .min_h_auto()
-            .justify_between()
-            .flex_wrap()
+            .flex_col()
             .gap(px(7.))
-            .child(chat_box_left_items)
-            .child(chat_box_right_items),
-    )
+            .children(render_pending_attachments(&elem.id, &attachments, cx))
+            .child(
+                div()
+                    .max_w_full()
+                    .flex()
+                    .min_h_auto()
+                    .justify_between()
+                    .flex_wrap()
+                    .gap(px(7.))
+                    .child(chat_box_left_items)
+                    .child(chat_box_right_items),
+            ),
+    );
+
+    // Images can also be dropped onto the chat box or pasted into it.
+    div()
+        .w_full()
+        .drag_over::<ExternalPaths>(|style, _paths, _window, _cx| style.opacity(0.7))
+        .on_drop({
+            let attachments = attachments.clone();
+            move |paths: &ExternalPaths, _window, cx| attach_dropped_files(&attachments, paths, cx)
+        })
+        .capture_key_down(move |event, _window, cx| {
+            let keystroke = &event.keystroke;
+            if keystroke.modifiers.secondary()
+                && keystroke.key == "v"
+                && paste_images(&attachments, cx)
+            {
+                cx.stop_propagation();
+            }
+        })
+        .child(input)
+}
+
+/// Sends the text and attachments in the chat box as a new message, clearing it.
+fn submit_chat_box(
+    managers: &Arc<RwLock<Managers>>,
+    chat_box_input_state: &Entity<InputState>,
+    attachments: &Entity<Vec<Attachment>>,
+    cx: &mut App,
+) {
+    let contents = chat_box_input_state.update(cx, |this, _cx| this.clear());
+    let attachments = attachments.update(cx, |attachments, cx| {
+        cx.notify();
+        std::mem::take(attachments)
+    });
+
+    if contents.is_none() && attachments.is_empty() {
+        return;
+    }
+
+    send_message(
+        managers.clone(),
+        contents.unwrap_or_default(),
+        attachments,
+        cx,
+    );
+}
+
+fn current_chat_is_streaming(managers: &Arc<RwLock<Managers>>, cx: &mut App) -> bool {
+    match managers.read_blocking().chats.get_current_chat(cx) {
+        Ok(Some(current_chat)) => current_chat.read(cx).is_streaming(cx),
+        _ => false,
+    }
+}
+
+/// Stops the reply streaming into the visible chat. Returns whether there was one.
+fn cancel_current_chat_streaming(managers: &Arc<RwLock<Managers>>, cx: &mut App) -> bool {
+    let Ok(Some(current_chat)) = managers.read_blocking().chats.get_current_chat(cx) else {
+        return false;
+    };
+
+    if !current_chat.read(cx).is_streaming(cx) {
+        return false;
+    }
+
+    current_chat.update(cx, |current_chat, cx| current_chat.cancel_streaming(cx));
+    true
 }
 
 fn send_message(
     managers: Arc<RwLock<Managers>>,
     contents: SharedString,
+    attachments: Vec<Attachment>,
     cx: &mut App,
 ) -> Option<()> {
     let managers_guard = managers.read_blocking();
-    let current_provider = managers_guard.models.get_current_provider(cx).cloned()?;
-    let current_model = managers_guard.models.get_current_model(cx).cloned()?;
+
+    if !has_current_model(&managers_guard, cx) {
+        return None;
+    }
 
     let (current_chat, is_new_chat) = match managers_guard.chats.get_current_chat(cx) {
         Ok(Some(current_chat)) => (current_chat, false),
-        Ok(None) => match managers_guard.chats.create_chat(cx) {
-            Ok(new_chat) => (new_chat, true),
-            _ => return None,
-        },
+        Ok(None) => {
+            let assistant = managers_guard
+                .assistants
+                .get_new_chat_assistant(cx)
+                .map(|(assistant_id, assistant)| (assistant_id.clone(), assistant.clone()));
+
+            let new_chat = managers_guard.chats.create_chat(
+                cx,
+                assistant
+                    .as_ref()
+                    .map(|(assistant_id, assistant)| (assistant_id, assistant)),
+            );
+
+            match new_chat {
+                Ok(new_chat) => {
+                    managers_guard.assistants.set_new_chat_assistant(cx, None);
+                    (new_chat, true)
+                }
+                _ => return None,
+            }
+        }
         Err(_) => return None,
     };
 
@@ -364,43 +560,279 @@ fn send_message(
         }
     }
 
+    current_chat
+        .update(cx, |current_chat, cx| {
+            current_chat.push_message(
+                cx,
+                &current_chat.chat_id.clone(),
+                contents,
+                MessageRole::User,
+                attachments,
+            )
+        })
+        .ok()?;
+
+    drop(managers_guard);
+
+    generate_reply(managers, current_chat, cx)
+}
+
+/// Adds an edited copy of a user message as a new branch next to it, then replies to it.
+/// The copy keeps the attachments of the original.
+fn edit_message(
+    managers: Arc<RwLock<Managers>>,
+    chat: Entity<Chat>,
+    message_id: &UniqueId,
+    contents: SharedString,
+    cx: &mut App,
+) -> Option<()> {
+    if !has_current_model(&managers.read_blocking(), cx) {
+        return None;
+    }
+
+    chat.update(cx, |chat, cx| {
+        let message = chat.read_messages(cx).get(message_id)?;
+        let parent_id = message.parent_id.clone();
+        let attachments = message.attachments.clone();
+
+        chat.rewind_to(cx, parent_id.as_ref()).ok()?;
+        chat.push_message(
+            cx,
+            &chat.chat_id.clone(),
+            contents,
+            MessageRole::User,
+            attachments,
+        )
+        .ok()
+    })?;
+
+    generate_reply(managers, chat, cx)
+}
+
+/// Generates a new reply as a branch next to an existing assistant message.
+fn regenerate_reply(
+    managers: Arc<RwLock<Managers>>,
+    chat: Entity<Chat>,
+    message_id: &UniqueId,
+    cx: &mut App,
+) -> Option<()> {
+    if !has_current_model(&managers.read_blocking(), cx) {
+        return None;
+    }
+
+    chat.update(cx, |chat, cx| {
+        let parent_id = chat.read_messages(cx).get(message_id)?.parent_id.clone();
+        chat.rewind_to(cx, parent_id.as_ref()).ok()
+    })?;
+
+    generate_reply(managers, chat, cx)
+}
+
+/// Sends the request for a failed reply again, generating it in place of the old one.
+fn retry_reply(
+    managers: Arc<RwLock<Managers>>,
+    chat: Entity<Chat>,
+    message_id: &UniqueId,
+    cx: &mut App,
+) -> Option<()> {
+    if !has_current_model(&managers.read_blocking(), cx) {
+        return None;
+    }
+
+    chat.update(cx, |chat, cx| {
+        chat.load_all_messages(cx).ok()?;
+        chat.clear_message_content(cx, message_id).ok()
+    })?;
+
+    stream_reply(managers, chat, message_id.clone(), None, cx)
+}
+
+/// A reply that failed before the model wrote anything, which isn't part of the conversation.
+fn is_failed_reply(message: &MessageWithMetadata) -> bool {
+    message.generation.as_ref().is_some_and(|generation| {
+        generation.status == MessageStatus::Errored && message.message.content.trim().is_empty()
+    })
+}
+
+fn has_current_model(managers: &Managers, cx: &App) -> bool {
+    managers.models.get_current_provider(cx).is_some()
+        && managers.models.get_current_model(cx).is_some()
+}
+
+/// Streams a reply to the active branch of a chat from the current model.
+fn generate_reply(
+    managers: Arc<RwLock<Managers>>,
+    current_chat: Entity<Chat>,
+    cx: &mut App,
+) -> Option<()> {
     let msg_id = current_chat
         .update(cx, |current_chat, cx| {
-            current_chat
-                .push_message(
-                    cx,
-                    &current_chat.chat_id.clone(),
-                    contents,
-                    MessageRole::User,
-                )
-                .unwrap();
+            // The whole active branch is sent to the model, not just the loaded part.
+            current_chat.load_all_messages(cx)?;
+
             current_chat.push_message(
                 cx,
                 &current_chat.chat_id.clone(),
                 "",
                 MessageRole::Assistant,
+                Vec::new(),
             )
         })
         .ok()?;
 
-    // Set streaming state to true and create abort handle
-    managers_guard.chats.set_streaming(cx, true);
+    stream_reply(managers, current_chat, msg_id, None, cx)
+}
+
+/// Asks the current model to carry on from where a stopped or cut off reply ended,
+/// appending to the same message.
+fn continue_reply(
+    managers: Arc<RwLock<Managers>>,
+    chat: Entity<Chat>,
+    message_id: &UniqueId,
+    cx: &mut App,
+) -> Option<()> {
+    if !has_current_model(&managers.read_blocking(), cx) {
+        return None;
+    }
+
+    let previous = chat.update(cx, |chat, cx| {
+        chat.load_all_messages(cx).ok()?;
+
+        chat.read_messages(cx)
+            .get(message_id)?
+            .generation
+            .clone()
+            .filter(|generation| generation.status.can_continue())
+    })?;
+
+    stream_reply(managers, chat, message_id.clone(), Some(previous), cx)
+}
+
+/// Streams the current model's output into an assistant message. When `previous` is set
+/// the message already holds a partial reply, which the model is asked to continue.
+fn stream_reply(
+    managers: Arc<RwLock<Managers>>,
+    current_chat: Entity<Chat>,
+    msg_id: UniqueId,
+    previous: Option<GenerationMetadata>,
+    cx: &mut App,
+) -> Option<()> {
+    let managers_guard = managers.read_blocking();
+    let current_provider_id = managers_guard
+        .models
+        .current_model
+        .provider_id
+        .read(cx)
+        .clone()?;
+    let current_provider = managers_guard.models.get_current_provider(cx).cloned()?;
+    let current_model = managers_guard.models.get_current_model(cx).cloned()?;
+
+    // Read when the reply starts, so editing the prompt never changes earlier replies.
+    let system_prompt = current_chat
+        .read(cx)
+        .system_prompt
+        .read(cx)
+        .clone()
+        .or_else(|| {
+            managers_guard
+                .settings
+                .default_system_prompt
+                .read(cx)
+                .clone()
+        })
+        .map(|system_prompt| Message {
+            content: system_prompt,
+            role: MessageRole::System,
+        });
+
+    let model_choice = managers_guard.models.current_model_choice(cx);
+
+    // The model's defaults, then the assistant's parameters, then the chat's overrides.
+    let assistant_parameters = current_chat
+        .read(cx)
+        .assistant_id
+        .as_ref()
+        .and_then(|assistant_id| managers_guard.assistants.get(cx, assistant_id))
+        .map(|assistant| assistant.parameters.clone())
+        .unwrap_or_default();
+    let parameters = managers_guard
+        .models
+        .get_model_parameters(cx, &current_provider_id, &current_model)
+        .merge(&assistant_parameters)
+        .merge(current_chat.read(cx).parameters.read(cx));
+
+    let is_continuing = previous.is_some();
+    let previous = previous.unwrap_or_default();
+
+    current_chat
+        .update(cx, |current_chat, cx| {
+            current_chat.set_generation_metadata(
+                cx,
+                &msg_id,
+                GenerationMetadata {
+                    prompt_tokens: previous.prompt_tokens,
+                    completion_tokens: previous.completion_tokens,
+                    time_to_first_token: previous.time_to_first_token,
+                    duration: previous.duration,
+                    ..GenerationMetadata::new(current_provider_id.clone(), current_model.clone())
+                },
+            )?;
+            current_chat.set_model(cx, model_choice)
+        })
+        .ok()?;
+
+    // Each chat streams on its own, so replies in other chats keep going.
     let (abort_handle, abort_registration) = AbortHandle::new_pair();
-    managers_guard
-        .chats
-        .set_abort_handle(cx, Some(abort_handle));
+    current_chat.update(cx, |current_chat, cx| {
+        current_chat.start_streaming(cx, msg_id.clone(), abort_handle)
+    });
 
     // Drop the read guard before spawning the async task
     drop(managers_guard);
 
-    let managers_for_cleanup = managers.clone();
-
     cx.spawn(async move |cx: &mut AsyncApp| {
+        let provider_kind = current_provider.kind;
+        let usage_tracker = Arc::new(UsageTracker::new(provider_kind));
+        let parameters_observer: Arc<dyn RequestObserver> =
+            Arc::new(GenerationParametersObserver::new(provider_kind, parameters));
+        let started_at = Instant::now();
+        let mut first_token_at = None;
+
         let streaming_future = async {
-            let Ok(messages) = cx.read_entity(&current_chat, move |current_chat, cx| {
-                serde_json::to_string(&ValuesOnly(&current_chat.read_messages(cx)))
+            // Only the active branch is sent, without the reply being generated.
+            // The system prompt always goes first. A reply being continued is sent
+            // last, so the model picks up where it left off.
+            let Ok(messages) = cx.read_entity(&current_chat, |current_chat, cx| {
+                let path = current_chat.active_path(cx);
+
+                // Some providers reject a trailing assistant message that ends in whitespace.
+                let partial_reply = path
+                    .iter()
+                    .find(|message| is_continuing && message.message_id() == &msg_id)
+                    .map(|message| Message {
+                        content: message.message.content.trim_end().to_string(),
+                        role: MessageRole::Assistant,
+                    });
+
+                // Attachments are written in the format of the provider.
+                let messages = system_prompt
+                    .iter()
+                    .map(|message| (message, &[][..]))
+                    .chain(
+                        path.iter()
+                            .take_while(|message| message.message_id() != &msg_id)
+                            .filter(|message| !is_failed_reply(message))
+                            .map(|message| (&message.message, message.attachments.as_slice())),
+                    )
+                    .chain(partial_reply.iter().map(|message| (message, &[][..])))
+                    .map(|(message, attachments)| {
+                        message_to_json(provider_kind, message, attachments)
+                    })
+                    .collect::<Vec<_>>();
+
+                serde_json::to_string(&messages)
             }) else {
-                return;
+                return Err(usage_tracker.error("The chat could not be read."));
             };
 
             let messages = unsafe {
@@ -408,38 +840,67 @@ fn send_message(
             };
 
             let options = ChatOptions::new(&current_model).messages_serialized(messages);
-            let response = current_provider.inner.chat(&options).await;
+            let client =
+                current_provider.observed_client([parameters_observer, usage_tracker.clone()]);
+            let response = client.chat(&options).await;
 
             match response {
-                Ok(mut response) => {
-                    while let Some(Ok(chunk)) = response.next().await {
-                        let _ = current_chat.update(cx, |current_chat, cx| {
-                            current_chat
-                                .push_message_content(cx, &msg_id, &chunk.content)
-                                .unwrap();
-                            cx.notify();
-                        });
+                Ok(mut response) => loop {
+                    match response.next().await {
+                        Some(Ok(chunk)) => {
+                            if first_token_at.is_none() && !chunk.content.is_empty() {
+                                first_token_at = Some(Instant::now());
+                            }
+
+                            let _ = current_chat.update(cx, |current_chat, cx| {
+                                current_chat
+                                    .push_message_content(cx, &msg_id, &chunk.content)
+                                    .unwrap();
+                                cx.notify();
+                            });
+                        }
+                        Some(Err(err)) => return Err(usage_tracker.error(err)),
+                        None => return Ok(()),
                     }
-                }
-                Err(err) => {
-                    let _ = current_chat.update(cx, |current_chat, cx| {
-                        current_chat
-                            .push_message_content(cx, &msg_id, &format!("{}", err.to_string()))
-                            .unwrap();
-                        cx.notify();
-                    });
-                }
-            };
+                },
+                Err(err) => Err(usage_tracker.error(err)),
+            }
         };
 
         // Wrap the streaming future with abort registration
-        let _ = Abortable::new(streaming_future, abort_registration).await;
+        let result = Abortable::new(streaming_future, abort_registration).await;
+
+        let usage = usage_tracker.usage();
+        let (status, error) = match result {
+            // A reply can also stop because it ran out of tokens.
+            Ok(Ok(())) => (
+                MessageStatus::from_finish_reason(usage.finish_reason.as_deref()),
+                None,
+            ),
+            Ok(Err(error)) => (MessageStatus::Errored, Some(error)),
+            Err(Aborted) => (MessageStatus::Cancelled, None),
+        };
 
+        // A continued reply adds up with the part that was generated before.
+        let generation = GenerationMetadata {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens.map(|completion_tokens| {
+                completion_tokens + previous.completion_tokens.unwrap_or_default()
+            }),
+            time_to_first_token: previous
+                .time_to_first_token
+                .or(first_token_at.map(|first_token_at| first_token_at - started_at)),
+            duration: Some(previous.duration.unwrap_or_default() + started_at.elapsed()),
+            finish_reason: usage.finish_reason,
+            status,
+            error,
+            ..GenerationMetadata::new(current_provider_id, current_model)
+        };
         // Clean up streaming state when done (whether completed or aborted)
-        let _ = cx.update(|cx| {
-            let managers_guard = managers_for_cleanup.read_blocking();
-            managers_guard.chats.set_streaming(cx, false);
-            managers_guard.chats.set_abort_handle(cx, None);
+        let _ = current_chat.update(cx, |current_chat, cx| {
+            if let Err(err) = current_chat.finish_reply(cx, &msg_id, generation) {
+                tracing::error!("failed to write the reply: {err}");
+            }
         });
     })
     .detach();
@@ -0,0 +1,322 @@
+use std::sync::Arc;
+
+use gpui::{
+    App, ClickEvent, Div, ElementId, Entity, InteractiveElement, MouseDownEvent, SharedString,
+    Window, deferred, div, prelude::*, px,
+};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant, Toggle, ToggleVariant},
+    primitives::min_w0_wrapper,
+    theme::{ThemeExt, ThemeLayerKind},
+};
+use smol::lock::RwLock;
+
+use crate::{
+    assets::AstrumIconKind,
+    blocks::ParametersForm,
+    managers::{DbError, GenerationParameters, Managers, UniqueId},
+};
+
+/// Which parameters the popover edits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ParametersScope {
+    /// The open chat, or the next new chat if none is open.
+    Chat,
+    /// The defaults of the selected model.
+    Model,
+}
+
+#[derive(Clone)]
+struct ParametersPopover {
+    scope: ParametersScope,
+    form: ParametersForm,
+    error: Option<SharedString>,
+}
+
+/// A button next to the model picker that opens a popover for editing
+/// the generation parameters of the chat or of the selected model.
+pub fn render_parameters_button(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    window: &mut Window,
+    cx: &mut App,
+) -> Div {
+    let id = base_id.with_suffix("parameters");
+
+    let popover_state =
+        window.use_keyed_state(id.with_suffix("state:popover"), cx, |_window, _cx| {
+            None::<ParametersPopover>
+        });
+
+    let popover = popover_state.read(cx).clone();
+    let is_open = popover.is_some();
+
+    // While open, pressing the button closes the popover through `on_mouse_down_out`.
+    let button = Button::new(id.with_suffix("btn"))
+        .variant(ButtonVariant::SecondaryGhost)
+        .icon(AstrumIconKind::Settings)
+        .icon_size(px(14.))
+        .p(px(7.))
+        .when(!is_open, |this| {
+            let managers = managers.clone();
+            let popover_state = popover_state.clone();
+
+            this.on_click(move |_event, _window, cx| {
+                let parameters = scope_parameters(&managers, ParametersScope::Chat, cx);
+                let popover = ParametersPopover {
+                    scope: ParametersScope::Chat,
+                    form: ParametersForm::new(cx, &parameters),
+                    error: None,
+                };
+
+                set_popover(&popover_state, Some(popover), cx);
+            })
+        });
+
+    div()
+        .relative()
+        .child(button)
+        .when_some(popover, |this, popover| {
+            this.child(render_popover(&id, managers, &popover_state, popover, cx))
+        })
+}
+
+fn render_popover(
+    id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    popover_state: &Entity<Option<ParametersPopover>>,
+    popover: ParametersPopover,
+    cx: &App,
+) -> Div {
+    let background_color = ThemeLayerKind::Tertiary.resolve(cx);
+    let border_color = ThemeLayerKind::Tertiary.next().resolve(cx);
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    let has_model = selected_model(&managers.read_blocking(), cx).is_some();
+
+    let scope_toggle = |suffix: &str, text: &'static str, scope: ParametersScope| {
+        let managers = managers.clone();
+        let popover_state = popover_state.clone();
+
+        Toggle::new(id.with_suffix(suffix))
+            .text(text)
+            .variant(ToggleVariant::Secondary)
+            .checked(popover.scope == scope)
+            .disabled(scope == ParametersScope::Model && !has_model)
+            .on_click(move |_checked, _window, cx| {
+                let parameters = scope_parameters(&managers, scope, cx);
+                let popover = ParametersPopover {
+                    scope,
+                    form: ParametersForm::new(cx, &parameters),
+                    error: None,
+                };
+
+                set_popover(&popover_state, Some(popover), cx);
+            })
+    };
+
+    let save = {
+        let managers = managers.clone();
+        let popover_state = popover_state.clone();
+        let popover = popover.clone();
+
+        move |_event: &ClickEvent, _window: &mut Window, cx: &mut App| {
+            let result = popover.form.parameters(cx).and_then(|parameters| {
+                save_scope_parameters(&managers, popover.scope, parameters, cx)
+                    .map_err(|err| SharedString::from(err.to_string()))
+            });
+
+            match result {
+                Ok(()) => set_popover(&popover_state, None, cx),
+                Err(error) => set_popover(
+                    &popover_state,
+                    Some(ParametersPopover {
+                        error: Some(error),
+                        ..popover.clone()
+                    }),
+                    cx,
+                ),
+            }
+        }
+    };
+
+    let reset = {
+        let managers = managers.clone();
+        let popover_state = popover_state.clone();
+        let scope = popover.scope;
+
+        move |_event: &ClickEvent, _window: &mut Window, cx: &mut App| {
+            let result =
+                save_scope_parameters(&managers, scope, GenerationParameters::default(), cx);
+            if let Err(err) = result {
+                tracing::error!("failed to reset generation parameters: {err}");
+            }
+
+            set_popover(&popover_state, None, cx);
+        }
+    };
+
+    let close = {
+        let popover_state = popover_state.clone();
+
+        move |_event: &MouseDownEvent, _window: &mut Window, cx: &mut App| {
+            set_popover(&popover_state, None, cx)
+        }
+    };
+
+    let hint = match popover.scope {
+        ParametersScope::Chat => "Overrides the model and assistant for this chat.",
+        ParametersScope::Model => "Used by every chat with this model, unless overridden.",
+    };
+
+    div()
+        .w(px(340.))
+        .absolute()
+        .bottom_full()
+        .left_0()
+        .pb(cx.get_theme().layout.padding.md)
+        .child(
+            deferred(
+                div()
+                    .id(id.with_suffix("popover"))
+                    .occlude()
+                    .w_full()
+                    .p(px(12.))
+                    .flex()
+                    .flex_col()
+                    .gap(px(10.))
+                    .rounded(px(8.))
+                    .bg(background_color)
+                    .border(px(1.))
+                    .border_color(border_color)
+                    .on_mouse_down_out(close)
+                    .child(
+                        div()
+                            .flex()
+                            .gap(px(4.))
+                            .child(scope_toggle(
+                                "chat_scope",
+                                "This chat",
+                                ParametersScope::Chat,
+                            ))
+                            .child(scope_toggle(
+                                "model_scope",
+                                "Model default",
+                                ParametersScope::Model,
+                            )),
+                    )
+                    .child(
+                        min_w0_wrapper()
+                            .text_size(caption_size)
+                            .text_color(secondary_text_color)
+                            .child(hint),
+                    )
+                    .child(popover.form.render(&id.with_suffix("form"), cx))
+                    .when_some(popover.error.clone(), |this, error| {
+                        this.child(
+                            min_w0_wrapper()
+                                .text_size(caption_size)
+                                .text_color(secondary_text_color)
+                                .child(error),
+                        )
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .justify_end()
+                            .gap(px(7.))
+                            .child(
+                                Button::new(id.with_suffix("reset_btn"))
+                                    .text("Reset")
+                                    .variant(ButtonVariant::SecondaryGhost)
+                                    .on_click(reset),
+                            )
+                            .child(
+                                Button::new(id.with_suffix("save_btn"))
+                                    .text("Save")
+                                    .on_click(save),
+                            ),
+                    ),
+            )
+            .with_priority(1),
+        )
+}
+
+fn set_popover(
+    popover_state: &Entity<Option<ParametersPopover>>,
+    popover: Option<ParametersPopover>,
+    cx: &mut App,
+) {
+    popover_state.update(cx, |popover_state, cx| {
+        *popover_state = popover;
+        cx.notify();
+    });
+}
+
+/// The provider and model the chat box is set to.
+fn selected_model(managers: &Managers, cx: &App) -> Option<(UniqueId, String)> {
+    let provider_id = managers.models.current_model.provider_id.read(cx).clone()?;
+    let model = managers.models.get_current_model(cx).cloned()?;
+    Some((provider_id, model))
+}
+
+fn scope_parameters(
+    managers: &Arc<RwLock<Managers>>,
+    scope: ParametersScope,
+    cx: &mut App,
+) -> GenerationParameters {
+    let managers = managers.read_blocking();
+
+    match scope {
+        ParametersScope::Chat => match managers.chats.get_current_chat(cx) {
+            Ok(Some(current_chat)) => current_chat.read(cx).parameters.read(cx).clone(),
+            _ => managers.chats.new_chat_parameters.read(cx).clone(),
+        },
+        ParametersScope::Model => match selected_model(&managers, cx) {
+            Some((provider_id, model)) => {
+                managers
+                    .models
+                    .get_model_parameters(cx, &provider_id, &model)
+            }
+            None => GenerationParameters::default(),
+        },
+    }
+}
+
+fn save_scope_parameters(
+    managers: &Arc<RwLock<Managers>>,
+    scope: ParametersScope,
+    parameters: GenerationParameters,
+    cx: &mut App,
+) -> Result<(), DbError> {
+    let managers = managers.read_blocking();
+
+    match scope {
+        ParametersScope::Chat => match managers.chats.get_current_chat(cx)? {
+            Some(current_chat) => {
+                current_chat.update(cx, |chat, cx| chat.set_parameters(cx, parameters))
+            }
+            // Taken over by the chat once the first message is sent.
+            None => {
+                managers
+                    .chats
+                    .new_chat_parameters
+                    .update(cx, |new_chat_parameters, cx| {
+                        *new_chat_parameters = parameters;
+                        cx.notify();
+                    });
+                Ok(())
+            }
+        },
+        ParametersScope::Model => {
+            let (provider_id, model) =
+                selected_model(&managers, cx).ok_or_else(|| DbError::MissingData("model"))?;
+
+            managers
+                .models
+                .set_model_parameters(cx, &provider_id, &model, parameters)
+        }
+    }
+}
@@ -1,13 +1,29 @@
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use gpui::{App, Window, div, ease_out_quint, prelude::*, px, radians};
-use gpui_tesserae::{components::Icon, primitives::min_w0_wrapper, theme::ThemeExt};
+use gpui::{App, ElementId, Window, div, ease_out_quint, prelude::*, px, radians};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Icon, Toggle, ToggleVariant},
+    primitives::min_w0_wrapper,
+    theme::ThemeExt,
+};
 use gpui_transitions::WindowUseTransition;
+use smol::lock::RwLock;
 
 use crate::assets::AstrumIconKind;
+use crate::managers::{Assistant, Managers, ModelChoice, UniqueId};
 use crate::utils::strings::choose_string;
 
-pub fn render_prompt_new_chat(window: &mut Window, cx: &mut App) -> impl IntoElement {
+pub fn render_prompt_new_chat(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    managers_guard: &Managers,
+    window: &mut Window,
+    cx: &mut App,
+) -> impl IntoElement {
     let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
     let text_size = cx.get_theme().layout.text.default_font.sizes.heading_sm;
 
@@ -86,4 +102,111 @@ pub fn render_prompt_new_chat(window: &mut Window, cx: &mut App) -> impl IntoEle
                 .text_center()
                 .text_color(secondary_text_color),
         )
+        .children(render_assistant_picker(
+            base_id,
+            managers,
+            managers_guard,
+            cx,
+        ))
+}
+
+/// Lets the next chat start from an assistant. Hidden until an assistant exists.
+fn render_assistant_picker(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    managers_guard: &Managers,
+    cx: &App,
+) -> Option<impl IntoElement> {
+    let assistants = managers_guard.assistants.assistants.read(cx);
+    if assistants.is_empty() {
+        return None;
+    }
+
+    let selected_id = managers_guard
+        .assistants
+        .get_new_chat_assistant(cx)
+        .map(|(assistant_id, _)| assistant_id.clone());
+
+    let no_assistant_toggle = {
+        let managers = managers.clone();
+
+        Toggle::new(base_id.with_suffix("no_assistant_btn"))
+            .text("No assistant")
+            .variant(ToggleVariant::Secondary)
+            .checked(selected_id.is_none())
+            .on_click(move |_checked, _window, cx| {
+                let managers = managers.read_blocking();
+                managers.assistants.set_new_chat_assistant(cx, None);
+                managers.models.show_default_model(cx);
+            })
+    };
+
+    Some(
+        div()
+            .max_w(px(500.))
+            .flex()
+            .flex_row()
+            .flex_wrap()
+            .justify_center()
+            .gap(px(5.))
+            .child(no_assistant_toggle)
+            .children(assistants.iter().map(|(assistant_id, assistant)| {
+                let managers = managers.clone();
+                let assistant_id = assistant_id.clone();
+                let is_selected = selected_id.as_ref() == Some(&assistant_id);
+                let selection = (assistant_id.clone(), assistant.clone());
+
+                Toggle::new(base_id.with_suffix(format!("assistant_{}", assistant_id)))
+                    .text(assistant.name.clone())
+                    .icon(assistant.icon.clone())
+                    .variant(ToggleVariant::Secondary)
+                    .checked(is_selected)
+                    .on_click(move |_checked, _window, cx| {
+                        let (assistant_id, assistant) = &selection;
+                        select_assistant(&managers, assistant_id, assistant, cx);
+                    })
+            })),
+    )
+}
+
+/// Makes the next chat start from an assistant, switching to its model when it has one.
+/// The default model is left as it is.
+fn select_assistant(
+    managers: &Arc<RwLock<Managers>>,
+    assistant_id: &UniqueId,
+    assistant: &Assistant,
+    cx: &mut App,
+) {
+    let managers = managers.read_blocking();
+
+    managers
+        .assistants
+        .set_new_chat_assistant(cx, Some(assistant_id.clone()));
+
+    let (Some(provider_id), Some(model)) = (&assistant.provider_id, &assistant.model) else {
+        return;
+    };
+
+    let Some(provider_name) = managers
+        .models
+        .providers
+        .read(cx)
+        .get(provider_id)
+        .map(|provider| provider.name.read(cx).to_string())
+    else {
+        tracing::warn!(
+            "the provider of assistant {} no longer exists, keeping the current model",
+            assistant.name
+        );
+        return;
+    };
+
+    managers.models.show_model(
+        cx,
+        Some(&ModelChoice {
+            provider_id: provider_id.clone(),
+            provider_name,
+            model: model.clone(),
+        }),
+    );
 }
@@ -0,0 +1,200 @@
+use gpui::{App, AppContext, Div, ElementId, Entity, IntoElement, Window, div, prelude::*, px};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant, Input},
+    primitives::{input::InputState, min_w0_wrapper},
+    theme::ThemeExt,
+};
+
+use crate::{
+    assets::AstrumIconKind,
+    managers::{Chat, Managers},
+};
+
+/// Shows the system prompt of a chat above its messages, and lets it be edited.
+pub fn render_system_prompt_header(
+    base_id: &ElementId,
+    current_chat: &Entity<Chat>,
+    managers_guard: &Managers,
+    window: &mut Window,
+    cx: &mut App,
+) -> Div {
+    // Keyed by chat so switching chats closes the editor.
+    let id = base_id
+        .with_suffix("system_prompt")
+        .with_suffix(current_chat.read(cx).chat_id.to_string());
+
+    let editing_state =
+        window.use_keyed_state(id.with_suffix("state:editing"), cx, |_window, _cx| {
+            None::<Entity<InputState>>
+        });
+
+    let chat_system_prompt = current_chat.read(cx).system_prompt.read(cx).clone();
+    let default_system_prompt = managers_guard
+        .settings
+        .default_system_prompt
+        .read(cx)
+        .clone();
+
+    let container = div().w_full().flex().flex_col().px(px(20.)).pt(px(20.));
+
+    if let Some(edit_input_state) = editing_state.read(cx).clone() {
+        return container.child(render_system_prompt_editor(
+            &id,
+            current_chat,
+            edit_input_state,
+            &editing_state,
+            default_system_prompt,
+        ));
+    }
+
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    let summary = match (&chat_system_prompt, &default_system_prompt) {
+        (Some(system_prompt), _) => system_prompt_preview(system_prompt),
+        (None, Some(system_prompt)) => {
+            format!("Default · {}", system_prompt_preview(system_prompt))
+        }
+        (None, None) => String::from("No system prompt"),
+    };
+
+    let edit_button = {
+        let editing_state = editing_state.clone();
+        let initial_value = chat_system_prompt
+            .or(default_system_prompt)
+            .unwrap_or_default();
+
+        Button::new(id.with_suffix("edit_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .icon(AstrumIconKind::Edit)
+            .icon_size(px(12.))
+            .p(px(6.))
+            .rounded(px(6.))
+            .on_click(move |_event, _window, cx| {
+                let initial_value = initial_value.clone();
+                let edit_input_state =
+                    cx.new(|cx| InputState::new(cx).initial_value(initial_value));
+
+                editing_state.update(cx, |editing_state, cx| {
+                    *editing_state = Some(edit_input_state);
+                    cx.notify();
+                });
+            })
+    };
+
+    container.child(
+        div()
+            .w_full()
+            .flex()
+            .items_center()
+            .justify_between()
+            .gap(px(7.))
+            .child(
+                min_w0_wrapper()
+                    .text_size(caption_size)
+                    .text_color(secondary_text_color)
+                    .truncate()
+                    .child(summary),
+            )
+            .child(edit_button),
+    )
+}
+
+fn render_system_prompt_editor(
+    id: &ElementId,
+    current_chat: &Entity<Chat>,
+    edit_input_state: Entity<InputState>,
+    editing_state: &Entity<Option<Entity<InputState>>>,
+    default_system_prompt: Option<String>,
+) -> Div {
+    let has_default = default_system_prompt.is_some();
+
+    let set_system_prompt = {
+        let current_chat = current_chat.clone();
+        let editing_state = editing_state.clone();
+
+        move |system_prompt: Option<String>, cx: &mut App| {
+            let result =
+                current_chat.update(cx, |chat, cx| chat.set_system_prompt(cx, system_prompt));
+            if let Err(err) = result {
+                tracing::error!("failed to save system prompt: {err}");
+                return;
+            }
+
+            close_editing(&editing_state, cx);
+        }
+    };
+
+    let save = {
+        let set_system_prompt = set_system_prompt.clone();
+        let edit_input_state = edit_input_state.clone();
+
+        // Saving the default unchanged keeps the chat following it, rather than pinning it
+        // to a copy that later changes to the default don't reach.
+        move |cx: &mut App| {
+            let system_prompt = edit_input_state.read(cx).value().to_string();
+            let is_default = default_system_prompt.as_ref() == Some(&system_prompt);
+            set_system_prompt((!is_default).then_some(system_prompt), cx);
+        }
+    };
+
+    let editing_state = editing_state.clone();
+
+    div()
+        .w_full()
+        .flex()
+        .flex_col()
+        .items_end()
+        .gap(px(7.))
+        .child(
+            Input::new(id.with_suffix("input"), edit_input_state)
+                .w_full()
+                .line_clamp(8)
+                .word_wrap(true)
+                .placeholder("Instructions sent before every message of this chat...")
+                .on_submit({
+                    let save = save.clone();
+                    move |_window, cx| save(cx)
+                }),
+        )
+        .child(
+            div()
+                .flex()
+                .gap(px(7.))
+                .child(
+                    Button::new(id.with_suffix("cancel_btn"))
+                        .text("Cancel")
+                        .variant(ButtonVariant::SecondaryGhost)
+                        .on_click(move |_event, _window, cx| close_editing(&editing_state, cx)),
+                )
+                .child(
+                    Button::new(id.with_suffix("use_default_btn"))
+                        .text(if has_default { "Use default" } else { "Clear" })
+                        .variant(ButtonVariant::SecondaryGhost)
+                        .on_click(move |_event, _window, cx| set_system_prompt(None, cx)),
+                )
+                .child(
+                    Button::new(id.with_suffix("save_btn"))
+                        .text("Save")
+                        .on_click(move |_event, _window, cx| save(cx)),
+                ),
+        )
+}
+
+fn close_editing(editing_state: &Entity<Option<Entity<InputState>>>, cx: &mut App) {
+    editing_state.update(cx, |editing_state, cx| {
+        *editing_state = None;
+        cx.notify();
+    });
+}
+
+/// The first line of a system prompt, for showing it in a single row.
+fn system_prompt_preview(system_prompt: &str) -> String {
+    let first_line = system_prompt.trim().lines().next().unwrap_or_default();
+
+    match system_prompt.trim().lines().nth(1) {
+        Some(_) => format!("{first_line}…"),
+        None => first_line.to_string(),
+    }
+}
@@ -1,27 +1,54 @@
 use std::sync::Arc;
 
 use gpui::{
-    App, ElementId, Fill, InteractiveElement, IntoElement, Overflow, PointRefinement, RenderOnce,
-    div, prelude::*, px, relative,
+    AnyElement, App, ElementId, Entity, Fill, FontWeight, HighlightStyle, InteractiveElement,
+    IntoElement, MouseButton, MouseDownEvent, Overflow, Pixels, Point, PointRefinement, RenderOnce,
+    SharedString, StyledText, Window, anchored, deferred, div, prelude::*, px, relative,
 };
 use gpui_tesserae::{
     ElementIdExt, PositionalParentElement,
     components::{Button, ButtonVariant, Icon, Input, Toggle, ToggleVariant},
     extensions::mouse_handleable::MouseHandleable,
     primitives::input::InputState,
-    theme::ThemeExt,
+    theme::{ThemeExt, ThemeLayerKind},
 };
 use smol::lock::RwLock;
 
 use crate::{
-    OpenSettings, PixelsExt, assets::AstrumIconKind, managers::Managers, managers::UniqueId,
+    OpenSettings, PixelsExt,
+    assets::AstrumIconKind,
+    managers::{
+        ChatSearchResult, ChatSummary, ExportFormat, Managers, TRASH_RETENTION_DAYS, UniqueId,
+        save_export_files,
+    },
     utils::search::filter_by_relevance,
 };
 
+/// Which list of chats the sidebar is currently showing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ListMode {
+    Chats,
+    Archive,
+    Trash,
+}
+
+/// A context menu opened on a chat in the list.
+#[derive(Clone)]
+struct ChatContextMenu {
+    chat_id: UniqueId,
+    position: Point<Pixels>,
+}
+
+/// How many messages are searched for a query.
+const MESSAGE_SEARCH_LIMIT: usize = 50;
+/// How many matching messages are shown per chat.
+const MESSAGE_MATCHES_PER_CHAT: usize = 3;
+
 #[derive(Clone)]
 struct SearchState {
     last_query: String,
     filtered_ids: Option<Vec<UniqueId>>,
+    message_results: Vec<ChatSearchResult>,
 }
 
 impl SearchState {
@@ -29,6 +56,7 @@ impl SearchState {
         Self {
             last_query: String::new(),
             filtered_ids: None,
+            message_results: Vec::new(),
         }
     }
 }
@@ -96,6 +124,20 @@ impl RenderOnce for Sidebar {
             |_window, _cx| SearchState::new(),
         );
 
+        let context_menu_state = window.use_keyed_state(
+            self.id.with_suffix("state:context_menu"),
+            cx,
+            |_window, _cx| None::<ChatContextMenu>,
+        );
+        let context_menu = context_menu_state.read(cx).clone();
+
+        let list_mode_state = window.use_keyed_state(
+            self.id.with_suffix("state:list_mode"),
+            cx,
+            |_window, _cx| ListMode::Chats,
+        );
+        let list_mode = *list_mode_state.read(cx);
+
         let managers = self.managers.read_blocking();
         let available_update = managers.update.available_update.read(cx).clone();
         let chats = &managers.chats;
@@ -109,13 +151,22 @@ impl RenderOnce for Sidebar {
             let new_query = current_query.clone();
             let search_state = search_state.clone();
             let chat_data = collect_chat_data(chats, cx);
+            let managers = self.managers.clone();
 
             cx.spawn(async move |cx| {
                 let filtered_ids = compute_filtered_ids(chat_data, &new_query);
 
+                let message_results = managers
+                    .read()
+                    .await
+                    .chats
+                    .search_messages(&new_query, MESSAGE_SEARCH_LIMIT)
+                    .unwrap_or_default();
+
                 let _ = search_state.update(cx, |state, cx| {
                     state.last_query = new_query;
                     state.filtered_ids = filtered_ids;
+                    state.message_results = message_results;
                     cx.notify();
                 });
             })
@@ -123,6 +174,7 @@ impl RenderOnce for Sidebar {
         }
 
         let filtered_ids = search_state_data.filtered_ids.clone();
+        let message_results = search_state_data.message_results.clone();
 
         let top_section = div()
             .flex()
@@ -171,47 +223,83 @@ impl RenderOnce for Sidebar {
                 };
                 this
             })
-            .map(|this| {
-                let Some(iter) = chats.chats_iter(cx) else {
-                    return this.child(empty_state_text("No threads exist yet.", window, cx));
-                };
-
-                let all_chats: Vec<_> = iter.collect();
-                if all_chats.is_empty() {
-                    return this.child(empty_state_text("No threads exist yet.", window, cx));
+            .map(|this| match list_mode {
+                ListMode::Chats => {
+                    let Some(iter) = chats.chats_iter(cx) else {
+                        return this.child(empty_state_text("No threads exist yet.", window, cx));
+                    };
+
+                    let all_chats: Vec<_> = iter.collect();
+                    if all_chats.is_empty() {
+                        return this.child(empty_state_text("No threads exist yet.", window, cx));
+                    }
+
+                    let visible_chats: Vec<_> = match &filtered_ids {
+                        Some(ids) => all_chats
+                            .into_iter()
+                            .filter(|chat| ids.contains(&chat.chat_id))
+                            .collect(),
+                        None => all_chats,
+                    };
+
+                    if visible_chats.is_empty() && message_results.is_empty() {
+                        return this.child(empty_state_text(
+                            "No threads matched this query.",
+                            window,
+                            cx,
+                        ));
+                    }
+
+                    this.children(visible_chats.into_iter().map(|chat| {
+                        render_chat_row(
+                            &self.id,
+                            &self.managers,
+                            context_menu_state.clone(),
+                            chat.chat_id.clone(),
+                            chat.title.read(cx).clone(),
+                            current_chat_id == Some(&chat.chat_id),
+                            chat.is_streaming(cx),
+                        )
+                    }))
+                    .when(
+                        filtered_ids.is_some() && !message_results.is_empty(),
+                        |this| {
+                            this.child(render_section_caption("Messages", cx)).children(
+                                message_results.iter().map(|result| {
+                                    render_message_search_result(
+                                        &self.id,
+                                        &self.managers,
+                                        result,
+                                        cx,
+                                    )
+                                }),
+                            )
+                        },
+                    )
                 }
+                ListMode::Archive => {
+                    let archived_chats = chats.archived_chats.read(cx);
 
-                let visible_chats: Vec<_> = match &filtered_ids {
-                    Some(ids) => all_chats
-                        .into_iter()
-                        .filter(|chat| ids.contains(&chat.chat_id))
-                        .collect(),
-                    None => all_chats,
-                };
+                    if archived_chats.is_empty() {
+                        return this.child(empty_state_text("No archived threads.", window, cx));
+                    }
 
-                if visible_chats.is_empty() {
-                    return this.child(empty_state_text(
-                        "No threads matched this query.",
-                        window,
-                        cx,
-                    ));
+                    this.children(archived_chats.iter().map(|summary| {
+                        render_inactive_chat_row(&self.id, &self.managers, summary, list_mode)
+                    }))
                 }
+                ListMode::Trash => {
+                    let trashed_chats = chats.trashed_chats.read(cx);
 
-                this.children(visible_chats.into_iter().map(|chat| {
-                    let current_chat_id_state = current_chat_id_state.clone();
-                    let chat_id = chat.chat_id.clone();
-
-                    Toggle::new(self.id.with_suffix(format!("thread_{}", chat_id)))
-                        .text(chat.title.read(cx).replace("\n", " ").replace("  ", " "))
-                        .variant(ToggleVariant::Secondary)
-                        .checked(current_chat_id == Some(&chat_id))
-                        .icon(AstrumIconKind::Chat)
-                        .on_click(move |_checked, _window, cx| {
-                            current_chat_id_state
-                                .update(cx, |this, _cx| *this = Some(chat_id.clone()));
-                        })
-                        .justify_start()
-                }))
+                    if trashed_chats.is_empty() {
+                        return this.child(empty_state_text("The trash is empty.", window, cx));
+                    }
+
+                    this.child(render_trash_header(&self.id, &self.managers, cx))
+                        .children(trashed_chats.iter().map(|summary| {
+                            render_inactive_chat_row(&self.id, &self.managers, summary, list_mode)
+                        }))
+                }
             });
 
         let bottom_section = div()
@@ -233,6 +321,20 @@ impl RenderOnce for Sidebar {
                         })
                     }),
             )
+            .child(render_list_mode_toggle(
+                &self.id,
+                list_mode_state.clone(),
+                list_mode,
+                ListMode::Archive,
+                AstrumIconKind::Archive,
+            ))
+            .child(render_list_mode_toggle(
+                &self.id,
+                list_mode_state.clone(),
+                list_mode,
+                ListMode::Trash,
+                AstrumIconKind::Trash,
+            ))
             .when(available_update.is_some(), |this| {
                 this.child(
                     Toggle::new(self.id.with_suffix("download_btn"))
@@ -278,9 +380,416 @@ impl RenderOnce for Sidebar {
                     .child(divider(secondary_bg_color))
                     .child(bottom_section),
             )
+            .when_some(context_menu, |this, context_menu| {
+                this.child(render_chat_context_menu(
+                    &self.id,
+                    &self.managers,
+                    context_menu_state,
+                    context_menu,
+                    cx,
+                ))
+            })
     }
 }
 
+fn render_list_mode_toggle(
+    base_id: &ElementId,
+    list_mode_state: Entity<ListMode>,
+    current_mode: ListMode,
+    mode: ListMode,
+    icon: AstrumIconKind,
+) -> impl IntoElement {
+    let suffix = match mode {
+        ListMode::Chats => "chats_btn",
+        ListMode::Archive => "archive_btn",
+        ListMode::Trash => "trash_btn",
+    };
+
+    Toggle::new(base_id.with_suffix(suffix))
+        .variant(ToggleVariant::Tertiary)
+        .icon(icon)
+        .icon_size(px(18.))
+        .p(px(9.))
+        .checked(current_mode == mode)
+        .on_click(move |_checked, _window, cx| {
+            list_mode_state.update(cx, |list_mode, cx| {
+                *list_mode = if *list_mode == mode {
+                    ListMode::Chats
+                } else {
+                    mode
+                };
+                cx.notify();
+            });
+        })
+}
+
+fn render_chat_row(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    context_menu_state: Entity<Option<ChatContextMenu>>,
+    chat_id: UniqueId,
+    title: String,
+    is_current: bool,
+    is_streaming: bool,
+) -> impl IntoElement {
+    let row_id = base_id.with_suffix(format!("thread_{}", chat_id));
+    let group_name = SharedString::from(format!("thread_row_{}", chat_id));
+
+    let toggle = {
+        let managers = managers.clone();
+        let chat_id = chat_id.clone();
+
+        Toggle::new(row_id.clone())
+            .text(title.replace("\n", " ").replace("  ", " "))
+            .variant(ToggleVariant::Secondary)
+            .checked(is_current)
+            // Chats that are generating a reply stand out from the rest.
+            .icon(match is_streaming {
+                true => AstrumIconKind::Think,
+                false => AstrumIconKind::Chat,
+            })
+            .on_click(move |_checked, _window, cx| {
+                managers
+                    .read_blocking()
+                    .chats
+                    .set_current_chat(cx, chat_id.clone());
+            })
+            .justify_start()
+    };
+
+    let archive_button = {
+        let managers = managers.clone();
+        let chat_id = chat_id.clone();
+
+        row_action_button(row_id.with_suffix("archive_btn"), AstrumIconKind::Archive).on_click(
+            move |_event, _window, cx| {
+                let _ = managers.read_blocking().chats.archive_chat(cx, &chat_id);
+            },
+        )
+    };
+
+    let delete_button = {
+        let managers = managers.clone();
+        let chat_id = chat_id.clone();
+
+        row_action_button(row_id.with_suffix("delete_btn"), AstrumIconKind::Trash)
+            .variant(ButtonVariant::DestructiveGhost)
+            .on_click(move |_event, _window, cx| {
+                let _ = managers.read_blocking().chats.delete_chat(cx, &chat_id);
+            })
+    };
+
+    let open_context_menu = {
+        let chat_id = chat_id.clone();
+
+        move |event: &MouseDownEvent, _window: &mut Window, cx: &mut App| {
+            context_menu_state.update(cx, |context_menu, cx| {
+                *context_menu = Some(ChatContextMenu {
+                    chat_id: chat_id.clone(),
+                    position: event.position,
+                });
+                cx.notify();
+            });
+        }
+    };
+
+    div()
+        .group(group_name.clone())
+        .w_full()
+        .flex()
+        .flex_col()
+        .on_mouse_down(MouseButton::Right, open_context_menu)
+        .child(toggle)
+        .child(
+            div()
+                .absolute()
+                .top_0()
+                .bottom_0()
+                .right(px(4.))
+                .flex()
+                .flex_row()
+                .items_center()
+                .gap(px(2.))
+                .invisible()
+                .group_hover(group_name, |this| this.visible())
+                .occlude()
+                .child(archive_button)
+                .child(delete_button),
+        )
+}
+
+fn render_chat_context_menu(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    context_menu_state: Entity<Option<ChatContextMenu>>,
+    context_menu: ChatContextMenu,
+    cx: &App,
+) -> impl IntoElement {
+    let background_color = ThemeLayerKind::Tertiary.resolve(cx);
+    let border_color = ThemeLayerKind::Tertiary.next().resolve(cx);
+    let menu_id = base_id.with_suffix("context_menu");
+
+    let close_menu = {
+        let context_menu_state = context_menu_state.clone();
+
+        move |_event: &MouseDownEvent, _window: &mut Window, cx: &mut App| {
+            context_menu_state.update(cx, |context_menu, cx| {
+                *context_menu = None;
+                cx.notify();
+            });
+        }
+    };
+
+    let export_buttons = ExportFormat::ALL.map(|format| {
+        let managers = managers.clone();
+        let context_menu_state = context_menu_state.clone();
+        let chat_id = context_menu.chat_id.clone();
+
+        Button::new(menu_id.with_suffix(format.extension()))
+            .variant(ButtonVariant::SecondaryGhost)
+            .icon(AstrumIconKind::Download)
+            .icon_size(px(14.))
+            .text(format!("Export as {}", format.label()))
+            .on_click(move |_event, _window, cx| {
+                context_menu_state.update(cx, |context_menu, cx| {
+                    *context_menu = None;
+                    cx.notify();
+                });
+
+                let files = managers.read_blocking().chats.export_chats(
+                    cx,
+                    std::slice::from_ref(&chat_id),
+                    format,
+                );
+
+                match files {
+                    Ok(files) => save_export_files(cx, files),
+                    Err(err) => tracing::error!("failed to export chat: {err}"),
+                }
+            })
+    });
+
+    deferred(
+        anchored()
+            .position(context_menu.position)
+            .snap_to_window_with_margin(px(8.))
+            .child(
+                div()
+                    .id(menu_id)
+                    .occlude()
+                    .min_w(px(180.))
+                    .p(px(4.))
+                    .flex()
+                    .flex_col()
+                    .items_start()
+                    .gap(px(2.))
+                    .rounded(px(8.))
+                    .bg(background_color)
+                    .border(px(1.))
+                    .border_color(border_color)
+                    .on_mouse_down_out(close_menu)
+                    .children(export_buttons),
+            ),
+    )
+    .with_priority(1)
+}
+
+fn render_inactive_chat_row(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    summary: &ChatSummary,
+    list_mode: ListMode,
+) -> AnyElement {
+    let row_id = base_id.with_suffix(format!("inactive_thread_{}", summary.chat_id));
+
+    let restore_button = {
+        let managers = managers.clone();
+        let chat_id = summary.chat_id.clone();
+
+        row_action_button(row_id.with_suffix("restore_btn"), AstrumIconKind::Restore).on_click(
+            move |_event, _window, cx| {
+                let _ = managers.read_blocking().chats.restore_chat(cx, &chat_id);
+            },
+        )
+    };
+
+    let delete_button = {
+        let managers = managers.clone();
+        let chat_id = summary.chat_id.clone();
+
+        row_action_button(row_id.with_suffix("delete_btn"), AstrumIconKind::Trash)
+            .variant(ButtonVariant::DestructiveGhost)
+            .on_click(move |_event, _window, cx| {
+                let managers = managers.read_blocking();
+                let _ = match list_mode {
+                    ListMode::Trash => managers.chats.delete_chat_permanently(cx, &chat_id),
+                    _ => managers.chats.delete_chat(cx, &chat_id),
+                };
+            })
+    };
+
+    div()
+        .w_full()
+        .flex()
+        .flex_row()
+        .items_center()
+        .gap(px(2.))
+        .child(
+            div().flex_1().min_w_0().child(
+                Toggle::new(row_id)
+                    .text(summary.title.replace("\n", " ").replace("  ", " "))
+                    .variant(ToggleVariant::Secondary)
+                    .icon(match list_mode {
+                        ListMode::Trash => AstrumIconKind::Trash,
+                        _ => AstrumIconKind::Archive,
+                    })
+                    .disabled(true)
+                    .justify_start(),
+            ),
+        )
+        .child(restore_button)
+        .child(delete_button)
+        .into_any_element()
+}
+
+fn render_section_caption(caption: &'static str, cx: &App) -> impl IntoElement {
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    div()
+        .pt(px(10.))
+        .px(px(4.))
+        .text_size(caption_size)
+        .text_color(secondary_text_color)
+        .child(caption)
+}
+
+/// Renders a chat whose messages matched the search query, with a snippet for each match.
+fn render_message_search_result(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    result: &ChatSearchResult,
+    cx: &App,
+) -> impl IntoElement {
+    let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let hover_bg_color = cx
+        .get_theme()
+        .variants
+        .active(cx)
+        .colors
+        .background
+        .secondary;
+    let body_size = cx.get_theme().layout.text.default_font.sizes.body;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    let highlight_style = HighlightStyle {
+        color: Some(primary_text_color.into()),
+        font_weight: Some(FontWeight::BOLD),
+        ..Default::default()
+    };
+
+    div()
+        .w_full()
+        .flex()
+        .flex_col()
+        .gap(px(2.))
+        .child(
+            div()
+                .px(px(8.))
+                .pt(px(4.))
+                .text_size(body_size)
+                .text_color(primary_text_color)
+                .truncate()
+                .child(result.title.replace("\n", " ")),
+        )
+        .children(
+            result
+                .matches
+                .iter()
+                .take(MESSAGE_MATCHES_PER_CHAT)
+                .map(|message_match| {
+                    let managers = managers.clone();
+                    let chat_id = result.chat_id.clone();
+                    let message_id = message_match.message_id.clone();
+
+                    div()
+                        .id(base_id.with_suffix(format!("message_result_{}", message_id)))
+                        .w_full()
+                        .px(px(8.))
+                        .py(px(4.))
+                        .rounded(px(6.))
+                        .cursor_pointer()
+                        .hover(|this| this.bg(hover_bg_color))
+                        .text_size(caption_size)
+                        .text_color(secondary_text_color)
+                        .line_clamp(2)
+                        .child(
+                            StyledText::new(message_match.snippet.clone()).with_highlights(
+                                message_match
+                                    .highlights
+                                    .iter()
+                                    .map(|range| (range.clone(), highlight_style)),
+                            ),
+                        )
+                        .on_click(move |_event, _window, cx| {
+                            let _ = managers.read_blocking().chats.open_message(
+                                cx,
+                                &chat_id,
+                                &message_id,
+                            );
+                        })
+                }),
+        )
+}
+
+fn render_trash_header(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    cx: &App,
+) -> impl IntoElement {
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    let managers = managers.clone();
+
+    div()
+        .w_full()
+        .flex()
+        .flex_col()
+        .gap(px(5.))
+        .pb(px(5.))
+        .child(
+            div()
+                .px(px(4.))
+                .text_size(caption_size)
+                .text_color(secondary_text_color)
+                .child(format!(
+                    "Chats in the trash are permanently deleted after {} days.",
+                    TRASH_RETENTION_DAYS
+                )),
+        )
+        .child(
+            Button::new(base_id.with_suffix("empty_trash_btn"))
+                .text("Empty Trash")
+                .variant(ButtonVariant::DestructiveGhost)
+                .justify_start()
+                .child_left(Icon::new(AstrumIconKind::Trash))
+                .on_click(move |_event, _window, cx| {
+                    let _ = managers.read_blocking().chats.empty_trash(cx);
+                }),
+        )
+}
+
+fn row_action_button(id: impl Into<ElementId>, icon: AstrumIconKind) -> Button {
+    Button::new(id)
+        .variant(ButtonVariant::SecondaryGhost)
+        .icon(icon)
+        .icon_size(px(12.))
+        .p(px(6.))
+        .rounded(px(6.))
+}
+
 fn divider(color: impl Into<Fill>) -> impl IntoElement {
     div().w(relative(1.)).h(px(1.)).min_h(px(1.)).bg(color)
 }
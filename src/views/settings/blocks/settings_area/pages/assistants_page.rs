@@ -0,0 +1,504 @@
+use std::sync::Arc;
+
+use gpui::{
+    AnyElement, App, AppContext, ElementId, Entity, Overflow, PointRefinement, SharedString, div,
+    prelude::*, px,
+};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant, Icon, Input, Toggle, ToggleVariant},
+    primitives::{input::InputState, min_w0_wrapper},
+    theme::ThemeExt,
+};
+use smol::lock::RwLock;
+
+use crate::{
+    assets::AstrumIconKind,
+    blocks::ParametersForm,
+    managers::{ASSISTANT_ICONS, Assistant, Managers, UniqueId},
+    views::settings::blocks::settings_area::pages::{
+        render_settings_card, render_settings_page_title,
+    },
+};
+
+/// The assistant being created or edited.
+struct AssistantEditor {
+    /// `None` while creating a new assistant.
+    assistant_id: Option<UniqueId>,
+    name: Entity<InputState>,
+    icon: SharedString,
+    system_prompt: Entity<InputState>,
+    /// The provider id, provider name and model.
+    model: Option<(UniqueId, String, String)>,
+    parameters: ParametersForm,
+    error: Option<SharedString>,
+}
+
+impl AssistantEditor {
+    fn new(assistant_id: Option<UniqueId>, assistant: Assistant, cx: &mut App) -> Self {
+        let model = match (
+            assistant.provider_id,
+            assistant.provider_name,
+            assistant.model,
+        ) {
+            (Some(provider_id), Some(provider_name), Some(model)) => {
+                Some((provider_id, provider_name, model))
+            }
+            _ => None,
+        };
+
+        Self {
+            assistant_id,
+            name: cx.new(|cx| InputState::new(cx).initial_value(assistant.name)),
+            icon: assistant.icon,
+            system_prompt: cx.new(|cx| {
+                InputState::new(cx).initial_value(assistant.system_prompt.unwrap_or_default())
+            }),
+            model,
+            parameters: ParametersForm::new(cx, &assistant.parameters),
+            error: None,
+        }
+    }
+
+    /// Reads the assistant out of the editor, or explains what's wrong with it.
+    fn assistant(&self, cx: &App) -> Result<Assistant, SharedString> {
+        let name = self.name.read(cx).value().trim().to_string();
+        if name.is_empty() {
+            return Err("The assistant needs a name.".into());
+        }
+
+        let system_prompt = self.system_prompt.read(cx).value().trim().to_string();
+        let (provider_id, provider_name, model) = match self.model.clone() {
+            Some((provider_id, provider_name, model)) => {
+                (Some(provider_id), Some(provider_name), Some(model))
+            }
+            None => (None, None, None),
+        };
+
+        Ok(Assistant {
+            name,
+            icon: self.icon.clone(),
+            system_prompt: (!system_prompt.is_empty()).then_some(system_prompt),
+            provider_id,
+            provider_name,
+            model,
+            parameters: self.parameters.parameters(cx)?,
+        })
+    }
+}
+
+#[derive(IntoElement)]
+pub struct AssistantsPage {
+    id: ElementId,
+    managers: Arc<RwLock<Managers>>,
+}
+
+impl AssistantsPage {
+    pub fn new(id: impl Into<ElementId>, managers: Arc<RwLock<Managers>>) -> Self {
+        Self {
+            id: id.into(),
+            managers,
+        }
+    }
+}
+
+impl RenderOnce for AssistantsPage {
+    fn render(self, window: &mut gpui::Window, cx: &mut gpui::App) -> impl IntoElement {
+        let editor_state =
+            window.use_keyed_state(self.id.with_suffix("state:editor"), cx, |_window, _cx| {
+                None::<AssistantEditor>
+            });
+
+        let assistants = self
+            .managers
+            .read_blocking()
+            .assistants
+            .assistants
+            .read(cx)
+            .iter()
+            .map(|(assistant_id, assistant)| (assistant_id.clone(), assistant.clone()))
+            .collect::<Vec<_>>();
+
+        let new_assistant_button = {
+            let editor_state = editor_state.clone();
+
+            Button::new(self.id.with_suffix("new_assistant_btn"))
+                .icon(AstrumIconKind::Plus)
+                .icon_size(px(14.))
+                .text("New assistant")
+                .on_click(move |_event, _window, cx| {
+                    let editor = AssistantEditor::new(None, Assistant::default(), cx);
+                    set_editor(&editor_state, Some(editor), cx);
+                })
+        };
+
+        let content = match editor_state.read(cx).is_some() {
+            true => render_editor(&self.id, &self.managers, &editor_state, cx).into_any_element(),
+            false => render_settings_card(
+                cx,
+                "Assistants",
+                "Presets with their own system prompt, model and parameters. Pick one when starting a new chat.",
+            )
+            .children(assistants.into_iter().map(|(assistant_id, assistant)| {
+                render_assistant_row(
+                    &self.id,
+                    &self.managers,
+                    &editor_state,
+                    assistant_id,
+                    assistant,
+                    cx,
+                )
+            }))
+            .child(div().flex().justify_end().child(new_assistant_button))
+            .into_any_element(),
+        };
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(20.))
+            .child(render_settings_page_title(
+                cx,
+                "Assistants",
+                "Reuse the same instructions, model and parameters across chats.",
+            ))
+            .child(
+                div()
+                    .id(self.id.clone())
+                    .w_full()
+                    .h_full()
+                    .flex()
+                    .flex_col()
+                    .pb(px(20.))
+                    .gap(px(10.))
+                    .map(|mut this| {
+                        this.style().overflow = PointRefinement {
+                            x: None,
+                            y: Some(Overflow::Scroll),
+                        };
+                        this
+                    })
+                    .child(content),
+            )
+    }
+}
+
+fn render_assistant_row(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    editor_state: &Entity<Option<AssistantEditor>>,
+    assistant_id: UniqueId,
+    assistant: Assistant,
+    cx: &App,
+) -> AnyElement {
+    let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let text_body_size = cx.get_theme().layout.text.default_font.sizes.body;
+    let caption_size = cx.get_theme().layout.text.default_font.sizes.caption;
+
+    let id = base_id.with_suffix(format!("assistant_{}", assistant_id));
+
+    let model = match (&assistant.provider_name, &assistant.model) {
+        (Some(provider_name), Some(model)) => {
+            format!("{}/{}", provider_name.to_lowercase(), model)
+        }
+        _ => String::from("Uses the selected model"),
+    };
+
+    let edit_button = {
+        let editor_state = editor_state.clone();
+        let assistant_id = assistant_id.clone();
+        let assistant = assistant.clone();
+
+        Button::new(id.with_suffix("edit_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .icon(AstrumIconKind::Edit)
+            .icon_size(px(12.))
+            .on_click(move |_event, _window, cx| {
+                let editor =
+                    AssistantEditor::new(Some(assistant_id.clone()), assistant.clone(), cx);
+                set_editor(&editor_state, Some(editor), cx);
+            })
+    };
+
+    let delete_button = {
+        let managers = managers.clone();
+
+        Button::new(id.with_suffix("delete_btn"))
+            .variant(ButtonVariant::DestructiveGhost)
+            .icon(AstrumIconKind::Trash)
+            .icon_size(px(12.))
+            .on_click(move |_event, _window, cx| {
+                let result = managers
+                    .read_blocking()
+                    .assistants
+                    .delete_assistant(cx, &assistant_id);
+
+                if let Err(err) = result {
+                    tracing::error!("failed to delete assistant: {err}");
+                }
+            })
+    };
+
+    div()
+        .w_full()
+        .flex()
+        .flex_row()
+        .items_center()
+        .gap(px(10.))
+        .child(
+            Icon::new(assistant.icon.clone())
+                .size(px(16.))
+                .color(secondary_text_color),
+        )
+        .child(
+            div()
+                .flex_1()
+                .min_w_0()
+                .flex()
+                .flex_col()
+                .child(
+                    min_w0_wrapper()
+                        .text_size(text_body_size)
+                        .text_color(primary_text_color)
+                        .child(assistant.name),
+                )
+                .child(
+                    min_w0_wrapper()
+                        .text_size(caption_size)
+                        .text_color(secondary_text_color)
+                        .child(model),
+                ),
+        )
+        .child(edit_button)
+        .child(delete_button)
+        .into_any_element()
+}
+
+fn render_editor(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    editor_state: &Entity<Option<AssistantEditor>>,
+    cx: &App,
+) -> impl IntoElement {
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let text_body_size = cx.get_theme().layout.text.default_font.sizes.body;
+
+    let Some(editor) = editor_state.read(cx).as_ref() else {
+        return div();
+    };
+
+    let id = base_id.with_suffix("editor");
+
+    let label = |text: &'static str| {
+        min_w0_wrapper()
+            .text_size(text_body_size)
+            .text_color(secondary_text_color)
+            .child(text)
+    };
+
+    let icon_picker = div().flex().flex_row().flex_wrap().gap(px(5.)).children(
+        ASSISTANT_ICONS.iter().enumerate().map(|(idx, icon)| {
+            let editor_state = editor_state.clone();
+            let icon: SharedString = icon.into();
+
+            Toggle::new(id.with_suffix(format!("icon_{idx}")))
+                .icon(icon.clone())
+                .variant(ToggleVariant::Secondary)
+                .checked(editor.icon == icon)
+                .on_click(move |_checked, _window, cx| {
+                    update_editor(&editor_state, cx, |editor| editor.icon = icon.clone());
+                })
+        }),
+    );
+
+    let model_text = match &editor.model {
+        Some((_, provider_name, model)) => format!("{}/{}", provider_name.to_lowercase(), model),
+        None => String::from("Uses the model selected in the chat"),
+    };
+
+    let use_current_model_button = {
+        let managers = managers.clone();
+        let editor_state = editor_state.clone();
+
+        Button::new(id.with_suffix("use_current_model_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .text("Use selected model")
+            .on_click(move |_event, _window, cx| {
+                let current_model = {
+                    let managers = managers.read_blocking();
+                    let current_model = &managers.models.current_model;
+
+                    match (
+                        current_model.provider_id.read(cx).clone(),
+                        current_model.provider_name.read(cx).clone(),
+                        current_model.model.read(cx).clone(),
+                    ) {
+                        (Some(provider_id), Some(provider_name), Some(model)) => {
+                            Some((provider_id, provider_name, model))
+                        }
+                        _ => None,
+                    }
+                };
+
+                update_editor(&editor_state, cx, |editor| editor.model = current_model);
+            })
+    };
+
+    let clear_model_button = {
+        let editor_state = editor_state.clone();
+
+        Button::new(id.with_suffix("clear_model_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .text("Clear")
+            .disabled(editor.model.is_none())
+            .on_click(move |_event, _window, cx| {
+                update_editor(&editor_state, cx, |editor| editor.model = None);
+            })
+    };
+
+    let cancel_button = {
+        let editor_state = editor_state.clone();
+
+        Button::new(id.with_suffix("cancel_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .text("Cancel")
+            .on_click(move |_event, _window, cx| set_editor(&editor_state, None, cx))
+    };
+
+    let save_button = {
+        let managers = managers.clone();
+        let editor_state = editor_state.clone();
+
+        Button::new(id.with_suffix("save_btn"))
+            .text("Save")
+            .on_click(move |_event, _window, cx| save_editor(&managers, &editor_state, cx))
+    };
+
+    render_settings_card(
+        cx,
+        match editor.assistant_id {
+            Some(_) => "Edit assistant",
+            None => "New assistant",
+        },
+        "Chats started from this assistant copy its system prompt, and use its model and parameters.",
+    )
+    .child(label("Name"))
+    .child(Input::new(id.with_suffix("name_input"), editor.name.clone()).w_full())
+    .child(label("Icon"))
+    .child(icon_picker)
+    .child(label("System prompt"))
+    .child(
+        Input::new(
+            id.with_suffix("system_prompt_input"),
+            editor.system_prompt.clone(),
+        )
+        .w_full()
+        .line_clamp(8)
+        .word_wrap(true)
+        .placeholder("You are a helpful assistant."),
+    )
+    .child(label("Model"))
+    .child(
+        div()
+            .w_full()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap(px(5.))
+            .child(
+                min_w0_wrapper()
+                    .flex_1()
+                    .text_size(text_body_size)
+                    .child(model_text),
+            )
+            .child(use_current_model_button)
+            .child(clear_model_button),
+    )
+    .child(label("Parameters"))
+    .child(editor.parameters.render(&id, cx))
+    .children(editor.error.clone().map(|error| {
+        min_w0_wrapper()
+            .text_size(text_body_size)
+            .text_color(secondary_text_color)
+            .child(error)
+    }))
+    .child(
+        div()
+            .w_full()
+            .flex()
+            .flex_row()
+            .justify_end()
+            .gap(px(5.))
+            .child(cancel_button)
+            .child(save_button),
+    )
+}
+
+fn save_editor(
+    managers: &Arc<RwLock<Managers>>,
+    editor_state: &Entity<Option<AssistantEditor>>,
+    cx: &mut App,
+) {
+    let Some((assistant_id, assistant)) = editor_state
+        .read(cx)
+        .as_ref()
+        .map(|editor| (editor.assistant_id.clone(), editor.assistant(cx)))
+    else {
+        return;
+    };
+
+    let assistant = match assistant {
+        Ok(assistant) => assistant,
+        Err(error) => {
+            update_editor(editor_state, cx, |editor| editor.error = Some(error));
+            return;
+        }
+    };
+
+    let managers = managers.read_blocking();
+
+    let result = match assistant_id {
+        Some(assistant_id) => managers
+            .assistants
+            .update_assistant(cx, &assistant_id, assistant),
+        None => managers
+            .assistants
+            .create_assistant(cx, assistant)
+            .map(|_| ()),
+    };
+
+    match result {
+        Ok(()) => set_editor(editor_state, None, cx),
+        Err(err) => {
+            tracing::error!("failed to save assistant: {err}");
+            update_editor(editor_state, cx, |editor| {
+                editor.error = Some("The assistant couldn't be saved.".into())
+            });
+        }
+    }
+}
+
+fn set_editor(
+    editor_state: &Entity<Option<AssistantEditor>>,
+    editor: Option<AssistantEditor>,
+    cx: &mut App,
+) {
+    editor_state.update(cx, |editor_state, cx| {
+        *editor_state = editor;
+        cx.notify();
+    });
+}
+
+fn update_editor(
+    editor_state: &Entity<Option<AssistantEditor>>,
+    cx: &mut App,
+    update: impl FnOnce(&mut AssistantEditor),
+) {
+    editor_state.update(cx, |editor_state, cx| {
+        if let Some(editor) = editor_state {
+            update(editor);
+            cx.notify();
+        }
+    });
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashSet, sync::Arc};
+
+use gpui::{AnyElement, App, ElementId, Entity, Overflow, PointRefinement, div, prelude::*, px};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant, Toggle, ToggleVariant},
+    primitives::min_w0_wrapper,
+    theme::ThemeExt,
+};
+use smol::lock::RwLock;
+
+use crate::{
+    assets::AstrumIconKind,
+    managers::{ExportFormat, Managers, UniqueId, save_export_files},
+    views::settings::blocks::settings_area::pages::{
+        render_settings_card, render_settings_page_title,
+    },
+};
+
+#[derive(IntoElement)]
+pub struct ExportPage {
+    id: ElementId,
+    managers: Arc<RwLock<Managers>>,
+}
+
+impl ExportPage {
+    pub fn new(id: impl Into<ElementId>, managers: Arc<RwLock<Managers>>) -> Self {
+        Self {
+            id: id.into(),
+            managers,
+        }
+    }
+}
+
+impl RenderOnce for ExportPage {
+    fn render(self, window: &mut gpui::Window, cx: &mut gpui::App) -> impl IntoElement {
+        let format_state =
+            window.use_keyed_state(self.id.with_suffix("state:format"), cx, |_window, _cx| {
+                ExportFormat::Markdown
+            });
+
+        let selection_state = window.use_keyed_state(
+            self.id.with_suffix("state:selection"),
+            cx,
+            |_window, _cx| HashSet::<UniqueId>::new(),
+        );
+
+        // Archived chats can be exported too, but trashed ones can't.
+        let exportable_chats = {
+            let managers = self.managers.read_blocking();
+
+            let mut chats = managers
+                .chats
+                .chats_iter(cx)
+                .map(|chats| {
+                    chats
+                        .map(|chat| (chat.chat_id.clone(), chat.title.read(cx).clone()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            chats.extend(
+                managers
+                    .chats
+                    .archived_chats
+                    .read(cx)
+                    .iter()
+                    .map(|summary| (summary.chat_id.clone(), summary.title.clone())),
+            );
+
+            chats
+        };
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(20.))
+            .child(render_settings_page_title(
+                cx,
+                "Export",
+                "Save chats as Markdown, lossless JSON or standalone HTML files.",
+            ))
+            .child(
+                div()
+                    .id(self.id.clone())
+                    .w_full()
+                    .h_full()
+                    .flex()
+                    .flex_col()
+                    .pb(px(20.))
+                    .gap(px(10.))
+                    .map(|mut this| {
+                        this.style().overflow = PointRefinement {
+                            x: None,
+                            y: Some(Overflow::Scroll),
+                        };
+                        this
+                    })
+                    .child(render_format_picker(&self.id, format_state.clone(), cx))
+                    .child(render_chat_picker(
+                        &self.id,
+                        &self.managers,
+                        &exportable_chats,
+                        format_state,
+                        selection_state,
+                        cx,
+                    )),
+            )
+    }
+}
+
+fn render_format_picker(
+    base_id: &ElementId,
+    format_state: Entity<ExportFormat>,
+    cx: &App,
+) -> impl IntoElement {
+    let current_format = *format_state.read(cx);
+
+    render_settings_card(
+        cx,
+        "Format",
+        "Markdown and HTML contain the branch that's shown in each chat. JSON keeps every branch and the metadata of each message.",
+    )
+    .child(
+        div()
+            .flex()
+            .flex_row()
+            .gap(px(5.))
+            .children(ExportFormat::ALL.map(|format| {
+                let format_state = format_state.clone();
+
+                Toggle::new(base_id.with_suffix("format").with_suffix(format.extension()))
+                    .text(format.label())
+                    .variant(ToggleVariant::Secondary)
+                    .checked(current_format == format)
+                    .on_click(move |_checked, _window, cx| {
+                        format_state.update(cx, |current_format, cx| {
+                            *current_format = format;
+                            cx.notify();
+                        });
+                    })
+            })),
+    )
+}
+
+fn render_chat_picker(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    exportable_chats: &[(UniqueId, String)],
+    format_state: Entity<ExportFormat>,
+    selection_state: Entity<HashSet<UniqueId>>,
+    cx: &App,
+) -> impl IntoElement {
+    let selection = selection_state.read(cx);
+    let selected_ids = exportable_chats
+        .iter()
+        .map(|(chat_id, _)| chat_id)
+        .filter(|chat_id| selection.contains(chat_id))
+        .cloned()
+        .collect::<Vec<_>>();
+    let all_selected = !exportable_chats.is_empty() && selected_ids.len() == exportable_chats.len();
+
+    let select_all_button = {
+        let selection_state = selection_state.clone();
+        let all_ids = exportable_chats
+            .iter()
+            .map(|(chat_id, _)| chat_id.clone())
+            .collect::<Vec<_>>();
+
+        Button::new(base_id.with_suffix("select_all_btn"))
+            .variant(ButtonVariant::SecondaryGhost)
+            .text(if all_selected {
+                "Deselect all"
+            } else {
+                "Select all"
+            })
+            .disabled(exportable_chats.is_empty())
+            .on_click(move |_event, _window, cx| {
+                selection_state.update(cx, |selection, cx| {
+                    match all_selected {
+                        true => selection.clear(),
+                        false => selection.extend(all_ids.iter().cloned()),
+                    }
+                    cx.notify();
+                });
+            })
+    };
+
+    let export_button = {
+        let managers = managers.clone();
+        let export_label = match selected_ids.len() {
+            1 => String::from("Export 1 chat"),
+            count => format!("Export {count} chats"),
+        };
+        let is_empty = selected_ids.is_empty();
+
+        Button::new(base_id.with_suffix("export_btn"))
+            .icon(AstrumIconKind::Download)
+            .icon_size(px(14.))
+            .text(export_label)
+            .disabled(is_empty)
+            .on_click(move |_event, _window, cx| {
+                let format = *format_state.read(cx);
+                let files = managers
+                    .read_blocking()
+                    .chats
+                    .export_chats(cx, &selected_ids, format);
+
+                match files {
+                    Ok(files) => save_export_files(cx, files),
+                    Err(err) => tracing::error!("failed to export chats: {err}"),
+                }
+            })
+    };
+
+    render_settings_card(
+        cx,
+        "Chats",
+        "Choose the chats to export. Each chat is saved to its own file.",
+    )
+    .child(div().w_full().flex().flex_col().gap(px(2.)).map(|this| {
+        match exportable_chats.is_empty() {
+            true => this.child(
+                min_w0_wrapper()
+                    .text_size(cx.get_theme().layout.text.default_font.sizes.body)
+                    .text_color(cx.get_theme().variants.active(cx).colors.text.secondary)
+                    .child("There are no chats to export."),
+            ),
+            false => this.children(exportable_chats.iter().map(|(chat_id, title)| {
+                render_chat_toggle(
+                    base_id,
+                    selection_state.clone(),
+                    chat_id.clone(),
+                    title,
+                    selection.contains(chat_id),
+                )
+            })),
+        }
+    }))
+    .child(
+        div()
+            .w_full()
+            .flex()
+            .flex_row()
+            .justify_between()
+            .child(select_all_button)
+            .child(export_button),
+    )
+}
+
+fn render_chat_toggle(
+    base_id: &ElementId,
+    selection_state: Entity<HashSet<UniqueId>>,
+    chat_id: UniqueId,
+    title: &str,
+    is_selected: bool,
+) -> AnyElement {
+    Toggle::new(base_id.with_suffix(format!("chat_{}", chat_id)))
+        .text(title.replace("\n", " ").replace("  ", " "))
+        .variant(ToggleVariant::Secondary)
+        .icon(AstrumIconKind::Chat)
+        .checked(is_selected)
+        .on_click(move |_checked, _window, cx| {
+            selection_state.update(cx, |selection, cx| {
+                if !selection.remove(&chat_id) {
+                    selection.insert(chat_id.clone());
+                }
+                cx.notify();
+            });
+        })
+        .justify_start()
+        .into_any_element()
+}
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use gpui::{
+    App, AsyncApp, ElementId, Entity, Overflow, PathPromptOptions, PointRefinement, div,
+    prelude::*, px,
+};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant},
+    primitives::min_w0_wrapper,
+    theme::ThemeExt,
+};
+use smol::lock::RwLock;
+
+use crate::{
+    assets::AstrumIconKind,
+    managers::{ImportSummary, Managers, ParsedImport, parse_import},
+    views::settings::blocks::settings_area::pages::{
+        render_settings_card, render_settings_page_title,
+    },
+};
+
+/// Where the import is at. Nothing is written until a previewed import is confirmed.
+#[derive(Clone)]
+enum ImportState {
+    Idle,
+    Reading,
+    Preview {
+        import: Arc<ParsedImport>,
+        summary: ImportSummary,
+    },
+    Imported(ImportSummary),
+    Failed(String),
+}
+
+#[derive(IntoElement)]
+pub struct ImportPage {
+    id: ElementId,
+    managers: Arc<RwLock<Managers>>,
+}
+
+impl ImportPage {
+    pub fn new(id: impl Into<ElementId>, managers: Arc<RwLock<Managers>>) -> Self {
+        Self {
+            id: id.into(),
+            managers,
+        }
+    }
+}
+
+impl RenderOnce for ImportPage {
+    fn render(self, window: &mut gpui::Window, cx: &mut gpui::App) -> impl IntoElement {
+        let import_state =
+            window.use_keyed_state(self.id.with_suffix("state:import"), cx, |_window, _cx| {
+                ImportState::Idle
+            });
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(20.))
+            .child(render_settings_page_title(
+                cx,
+                "Import",
+                "Bring your chat history over from other apps.",
+            ))
+            .child(
+                div()
+                    .id(self.id.clone())
+                    .w_full()
+                    .h_full()
+                    .flex()
+                    .flex_col()
+                    .pb(px(20.))
+                    .gap(px(10.))
+                    .map(|mut this| {
+                        this.style().overflow = PointRefinement {
+                            x: None,
+                            y: Some(Overflow::Scroll),
+                        };
+                        this
+                    })
+                    .child(render_import_card(
+                        &self.id,
+                        &self.managers,
+                        import_state,
+                        cx,
+                    )),
+            )
+    }
+}
+
+fn render_import_card(
+    base_id: &ElementId,
+    managers: &Arc<RwLock<Managers>>,
+    import_state: Entity<ImportState>,
+    cx: &App,
+) -> impl IntoElement {
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let text_body_size = cx.get_theme().layout.text.default_font.sizes.body;
+
+    let status_text = |text: String| {
+        min_w0_wrapper()
+            .text_size(text_body_size)
+            .text_color(secondary_text_color)
+            .child(text)
+    };
+
+    let choose_file_button = {
+        let managers = managers.clone();
+        let import_state = import_state.clone();
+        let is_reading = matches!(import_state.read(cx), ImportState::Reading);
+
+        Button::new(base_id.with_suffix("choose_file_btn"))
+            .icon(AstrumIconKind::Upload)
+            .icon_size(px(14.))
+            .text("Choose file…")
+            .disabled(is_reading)
+            .on_click(move |_event, _window, cx| {
+                choose_export_file(managers.clone(), import_state.clone(), cx)
+            })
+    };
+
+    let card = render_settings_card(
+        cx,
+        "Chat history",
+        "Choose the conversations.json file from a ChatGPT or Claude data export, or a JSON export from Open WebUI. Chats that were imported before are skipped.",
+    );
+
+    match import_state.read(cx).clone() {
+        ImportState::Idle => card.child(choose_file_button),
+        ImportState::Reading => card.child(status_text(String::from("Reading export…"))),
+        ImportState::Failed(message) => card.child(status_text(message)).child(choose_file_button),
+        ImportState::Imported(summary) => card
+            .child(status_text(format!(
+                "Imported {} from {}.",
+                count_chats_and_messages(&summary),
+                summary.source.label()
+            )))
+            .child(choose_file_button),
+        ImportState::Preview { import, summary } => {
+            let mut preview = format!(
+                "This {} export contains {}.",
+                summary.source.label(),
+                count_chats_and_messages(&summary)
+            );
+            if summary.duplicates > 0 {
+                preview.push_str(&format!(
+                    " {} already imported and will be skipped.",
+                    plural(summary.duplicates, "chat was", "chats were")
+                ));
+            }
+
+            let cancel_button = {
+                let import_state = import_state.clone();
+
+                Button::new(base_id.with_suffix("cancel_btn"))
+                    .variant(ButtonVariant::SecondaryGhost)
+                    .text("Cancel")
+                    .on_click(move |_event, _window, cx| {
+                        set_import_state(&import_state, ImportState::Idle, cx);
+                    })
+            };
+
+            let import_button = {
+                let managers = managers.clone();
+
+                Button::new(base_id.with_suffix("import_btn"))
+                    .icon(AstrumIconKind::Upload)
+                    .icon_size(px(14.))
+                    .text(format!("Import {}", plural(summary.chats, "chat", "chats")))
+                    .disabled(summary.chats == 0)
+                    .on_click(move |_event, _window, cx| {
+                        let result = managers.read_blocking().chats.import_chats(cx, &import);
+
+                        let new_state = match result {
+                            Ok(summary) => ImportState::Imported(summary),
+                            Err(err) => {
+                                tracing::error!("failed to import chats: {err}");
+                                ImportState::Failed(String::from(
+                                    "The chats couldn't be imported. Nothing was changed.",
+                                ))
+                            }
+                        };
+                        set_import_state(&import_state, new_state, cx);
+                    })
+            };
+
+            card.child(status_text(preview)).child(
+                div()
+                    .w_full()
+                    .flex()
+                    .flex_row()
+                    .justify_end()
+                    .gap(px(5.))
+                    .child(cancel_button)
+                    .child(import_button),
+            )
+        }
+    }
+}
+
+/// Asks for an export file, then parses it and previews what importing it would do.
+fn choose_export_file(
+    managers: Arc<RwLock<Managers>>,
+    import_state: Entity<ImportState>,
+    cx: &mut App,
+) {
+    let paths = cx.prompt_for_paths(PathPromptOptions {
+        files: true,
+        directories: false,
+        multiple: false,
+        prompt: Some("Import".into()),
+    });
+
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        let Ok(Ok(Some(paths))) = paths.await else {
+            return;
+        };
+        let Some(path) = paths.into_iter().next() else {
+            return;
+        };
+
+        let _ = import_state.update(cx, |state, cx| {
+            *state = ImportState::Reading;
+            cx.notify();
+        });
+
+        let parsed = cx
+            .background_executor()
+            .spawn(async move {
+                let contents = smol::fs::read_to_string(&path).await.map_err(|err| {
+                    tracing::error!("failed to read {}: {err}", path.display());
+                    String::from("The file couldn't be read.")
+                })?;
+
+                parse_import(&contents).map_err(|err| err.to_string())
+            })
+            .await;
+
+        let new_state = match parsed {
+            Ok(import) => match managers.read().await.chats.preview_import(&import) {
+                Ok(summary) => ImportState::Preview {
+                    import: Arc::new(import),
+                    summary,
+                },
+                Err(err) => {
+                    tracing::error!("failed to preview import: {err}");
+                    ImportState::Failed(String::from(
+                        "The export couldn't be checked against your chats.",
+                    ))
+                }
+            },
+            Err(message) => ImportState::Failed(message),
+        };
+
+        let _ = import_state.update(cx, |state, cx| {
+            *state = new_state;
+            cx.notify();
+        });
+    })
+    .detach();
+}
+
+fn set_import_state(import_state: &Entity<ImportState>, new_state: ImportState, cx: &mut App) {
+    import_state.update(cx, |state, cx| {
+        *state = new_state;
+        cx.notify();
+    });
+}
+
+fn count_chats_and_messages(summary: &ImportSummary) -> String {
+    format!(
+        "{} with {}",
+        plural(summary.chats, "new chat", "new chats"),
+        plural(summary.messages, "message", "messages")
+    )
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    match count {
+        1 => format!("1 {singular}"),
+        count => format!("{count} {plural}"),
+    }
+}
@@ -1,10 +1,16 @@
-use gpui_tesserae::{ElementIdExt, theme::ThemeExt};
+use gpui_squircle::{SquircleStyled, squircle};
+use gpui_tesserae::{
+    ElementIdExt,
+    primitives::min_w0_wrapper,
+    theme::{ThemeExt, ThemeLayerKind},
+};
 use phf::phf_map;
 use smol::lock::RwLock;
 use std::sync::Arc;
 
 use gpui::{
-    AnyElement, App, ElementId, IntoElement, ParentElement, SharedString, Styled, div, px, relative,
+    AnyElement, App, Div, ElementId, IntoElement, ParentElement, SharedString, Styled, div, px,
+    relative,
 };
 
 mod providers_page;
@@ -13,6 +19,18 @@ pub use providers_page::*;
 mod chat_titles_page;
 pub use chat_titles_page::*;
 
+mod assistants_page;
+pub use assistants_page::*;
+
+mod system_prompt_page;
+pub use system_prompt_page::*;
+
+mod export_page;
+pub use export_page::*;
+
+mod import_page;
+pub use import_page::*;
+
 use crate::managers::Managers;
 
 const SETTING_PAGES: phf::Map<&str, fn(ElementId, Arc<RwLock<Managers>>) -> AnyElement> = phf_map! {
@@ -21,6 +39,18 @@ const SETTING_PAGES: phf::Map<&str, fn(ElementId, Arc<RwLock<Managers>>) -> AnyE
     },
     "Chat Titles" => |id, managers| {
         ChatTitlesPage::new(id, managers).into_any_element()
+    },
+    "Assistants" => |id, managers| {
+        AssistantsPage::new(id, managers).into_any_element()
+    },
+    "System Prompt" => |id, managers| {
+        SystemPromptPage::new(id, managers).into_any_element()
+    },
+    "Export" => |id, managers| {
+        ExportPage::new(id, managers).into_any_element()
+    },
+    "Import" => |id, managers| {
+        ImportPage::new(id, managers).into_any_element()
     }
 };
 
@@ -97,3 +127,57 @@ pub fn render_settings_page_title(
                 .child(description.into()),
         )
 }
+
+/// A card with a title and description, for grouping the settings of a page.
+pub fn render_settings_card(
+    cx: &App,
+    title: impl Into<SharedString>,
+    description: impl Into<SharedString>,
+) -> Div {
+    let layer_kind = ThemeLayerKind::Tertiary;
+    let background_color = layer_kind.resolve(cx);
+    let border_color = layer_kind.next().resolve(cx);
+    let primary_text_color = cx.get_theme().variants.active(cx).colors.text.primary;
+    let secondary_text_color = cx.get_theme().variants.active(cx).colors.text.secondary;
+    let text_heading_sm_size = cx.get_theme().layout.text.default_font.sizes.heading_sm;
+    let text_body_size = cx.get_theme().layout.text.default_font.sizes.body;
+    let corner_radius = cx.get_theme().layout.corner_radii.lg;
+    let padding = cx.get_theme().layout.padding.xl;
+
+    div()
+        .w_full()
+        .h_auto()
+        .flex()
+        .flex_col()
+        .p(padding)
+        .gap(padding)
+        .child(
+            squircle()
+                .absolute_expand()
+                .bg(background_color)
+                .border(px(1.))
+                .border_color(border_color)
+                .border_inside()
+                .rounded(corner_radius),
+        )
+        .child(
+            div()
+                .w_full()
+                .flex()
+                .flex_col()
+                .gap(padding / 2.)
+                .child(
+                    min_w0_wrapper()
+                        .text_size(text_heading_sm_size)
+                        .text_color(primary_text_color)
+                        .line_height(relative(1.))
+                        .child(title.into()),
+                )
+                .child(
+                    min_w0_wrapper()
+                        .text_size(text_body_size)
+                        .text_color(secondary_text_color)
+                        .child(description.into()),
+                ),
+        )
+}
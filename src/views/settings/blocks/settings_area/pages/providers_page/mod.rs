@@ -52,6 +52,9 @@ impl RenderOnce for ProvidersPage {
                 map.push_item(cx, "Ollama");
                 map.push_item(cx, "OpenAI");
                 map.push_item(cx, "Anthropic");
+                map.push_item(cx, "Gemini");
+                map.push_item(cx, "Azure OpenAI");
+                map.push_item(cx, "OpenAI-compatible");
 
                 map
             },
@@ -63,6 +66,9 @@ impl RenderOnce for ProvidersPage {
                 "Ollama" => ProviderKind::Ollama,
                 "OpenAI" => ProviderKind::OpenAi,
                 "Anthropic" => ProviderKind::Anthropic,
+                "Gemini" => ProviderKind::Gemini,
+                "Azure OpenAI" => ProviderKind::AzureOpenAi,
+                "OpenAI-compatible" => ProviderKind::OpenAiCompatible,
                 _ => return,
             };
 
@@ -81,7 +87,7 @@ impl RenderOnce for ProvidersPage {
 
             if let Ok(provider_id) = provider_id {
                 // Fetch models for the newly created provider
-                refetch_provider_models(
+                let _ = refetch_provider_models(
                     managers.clone(),
                     provider_id,
                     ProviderConfigChange::Create,
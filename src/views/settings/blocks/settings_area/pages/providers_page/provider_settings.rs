@@ -1,8 +1,8 @@
 use std::{sync::Arc, time::Duration};
 
 use gpui::{
-    App, Div, ElementId, Entity, Fill, Focusable, FontWeight, div, ease_out_quint, img, prelude::*,
-    px, radians, relative,
+    App, Div, ElementId, Entity, Fill, Focusable, FontWeight, SharedString, Window, div,
+    ease_out_quint, img, prelude::*, px, radians, relative,
 };
 use gpui_squircle::{SquircleStyled, squircle};
 use gpui_tesserae::{
@@ -19,23 +19,44 @@ use smol::lock::RwLock;
 use crate::{
     assets::AstrumIconKind,
     blocks::models_menu::{ProviderConfigChange, refetch_provider_models},
-    managers::{Managers, Provider, UniqueId},
+    managers::{
+        DEFAULT_AZURE_API_VERSION, DbError, Managers, Provider, ProviderKind, ProviderOptions,
+        ProviderParam, ProviderParams, UniqueId,
+    },
     views::settings::blocks::settings_area::pages::providers_page::QueryBounds,
 };
 
+/// Shows why the last change to a provider couldn't be saved, or clears the error once
+/// one is.
+fn show_save_result(
+    save_error: &Entity<Option<SharedString>>,
+    result: Result<(), DbError>,
+    cx: &mut App,
+) {
+    let error = result.err().map(|err| {
+        tracing::error!("failed to save provider settings: {err}");
+        SharedString::from(format!("The change couldn't be saved. {err}"))
+    });
+
+    save_error.update(cx, |save_error, cx| {
+        *save_error = error;
+        cx.notify();
+    });
+}
+
 fn save_provider_url(
     managers: &Arc<RwLock<Managers>>,
     provider_id: &UniqueId,
     url_input_state: &Entity<InputState>,
     cx: &mut App,
-) {
+) -> Result<(), DbError> {
     let new_url = url_input_state.read(cx).value().to_string();
     refetch_provider_models(
         managers.clone(),
         provider_id.clone(),
         ProviderConfigChange::Url(new_url),
         cx,
-    );
+    )
 }
 
 fn save_provider_api_key(
@@ -43,7 +64,7 @@ fn save_provider_api_key(
     provider_id: &UniqueId,
     api_key_input_state: &Entity<InputState>,
     cx: &mut App,
-) {
+) -> Result<(), DbError> {
     let new_api_key = api_key_input_state.read(cx).value().to_string();
     let api_key = if new_api_key.is_empty() {
         None
@@ -55,7 +76,174 @@ fn save_provider_api_key(
         provider_id.clone(),
         ProviderConfigChange::ApiKey(api_key),
         cx,
-    );
+    )
+}
+
+fn save_provider_params(
+    managers: &Arc<RwLock<Managers>>,
+    provider_id: &UniqueId,
+    params_input_states: &ParamsInputStates,
+    cx: &mut App,
+) -> Result<(), DbError> {
+    refetch_provider_models(
+        managers.clone(),
+        provider_id.clone(),
+        ProviderConfigChange::Params(params_input_states.params(cx)),
+        cx,
+    )
+}
+
+/// The inputs for the headers and query parameters of a provider, with the ones whose
+/// values are kept in the keyring apart from the rest.
+#[derive(Clone)]
+struct ParamsInputStates {
+    headers: Entity<InputState>,
+    secret_headers: Entity<InputState>,
+    query: Entity<InputState>,
+    secret_query: Entity<InputState>,
+}
+
+impl ParamsInputStates {
+    fn new(id: &ElementId, params: &ProviderParams, window: &mut Window, cx: &mut App) -> Self {
+        let mut use_input_state = |suffix: &str, text: String| {
+            window.use_keyed_state(id.with_suffix(suffix), cx, |_window, cx| {
+                InputState::new(cx).initial_value(text)
+            })
+        };
+
+        Self {
+            headers: use_input_state(
+                "state:headers_input",
+                format_params(&params.headers, HEADER_SEPARATOR, false),
+            ),
+            secret_headers: use_input_state(
+                "state:secret_headers_input",
+                format_params(&params.headers, HEADER_SEPARATOR, true),
+            ),
+            query: use_input_state(
+                "state:query_input",
+                format_params(&params.query, QUERY_SEPARATOR, false),
+            ),
+            secret_query: use_input_state(
+                "state:secret_query_input",
+                format_params(&params.query, QUERY_SEPARATOR, true),
+            ),
+        }
+    }
+
+    fn params(&self, cx: &App) -> ProviderParams {
+        let parse = |state: &Entity<InputState>, separator, secret| {
+            parse_params(&state.read(cx).value(), separator, secret)
+        };
+
+        ProviderParams {
+            headers: [
+                parse(&self.headers, HEADER_SEPARATOR, false),
+                parse(&self.secret_headers, HEADER_SEPARATOR, true),
+            ]
+            .concat(),
+            query: [
+                parse(&self.query, QUERY_SEPARATOR, false),
+                parse(&self.secret_query, QUERY_SEPARATOR, true),
+            ]
+            .concat(),
+        }
+    }
+}
+
+const HEADER_SEPARATOR: &str = ": ";
+const QUERY_SEPARATOR: &str = "=";
+
+/// Reads params written one per line, as `Name: value` for headers or `name=value` for
+/// query parameters. Lines without a name are skipped.
+fn parse_params(text: &str, separator: &str, secret: bool) -> Vec<ProviderParam> {
+    text.lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(separator.trim())?;
+            let name = name.trim();
+
+            (!name.is_empty()).then(|| ProviderParam {
+                id: UniqueId::new(),
+                name: name.to_string(),
+                value: value.trim().to_string(),
+                secret,
+            })
+        })
+        .collect()
+}
+
+fn format_params(params: &[ProviderParam], separator: &str, secret: bool) -> String {
+    params
+        .iter()
+        .filter(|param| param.secret == secret)
+        .map(|param| format!("{}{separator}{}", param.name, param.value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn save_provider_options(
+    managers: &Arc<RwLock<Managers>>,
+    provider_id: &UniqueId,
+    options_input_states: &OptionsInputStates,
+    cx: &mut App,
+) -> Result<(), DbError> {
+    refetch_provider_models(
+        managers.clone(),
+        provider_id.clone(),
+        ProviderConfigChange::Options(options_input_states.options(cx)),
+        cx,
+    )
+}
+
+/// The inputs for the settings that only some kinds of providers have.
+#[derive(Clone)]
+struct OptionsInputStates {
+    api_version: Entity<InputState>,
+    deployments: Entity<InputState>,
+    username: Entity<InputState>,
+}
+
+impl OptionsInputStates {
+    fn new(id: &ElementId, options: &ProviderOptions, window: &mut Window, cx: &mut App) -> Self {
+        let mut use_input_state = |suffix: &str, text: String| {
+            window.use_keyed_state(id.with_suffix(suffix), cx, |_window, cx| {
+                InputState::new(cx).initial_value(text)
+            })
+        };
+
+        Self {
+            api_version: use_input_state(
+                "state:api_version_input",
+                options.api_version.clone().unwrap_or_default(),
+            ),
+            deployments: use_input_state("state:deployments_input", options.deployments.join("\n")),
+            username: use_input_state(
+                "state:username_input",
+                options.username.clone().unwrap_or_default(),
+            ),
+        }
+    }
+
+    fn options(&self, cx: &App) -> ProviderOptions {
+        let value = |state: &Entity<InputState>| {
+            let value = state.read(cx).value().trim().to_string();
+            (!value.is_empty()).then_some(value)
+        };
+
+        ProviderOptions {
+            api_version: value(&self.api_version),
+            deployments: self
+                .deployments
+                .read(cx)
+                .value()
+                .lines()
+                .map(str::trim)
+                .filter(|deployment| !deployment.is_empty())
+                .map(str::to_string)
+                .collect(),
+            username: value(&self.username),
+        }
+    }
 }
 
 #[derive(IntoElement)]
@@ -115,6 +303,25 @@ impl RenderOnce for ProviderSettings {
             },
         );
 
+        let params = self.provider.params.read(cx).clone();
+        let params_input_states = ParamsInputStates::new(&self.id, &params, window, cx);
+
+        let options = self.provider.options.read(cx).clone();
+        let options_input_states = OptionsInputStates::new(&self.id, &options, window, cx);
+
+        let save_error = window.use_keyed_state(
+            self.id.with_suffix("state:save_error"),
+            cx,
+            |_window, _cx| None::<SharedString>,
+        );
+        let save_error_text = save_error.read(cx).clone();
+
+        // Azure serves models from deployments it can't list, under a versioned API.
+        let is_azure = self.provider.kind == ProviderKind::AzureOpenAi;
+
+        // Ollama takes credentials for a reverse proxy in front of it, rather than an API key.
+        let is_ollama = self.provider.kind == ProviderKind::Ollama;
+
         let bottom_section_content_height = window.use_keyed_state(
             self.id.with_suffix("state:settings_height"),
             cx,
@@ -272,6 +479,60 @@ impl RenderOnce for ProviderSettings {
                         .placeholder("*************************")
                         .transform_text(|_| '*');
 
+                        let params_input =
+                            |suffix: &str, state: &Entity<InputState>, placeholder| {
+                                Input::new(self.id.with_suffix(suffix), state.clone())
+                                    .layer(ThemeLayerKind::Quaternary)
+                                    .line_clamp(4)
+                                    .placeholder(placeholder)
+                            };
+                        let headers_input = params_input(
+                            "headers_input",
+                            &params_input_states.headers,
+                            "X-Tenant: acme",
+                        );
+                        // Secret values are masked like the API key, keeping one param a line.
+                        let mask_secret = |char| match char {
+                            '\n' => '\n',
+                            _ => '*',
+                        };
+                        let secret_headers_input = params_input(
+                            "secret_headers_input",
+                            &params_input_states.secret_headers,
+                            "X-Api-Token: ...",
+                        )
+                        .transform_text(mask_secret);
+                        let query_input =
+                            params_input("query_input", &params_input_states.query, "tenant=acme");
+                        let secret_query_input = params_input(
+                            "secret_query_input",
+                            &params_input_states.secret_query,
+                            "token=...",
+                        )
+                        .transform_text(mask_secret);
+
+                        let api_version_input = Input::new(
+                            self.id.with_suffix("api_version_input"),
+                            options_input_states.api_version.clone(),
+                        )
+                        .layer(ThemeLayerKind::Quaternary)
+                        .placeholder(DEFAULT_AZURE_API_VERSION);
+
+                        let deployments_input = Input::new(
+                            self.id.with_suffix("deployments_input"),
+                            options_input_states.deployments.clone(),
+                        )
+                        .layer(ThemeLayerKind::Quaternary)
+                        .line_clamp(4)
+                        .placeholder("gpt-4o-mini");
+
+                        let username_input = Input::new(
+                            self.id.with_suffix("username_input"),
+                            options_input_states.username.clone(),
+                        )
+                        .layer(ThemeLayerKind::Quaternary)
+                        .placeholder("Leave empty to send a bearer token");
+
                         let managers = self.managers.clone();
                         let provider_id = self.provider_id.clone();
 
@@ -283,18 +544,20 @@ impl RenderOnce for ProviderSettings {
                                     let managers = managers.clone();
                                     let provider_id = provider_id.clone();
                                     let url_input_state = url_input_state.clone();
+                                    let save_error = save_error.clone();
 
                                     window
                                         .on_focus_out(
                                             &url_input.focus_handle(cx),
                                             cx,
                                             move |_event, _window, cx| {
-                                                save_provider_url(
+                                                let result = save_provider_url(
                                                     &managers,
                                                     &provider_id,
                                                     &url_input_state,
                                                     cx,
                                                 );
+                                                show_save_result(&save_error, result, cx);
                                             },
                                         )
                                         .detach();
@@ -304,18 +567,73 @@ impl RenderOnce for ProviderSettings {
                                     let managers = managers.clone();
                                     let provider_id = provider_id.clone();
                                     let api_key_input_state = api_key_input_state.clone();
+                                    let save_error = save_error.clone();
 
                                     window
                                         .on_focus_out(
                                             &api_key_input.focus_handle(cx),
                                             cx,
                                             move |_event, _window, cx| {
-                                                save_provider_api_key(
+                                                let result = save_provider_api_key(
                                                     &managers,
                                                     &provider_id,
                                                     &api_key_input_state,
                                                     cx,
                                                 );
+                                                show_save_result(&save_error, result, cx);
+                                            },
+                                        )
+                                        .detach();
+                                }
+
+                                for params_input in [
+                                    &headers_input,
+                                    &secret_headers_input,
+                                    &query_input,
+                                    &secret_query_input,
+                                ] {
+                                    let managers = managers.clone();
+                                    let provider_id = provider_id.clone();
+                                    let params_input_states = params_input_states.clone();
+                                    let save_error = save_error.clone();
+
+                                    window
+                                        .on_focus_out(
+                                            &params_input.focus_handle(cx),
+                                            cx,
+                                            move |_event, _window, cx| {
+                                                let result = save_provider_params(
+                                                    &managers,
+                                                    &provider_id,
+                                                    &params_input_states,
+                                                    cx,
+                                                );
+                                                show_save_result(&save_error, result, cx);
+                                            },
+                                        )
+                                        .detach();
+                                }
+
+                                for options_input in
+                                    [&api_version_input, &deployments_input, &username_input]
+                                {
+                                    let managers = managers.clone();
+                                    let provider_id = provider_id.clone();
+                                    let options_input_states = options_input_states.clone();
+                                    let save_error = save_error.clone();
+
+                                    window
+                                        .on_focus_out(
+                                            &options_input.focus_handle(cx),
+                                            cx,
+                                            move |_event, _window, cx| {
+                                                let result = save_provider_options(
+                                                    &managers,
+                                                    &provider_id,
+                                                    &options_input_states,
+                                                    cx,
+                                                );
+                                                show_save_result(&save_error, result, cx);
                                             },
                                         )
                                         .detach();
@@ -326,26 +644,63 @@ impl RenderOnce for ProviderSettings {
                                     let provider_id = provider_id.clone();
                                     let url_input_state = url_input_state.clone();
                                     let api_key_input_state = api_key_input_state.clone();
+                                    let params_input_states = params_input_states.clone();
+                                    let options_input_states = options_input_states.clone();
 
                                     window.on_window_should_close(cx, move |_window, cx| {
-                                        save_provider_url(
-                                            &managers,
-                                            &provider_id,
-                                            &url_input_state,
-                                            cx,
-                                        );
-                                        save_provider_api_key(
-                                            &managers,
-                                            &provider_id,
-                                            &api_key_input_state,
-                                            cx,
-                                        );
+                                        let results = [
+                                            save_provider_url(
+                                                &managers,
+                                                &provider_id,
+                                                &url_input_state,
+                                                cx,
+                                            ),
+                                            save_provider_api_key(
+                                                &managers,
+                                                &provider_id,
+                                                &api_key_input_state,
+                                                cx,
+                                            ),
+                                            save_provider_params(
+                                                &managers,
+                                                &provider_id,
+                                                &params_input_states,
+                                                cx,
+                                            ),
+                                            save_provider_options(
+                                                &managers,
+                                                &provider_id,
+                                                &options_input_states,
+                                                cx,
+                                            ),
+                                        ];
+                                        for err in results.into_iter().filter_map(Result::err) {
+                                            tracing::error!(
+                                                "failed to save provider settings: {err}"
+                                            );
+                                        }
                                         true
                                     });
                                 }
                             },
                         );
 
+                        let field = |label: &'static str, input: Input| {
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(padding / 1.5)
+                                .child(
+                                    div()
+                                        .text_size(text_caption_size)
+                                        .font_weight(FontWeight::SEMIBOLD)
+                                        .text_color(primary_text_color)
+                                        .line_height(relative(1.))
+                                        .child(label),
+                                )
+                                .child(input)
+                        };
+
                         this.child(
                             div()
                                 .w_full()
@@ -365,7 +720,10 @@ impl RenderOnce for ProviderSettings {
                                                 .font_weight(FontWeight::SEMIBOLD)
                                                 .text_color(primary_text_color)
                                                 .line_height(relative(1.))
-                                                .child("URL"),
+                                                .child(match is_azure {
+                                                    true => "Resource endpoint",
+                                                    false => "URL",
+                                                }),
                                         )
                                         .child(url_input),
                                 )
@@ -380,10 +738,66 @@ impl RenderOnce for ProviderSettings {
                                                 .font_weight(FontWeight::SEMIBOLD)
                                                 .text_color(primary_text_color)
                                                 .line_height(relative(1.))
-                                                .child("API Key"),
+                                                .child(match is_ollama {
+                                                    true => "Token or password",
+                                                    false => "API Key",
+                                                }),
                                         )
                                         .child(api_key_input),
-                                ),
+                                )
+                                .when(is_ollama, |this| {
+                                    this.child(field("Username, for basic auth", username_input))
+                                })
+                                .child(field("Headers, one per line", headers_input))
+                                .child(field(
+                                    "Secret headers, kept in the keyring",
+                                    secret_headers_input,
+                                ))
+                                .child(field("Query parameters, one per line", query_input))
+                                .child(field(
+                                    "Secret query parameters, kept in the keyring",
+                                    secret_query_input,
+                                ))
+                                .when(is_azure, |this| {
+                                    this.child(
+                                        div()
+                                            .flex()
+                                            .flex_col()
+                                            .gap(padding / 1.5)
+                                            .child(
+                                                div()
+                                                    .text_size(text_caption_size)
+                                                    .font_weight(FontWeight::SEMIBOLD)
+                                                    .text_color(primary_text_color)
+                                                    .line_height(relative(1.))
+                                                    .child("API version"),
+                                            )
+                                            .child(api_version_input),
+                                    )
+                                })
+                                .when(is_azure, |this| {
+                                    this.child(
+                                        div()
+                                            .flex()
+                                            .flex_col()
+                                            .gap(padding / 1.5)
+                                            .child(
+                                                div()
+                                                    .text_size(text_caption_size)
+                                                    .font_weight(FontWeight::SEMIBOLD)
+                                                    .text_color(primary_text_color)
+                                                    .line_height(relative(1.))
+                                                    .child("Deployments, one per line"),
+                                            )
+                                            .child(deployments_input),
+                                    )
+                                })
+                                .children(save_error_text.map(|error| {
+                                    min_w0_wrapper()
+                                        .text_size(text_caption_size)
+                                        .text_color(secondary_text_color)
+                                        .child(error)
+                                })),
                         )
                     }),
             );
@@ -410,3 +824,32 @@ impl RenderOnce for ProviderSettings {
 fn divider(color: impl Into<Fill>) -> Div {
     div().w(relative(1.)).h(px(1.)).min_h(px(1.)).bg(color)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_round_trip() {
+        let headers = "X-Tenant: acme\nX-Trace:  on \n\nnot a header\n: no name";
+        let parsed = parse_params(headers, HEADER_SEPARATOR, false);
+
+        assert_eq!(
+            parsed
+                .iter()
+                .map(|param| (param.name.as_str(), param.value.as_str(), param.secret))
+                .collect::<Vec<_>>(),
+            [("X-Tenant", "acme", false), ("X-Trace", "on", false)]
+        );
+        assert_eq!(
+            format_params(&parsed, HEADER_SEPARATOR, false),
+            "X-Tenant: acme\nX-Trace: on"
+        );
+        assert_eq!(format_params(&parsed, HEADER_SEPARATOR, true), "");
+
+        let query = "token=a=b\nregion=eu";
+        let parsed = parse_params(query, QUERY_SEPARATOR, true);
+        assert_eq!(parsed[0].value, "a=b");
+        assert_eq!(format_params(&parsed, QUERY_SEPARATOR, true), query);
+    }
+}
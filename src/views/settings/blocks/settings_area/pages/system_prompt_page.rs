@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use gpui::{AppContext, ElementId, Overflow, PointRefinement, div, prelude::*, px};
+use gpui_tesserae::{
+    ElementIdExt,
+    components::{Button, ButtonVariant, Input},
+    primitives::input::InputState,
+};
+use smol::lock::RwLock;
+
+use crate::{
+    managers::Managers,
+    views::settings::blocks::settings_area::pages::{
+        render_settings_card, render_settings_page_title,
+    },
+};
+
+#[derive(IntoElement)]
+pub struct SystemPromptPage {
+    id: ElementId,
+    managers: Arc<RwLock<Managers>>,
+}
+
+impl SystemPromptPage {
+    pub fn new(id: impl Into<ElementId>, managers: Arc<RwLock<Managers>>) -> Self {
+        Self {
+            id: id.into(),
+            managers,
+        }
+    }
+}
+
+impl RenderOnce for SystemPromptPage {
+    fn render(self, window: &mut gpui::Window, cx: &mut gpui::App) -> impl IntoElement {
+        let default_system_prompt = self
+            .managers
+            .read_blocking()
+            .settings
+            .default_system_prompt
+            .read(cx)
+            .clone();
+
+        let input_state =
+            window.use_keyed_state(self.id.with_suffix("state:input"), cx, |_window, cx| {
+                cx.new(|cx| {
+                    InputState::new(cx).initial_value(default_system_prompt.unwrap_or_default())
+                })
+            });
+        let input_state = input_state.read(cx).clone();
+
+        let save = {
+            let managers = self.managers.clone();
+            let input_state = input_state.clone();
+
+            move |cx: &mut gpui::App| {
+                let system_prompt = input_state.read(cx).value().to_string();
+                let result = managers
+                    .read_blocking()
+                    .settings
+                    .set_default_system_prompt(cx, Some(system_prompt));
+
+                if let Err(err) = result {
+                    tracing::error!("failed to save default system prompt: {err}");
+                }
+            }
+        };
+
+        let clear_button = {
+            let managers = self.managers.clone();
+            let input_state = input_state.clone();
+
+            Button::new(self.id.with_suffix("clear_btn"))
+                .variant(ButtonVariant::SecondaryGhost)
+                .text("Clear")
+                .on_click(move |_event, _window, cx| {
+                    input_state.update(cx, |input_state, _cx| input_state.clear());
+
+                    let result = managers
+                        .read_blocking()
+                        .settings
+                        .set_default_system_prompt(cx, None);
+
+                    if let Err(err) = result {
+                        tracing::error!("failed to clear default system prompt: {err}");
+                    }
+                })
+        };
+
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(20.))
+            .child(render_settings_page_title(
+                cx,
+                "System Prompt",
+                "Instructions the model is given before every chat.",
+            ))
+            .child(
+                div()
+                    .id(self.id.clone())
+                    .w_full()
+                    .h_full()
+                    .flex()
+                    .flex_col()
+                    .pb(px(20.))
+                    .gap(px(10.))
+                    .map(|mut this| {
+                        this.style().overflow = PointRefinement {
+                            x: None,
+                            y: Some(Overflow::Scroll),
+                        };
+                        this
+                    })
+                    .child(
+                        render_settings_card(
+                            cx,
+                            "Default system prompt",
+                            "Used by chats that don't set their own. Changing it only affects replies generated afterwards.",
+                        )
+                        .child(
+                            Input::new(self.id.with_suffix("input"), input_state)
+                                .w_full()
+                                .line_clamp(12)
+                                .word_wrap(true)
+                                .placeholder("You are a helpful assistant.")
+                                .on_submit({
+                                    let save = save.clone();
+                                    move |_window, cx| save(cx)
+                                }),
+                        )
+                        .child(
+                            div()
+                                .w_full()
+                                .flex()
+                                .flex_row()
+                                .justify_end()
+                                .gap(px(5.))
+                                .child(clear_button)
+                                .child(
+                                    Button::new(self.id.with_suffix("save_btn"))
+                                        .text("Save")
+                                        .on_click(move |_event, _window, cx| save(cx)),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
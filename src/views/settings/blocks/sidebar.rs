@@ -17,6 +17,10 @@ use crate::{assets::AstrumIconKind, managers::Managers};
 const SETTING_PAGES: &[(AstrumIconKind, &str)] = &[
     (AstrumIconKind::Key, "Providers"),
     (AstrumIconKind::Title, "Chat Titles"),
+    (AstrumIconKind::Edit, "System Prompt"),
+    (AstrumIconKind::Chat, "Assistants"),
+    (AstrumIconKind::Download, "Export"),
+    (AstrumIconKind::Upload, "Import"),
 ];
 
 #[derive(IntoElement)]